@@ -0,0 +1,250 @@
+use crate::config::VideoFormat;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+// 4194304 Hz / 70224 cycles per frame, the delay between two frames in 1/100 s for GIFs.
+const FRAME_CENTISECONDS: f64 = 100.0 * 70224.0 / 4194304.0;
+
+// saves an RGBA frame as a PNG.
+pub fn save_png(path: &Path, frame: &[u8], width: usize, height: usize) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(
+        BufWriter::new(File::create(path)?),
+        width as u32,
+        height as u32,
+    );
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+        .write_header()
+        .and_then(|mut writer| writer.write_image_data(frame))
+        .map_err(io::Error::other)
+}
+
+// scales an RGBA frame up by an integer factor, repeating every pixel.
+pub fn scale(frame: &[u8], width: usize, height: usize, factor: usize) -> Vec<u8> {
+    if factor <= 1 {
+        return frame.to_vec();
+    }
+
+    let mut scaled = Vec::with_capacity(frame.len() * factor * factor);
+    for y in 0..height {
+        let row = &frame[y * width * 4..(y + 1) * width * 4];
+        let start = scaled.len();
+        for pixel in row.chunks(4) {
+            for _ in 0..factor {
+                scaled.extend_from_slice(pixel);
+            }
+        }
+        for _ in 1..factor {
+            scaled.extend_from_within(start..start + width * factor * 4);
+        }
+    }
+    scaled
+}
+
+/* centers an RGBA frame in one of another size, cropping or padding it with black.
+a video keeps the size it started with when the SGB border comes and goes. */
+fn fit(
+    frame: &[u8],
+    (width, height): (usize, usize),
+    (to_width, to_height): (usize, usize),
+) -> Vec<u8> {
+    if (width, height) == (to_width, to_height) {
+        return frame.to_vec();
+    }
+
+    let mut fitted = [0, 0, 0, 0xff].repeat(to_width * to_height);
+    for y in 0..to_height {
+        let from_y = y as isize + (height as isize - to_height as isize) / 2;
+        if from_y < 0 || from_y >= height as isize {
+            continue;
+        }
+        for x in 0..to_width {
+            let from_x = x as isize + (width as isize - to_width as isize) / 2;
+            if from_x < 0 || from_x >= width as isize {
+                continue;
+            }
+            let from = (from_y as usize * width + from_x as usize) * 4;
+            let to = (y * to_width + x) * 4;
+            fitted[to..to + 4].copy_from_slice(&frame[from..from + 4]);
+        }
+    }
+    fitted
+}
+
+enum Writer {
+    // the frame waiting for its delay, and the time it was shown at in 1/100 s.
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        pending: Option<(Vec<u8>, f64)>,
+    },
+    Y4m(BufWriter<File>),
+}
+
+/* records frames into an animated GIF or a Y4M video. frame() is called once for every
+emulated frame, so the video runs at the speed of the game whatever the speed of emulation. */
+pub struct VideoRecorder {
+    pub path: PathBuf,
+    writer: Writer,
+    // size of the frames given to the recorder, and the scale they are written at.
+    width: usize,
+    height: usize,
+    scale: usize,
+    frames: u64,
+}
+
+impl VideoRecorder {
+    pub fn new(
+        path: &Path,
+        format: VideoFormat,
+        width: usize,
+        height: usize,
+        scale: usize,
+    ) -> io::Result<Self> {
+        let file = BufWriter::new(File::create(path)?);
+        let (out_width, out_height) = (width * scale, height * scale);
+
+        let writer = match format {
+            VideoFormat::Gif => {
+                let mut encoder = gif::Encoder::new(file, out_width as u16, out_height as u16, &[])
+                    .map_err(gif_error)?;
+                encoder
+                    .set_repeat(gif::Repeat::Infinite)
+                    .map_err(gif_error)?;
+                Writer::Gif {
+                    encoder,
+                    pending: None,
+                }
+            }
+            VideoFormat::Y4m => {
+                let mut file = file;
+                // the frame rate of the game boy, 4194304 Hz over 70224 cycles per frame.
+                writeln!(
+                    file,
+                    "YUV4MPEG2 W{} H{} F4194304:70224 Ip A1:1 C444",
+                    out_width, out_height
+                )?;
+                Writer::Y4m(file)
+            }
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer,
+            width,
+            height,
+            scale,
+            frames: 0,
+        })
+    }
+
+    // adds the next frame of the game, an RGBA frame of any size.
+    pub fn frame(&mut self, frame: &[u8], size: (usize, usize)) -> io::Result<()> {
+        let frame = fit(frame, size, (self.width, self.height));
+        let frame = scale(&frame, self.width, self.height, self.scale);
+        let (width, height) = (self.width * self.scale, self.height * self.scale);
+        let time = self.frames as f64 * FRAME_CENTISECONDS;
+        self.frames += 1;
+
+        match &mut self.writer {
+            /* gif delays are in 1/100 s and players slow down delays under 2, so frames that
+            would be shown shorter than that are merged into the one before. */
+            Writer::Gif { encoder, pending } => match pending.take() {
+                Some((previous, start)) => {
+                    let delay = (time.round() - start.round()) as u16;
+                    if delay >= 2 {
+                        write_gif(encoder, &previous, width, height, delay)?;
+                        *pending = Some((frame, time));
+                    } else {
+                        *pending = Some((frame, start));
+                    }
+                }
+                None => *pending = Some((frame, time)),
+            },
+            Writer::Y4m(file) => {
+                file.write_all(b"FRAME\n")?;
+                file.write_all(&yuv444(&frame))?;
+            }
+        }
+        Ok(())
+    }
+
+    // writes the last frame and the end of the file. returns the number of frames recorded.
+    pub fn finish(self) -> io::Result<u64> {
+        let (width, height) = (self.width * self.scale, self.height * self.scale);
+        match self.writer {
+            Writer::Gif {
+                mut encoder,
+                pending,
+            } => {
+                if let Some((previous, start)) = pending {
+                    let end = self.frames as f64 * FRAME_CENTISECONDS;
+                    let delay = ((end.round() - start.round()) as u16).max(2);
+                    write_gif(&mut encoder, &previous, width, height, delay)?;
+                }
+                encoder.into_inner()?.flush()?;
+            }
+            Writer::Y4m(mut file) => file.flush()?,
+        }
+        Ok(self.frames)
+    }
+}
+
+/* writes a frame with its own palette. the screen has few colors, so they are indexed
+exactly, frames with more than 256 are quantized. */
+fn write_gif(
+    encoder: &mut gif::Encoder<BufWriter<File>>,
+    frame: &[u8],
+    width: usize,
+    height: usize,
+    delay: u16,
+) -> io::Result<()> {
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut pixels = Vec::with_capacity(width * height);
+    for rgba in frame.chunks(4) {
+        let rgb = [rgba[0], rgba[1], rgba[2]];
+        let index = match palette.iter().position(|&color| color == rgb) {
+            Some(index) => index,
+            None => {
+                palette.push(rgb);
+                palette.len() - 1
+            }
+        };
+        if palette.len() > 256 {
+            break;
+        }
+        pixels.push(index as u8);
+    }
+
+    let mut gif_frame = if palette.len() <= 256 {
+        gif::Frame::from_palette_pixels(width as u16, height as u16, pixels, palette.concat(), None)
+    } else {
+        gif::Frame::from_rgba_speed(width as u16, height as u16, &mut frame.to_vec(), 10)
+    };
+    gif_frame.delay = delay;
+    encoder.write_frame(&gif_frame).map_err(gif_error)
+}
+
+// converts RGBA to the Y, Cb and Cr planes of a 4:4:4 frame, with BT.601 studio range.
+fn yuv444(frame: &[u8]) -> Vec<u8> {
+    let len = frame.len() / 4;
+    let mut planes = vec![0; len * 3];
+    for (i, rgba) in frame.chunks(4).enumerate() {
+        let (r, g, b) = (rgba[0] as f32, rgba[1] as f32, rgba[2] as f32);
+        let y = 16.0 + (65.738 * r + 129.057 * g + 25.064 * b) / 256.0;
+        let cb = 128.0 + (-37.945 * r - 74.494 * g + 112.439 * b) / 256.0;
+        let cr = 128.0 + (112.439 * r - 94.154 * g - 18.285 * b) / 256.0;
+        planes[i] = y.round() as u8;
+        planes[len + i] = cb.round() as u8;
+        planes[len * 2 + i] = cr.round() as u8;
+    }
+    planes
+}
+
+fn gif_error(e: gif::EncodingError) -> io::Error {
+    match e {
+        gif::EncodingError::Io(e) => e,
+        e => io::Error::other(e),
+    }
+}
@@ -0,0 +1,155 @@
+use crate::system::mmu::Patch;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/* a cheat of the cheat file of a game. code is a Game Genie code (ABC-DEF or ABC-DEF-GHI)
+or a GameShark code (01VVAAAA), several codes are joined by +. */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Cheat {
+    pub name: String,
+    pub code: String,
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+pub enum Code {
+    // replaces a byte read from ROM, if it is the compare value.
+    Genie(Patch),
+    // writes a byte to memory every frame.
+    Shark { address: u16, value: u8 },
+}
+
+// decodes every code of a cheat.
+pub fn parse(code: &str) -> Result<Vec<Code>, String> {
+    code.split('+')
+        .map(|code| {
+            let digits: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+            match digits.len() {
+                7 | 11 if digits.contains('-') => genie(&digits),
+                8 => shark(&digits),
+                _ => Err(format!(
+                    "'{}' is not a Game Genie or GameShark code",
+                    code.trim()
+                )),
+            }
+        })
+        .collect()
+}
+
+/* ABC-DEF-GHI. AB is the new value and FCDE the address, with F inverted. GI is the compare
+value, rotated by 2 and xored with 0xba, H is not used. */
+fn genie(code: &str) -> Result<Code, String> {
+    let nibbles = code
+        .chars()
+        .filter(|&c| c != '-')
+        .map(|c| c.to_digit(16).map(|d| d as u16))
+        .collect::<Option<Vec<u16>>>()
+        .ok_or(format!("'{}' has a digit that is not hex", code))?;
+    if nibbles.len() != 6 && nibbles.len() != 9 {
+        return Err(format!("'{}' is not a Game Genie code", code));
+    }
+
+    let value = ((nibbles[0] << 4) | nibbles[1]) as u8;
+    let address = ((nibbles[5] ^ 0xf) << 12) | (nibbles[2] << 8) | (nibbles[3] << 4) | nibbles[4];
+    if address > 0x7fff {
+        return Err(format!("'{}' is not an address in ROM", code));
+    }
+    let compare = match nibbles.len() {
+        9 => Some((((nibbles[6] << 4) | nibbles[8]) as u8).rotate_right(2) ^ 0xba),
+        _ => None,
+    };
+
+    Ok(Code::Genie(Patch {
+        address,
+        value,
+        compare,
+    }))
+}
+
+// TTVVAAAA, the type, the value and the address with its low byte first. only type 01, a write
+// to the memory mapped at the address, is supported.
+fn shark(code: &str) -> Result<Code, String> {
+    let number =
+        u32::from_str_radix(code, 16).map_err(|_| format!("'{}' is not a hex number", code))?;
+    if number >> 24 != 0x01 {
+        return Err(format!(
+            "'{}' has type {:02X}, only GameShark codes of type 01 are supported",
+            code,
+            number >> 24
+        ));
+    }
+    // writes to ROM would switch banks instead.
+    let address = ((number & 0xffff) as u16).swap_bytes();
+    if address < 0x8000 {
+        return Err(format!("'{}' is not an address in RAM", code));
+    }
+    Ok(Code::Shark {
+        address,
+        value: (number >> 16) as u8,
+    })
+}
+
+// the cheats of a game. a missing file has none.
+pub fn load(path: &Path) -> io::Result<Vec<Cheat>> {
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    serde_yml::from_str(&fs::read_to_string(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+pub fn save(path: &Path, cheats: &[Cheat]) -> io::Result<()> {
+    let text = serde_yml::to_string(cheats).map_err(io::Error::other)?;
+    fs::write(path, text)
+}
+
+/* reads the cheats of a .cht file, the format of RetroArch:
+cheats = 1
+cheat0_desc = "Infinite Lives"
+cheat0_code = "00A-17B-C49"
+cheat0_enable = false */
+pub fn import(path: &Path) -> io::Result<Vec<Cheat>> {
+    let text = fs::read_to_string(path)?;
+    let mut cheats: Vec<Cheat> = Vec::new();
+
+    for line in text.lines() {
+        let (key, value) = match line.split_once('=') {
+            Some((key, value)) => (key.trim(), value.trim().trim_matches('"')),
+            None => continue,
+        };
+        let (index, field) = match key
+            .strip_prefix("cheat")
+            .and_then(|rest| rest.split_once('_'))
+            .and_then(|(index, field)| Some((index.parse::<usize>().ok()?, field)))
+        {
+            // a limit, so a broken index does not fill memory.
+            Some(entry) if entry.0 < 1000 => entry,
+            _ => continue,
+        };
+
+        while cheats.len() <= index {
+            cheats.push(Cheat {
+                name: format!("Cheat {}", cheats.len() + 1),
+                code: String::new(),
+                enabled: false,
+            });
+        }
+        match field {
+            "desc" => cheats[index].name = value.to_string(),
+            "code" => cheats[index].code = value.to_string(),
+            "enable" => cheats[index].enabled = value == "true",
+            _ => (),
+        }
+    }
+
+    cheats.retain(|cheat| !cheat.code.is_empty());
+    if cheats.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "no cheats in the file",
+        ));
+    }
+    Ok(cheats)
+}
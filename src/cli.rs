@@ -0,0 +1,162 @@
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+use std::path::PathBuf;
+
+use crate::{blargg, doctor, gbs, profile, sm83};
+
+// the tools that run without a window. without a subcommand the emulator opens as usual.
+fn command() -> Command {
+    Command::new("kirboy")
+        .about("A GameBoy (DMG-01) Emulator.")
+        .subcommand(
+            Command::new("trace-diff")
+                .about("Runs a ROM without a window and compares every instruction with a gameboy-doctor log")
+                .arg(Arg::new("rom").required(true).value_parser(value_parser!(PathBuf)))
+                .arg(Arg::new("log").required(true).value_parser(value_parser!(PathBuf)))
+                .arg(
+                    Arg::new("context")
+                        .long("context")
+                        .help("Instructions shown before the one that differs")
+                        .default_value("10")
+                        .value_parser(value_parser!(usize)),
+                )
+                .arg(
+                    Arg::new("real-ly")
+                        .long("real-ly")
+                        .help("Reads LY from the PPU, instead of the 0x90 gameboy-doctor expects")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("sm83")
+                .about("Runs the SM83 single step tests, JSON files of CPU states before and after an instruction")
+                .arg(
+                    Arg::new("paths")
+                        .help("Test files, or folders of them")
+                        .required(true)
+                        .num_args(1..)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("verbose")
+                        .long("verbose")
+                        .short('v')
+                        .help("Shows every failed test, not only the first of each file")
+                        .action(ArgAction::SetTrue),
+                ),
+        )
+        .subcommand(
+            Command::new("blargg")
+                .about("Runs blargg's test ROMs without a window and shows the result each reports")
+                .arg(
+                    Arg::new("roms")
+                        .required(true)
+                        .num_args(1..)
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("frames")
+                        .long("frames")
+                        .help("Frames a ROM may run before it counts as failed, 60 are a second")
+                        .default_value("3600")
+                        .value_parser(value_parser!(u64)),
+                ),
+        )
+        .subcommand(
+            Command::new("profile")
+                .about("Runs a ROM without a window and shows where the CPU spends its cycles")
+                .arg(Arg::new("rom").required(true).value_parser(value_parser!(PathBuf)))
+                .arg(
+                    Arg::new("frames")
+                        .long("frames")
+                        .help("Frames to run, 60 are a second")
+                        .default_value("600")
+                        .value_parser(value_parser!(u64)),
+                )
+                .arg(
+                    Arg::new("top")
+                        .long("top")
+                        .help("Entries shown in each list")
+                        .default_value("20")
+                        .value_parser(value_parser!(usize)),
+                ),
+        )
+        .subcommand(
+            Command::new("gbs")
+                .about("Plays a GBS music file, or renders one of its tracks to a WAV file")
+                .arg(Arg::new("file").required(true).value_parser(value_parser!(PathBuf)))
+                .arg(
+                    Arg::new("track")
+                        .long("track")
+                        .help("Track to start with, from 1, instead of the first song of the file")
+                        .value_parser(value_parser!(u8).range(1..)),
+                )
+                .arg(
+                    Arg::new("wav")
+                        .long("wav")
+                        .help("Renders the track to this WAV file without playing it")
+                        .value_parser(value_parser!(PathBuf)),
+                )
+                .arg(
+                    Arg::new("seconds")
+                        .long("seconds")
+                        .help("Length of the rendered track")
+                        .default_value("150")
+                        .value_parser(value_parser!(u32)),
+                ),
+        )
+}
+
+// runs the subcommand if there is one, returning the exit code.
+pub fn run() -> Option<i32> {
+    let matches = command().get_matches();
+    let (name, args) = matches.subcommand()?;
+    let result = match name {
+        "trace-diff" => trace_diff(args),
+        "profile" => profile::run(
+            args.get_one::<PathBuf>("rom").unwrap(),
+            *args.get_one::<u64>("frames").unwrap(),
+            *args.get_one::<usize>("top").unwrap(),
+        ),
+        "sm83" => {
+            let paths: Vec<PathBuf> = args
+                .get_many::<PathBuf>("paths")
+                .unwrap()
+                .cloned()
+                .collect();
+            sm83::run(&paths, args.get_flag("verbose"))
+        }
+        "blargg" => {
+            let roms: Vec<PathBuf> = args.get_many::<PathBuf>("roms").unwrap().cloned().collect();
+            blargg::run(&roms, *args.get_one::<u64>("frames").unwrap())
+        }
+        "gbs" => play_gbs(args),
+        _ => return None,
+    };
+    Some(match result {
+        Ok(true) => 0,
+        Ok(false) => 1,
+        Err(e) => {
+            println!("{}", e);
+            2
+        }
+    })
+}
+
+fn trace_diff(args: &ArgMatches) -> Result<bool, String> {
+    doctor::run(
+        args.get_one::<PathBuf>("rom").unwrap(),
+        args.get_one::<PathBuf>("log").unwrap(),
+        *args.get_one::<usize>("context").unwrap(),
+        !args.get_flag("real-ly"),
+    )
+}
+
+fn play_gbs(args: &ArgMatches) -> Result<bool, String> {
+    let file = args.get_one::<PathBuf>("file").unwrap();
+    let track = args.get_one::<u8>("track").map(|track| track - 1);
+    match args.get_one::<PathBuf>("wav") {
+        Some(wav) => gbs::render(file, track, wav, *args.get_one::<u32>("seconds").unwrap())?,
+        None => gbs::play(file, track)?,
+    }
+    Ok(true)
+}
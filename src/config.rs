@@ -1,22 +1,287 @@
 use dirs::config_local_dir;
-use serde::{Deserialize, Serialize};
-use serde_yml;
+use notify_rust::Notification;
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_yml::{self, Mapping, Value};
 use std::{
     fs::{self},
-    path::Path,
+    io,
+    path::{Path, PathBuf},
 };
 
-use crate::system::joypad::Input;
+use crate::keybind::{bound, bound_holding, held, Binding, Key};
+use crate::renderer::{Shader, SHADER_LIST};
+use crate::system::{
+    apu::Mixer,
+    joypad::{Input, Opposing},
+};
+
+// the version written by this build. older configs are upgraded by MIGRATIONS when loaded.
+pub const CONFIG_VERSION: u32 = 2;
 
+// MIGRATIONS[n] upgrades a config of version n to version n + 1. configs without a version are 0.
+const MIGRATIONS: [fn(&mut Mapping); CONFIG_VERSION as usize] = [migrate_bindings, migrate_color];
+
+/* every field falls back to the default config when it is missing, so a partial config
+only needs the fields that differ from it. */
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Config {
-    pub color: Color,
+    pub version: u32,
+    pub palettes: Palettes,
     pub keybinds: Keybinds,
+    pub hotkeys: Hotkeys,
     pub audio: bool,
+    pub sync: SyncMode,
+    // also records each channel to its own file when recording audio.
+    pub record_stems: bool,
+    pub capture: Capture,
+    pub trace: Trace,
+    pub mixer: Mixer,
+    // name of the shader the screen is drawn with.
+    pub shader: String,
+    // emulation speed, 1 is full speed.
+    pub speed: f32,
+    pub macros: Vec<Macro>,
+    // allow, last or neutral when opposing directions are held together.
+    pub opposing: Opposing,
+    // SGB palettes, border and multiplayer for cartridges that support them.
+    pub sgb: bool,
+    // per-game overrides, layered on top of the rest of the config when a matching ROM is loaded.
+    pub profiles: Vec<Profile>,
     pub debug: bool,
 }
 
+/* overrides for a game. a ROM matches when every key that is given matches, the title of
+the cartridge header or the hashes of the whole file. config holds any fields of the config,
+like palette, keybinds, shader or speed, and replaces them while the game is running. */
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub crc32: Option<String>,
+    #[serde(default)]
+    pub sha1: Option<String>,
+    #[serde(default)]
+    pub config: Value,
+}
+
+impl Profile {
+    pub fn matches(&self, title: &str, crc32: &str, sha1: &str) -> bool {
+        let keys = [
+            (&self.title, title.trim()),
+            (&self.crc32, crc32),
+            (&self.sha1, sha1),
+        ];
+        keys.iter().any(|(key, _)| key.is_some())
+            && keys.iter().all(|(key, value)| match key {
+                Some(key) => key.trim().eq_ignore_ascii_case(value),
+                None => true,
+            })
+    }
+}
+
+// which profile is layered on top of the config.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProfileChoice {
+    // the first profile matching the ROM.
+    Auto,
+    // no profile, only the global config.
+    Global,
+    Named(String),
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config::new()
+    }
+}
+
+// keys for emulator actions, checked before the button bindings.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Hotkeys {
+    #[serde(deserialize_with = "bindings")]
+    pub save_state: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub load_state: Vec<String>,
+    // runs unthrottled while held.
+    #[serde(deserialize_with = "bindings")]
+    pub fast_forward: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub pause: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub screenshot: Vec<String>,
+    // starts or stops recording a video.
+    #[serde(deserialize_with = "bindings")]
+    pub record_video: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub reset: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub settings: Vec<String>,
+}
+
+impl Default for Hotkeys {
+    fn default() -> Self {
+        Self {
+            save_state: keys(&["f5"]),
+            load_state: keys(&["f7"]),
+            fast_forward: keys(&["tab"]),
+            pause: keys(&["p"]),
+            screenshot: keys(&["f12"]),
+            record_video: keys(&["f11"]),
+            reset: keys(&["f9"]),
+            settings: keys(&["escape"]),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Hotkey {
+    SaveState,
+    LoadState,
+    FastForward,
+    Pause,
+    Screenshot,
+    RecordVideo,
+    Reset,
+    Settings,
+}
+
+// a named sequence of button presses, played back frame by frame when one of its keys is pressed.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Macro {
+    pub name: String,
+    pub keys: Vec<String>,
+    pub steps: Vec<MacroStep>,
+}
+
+// buttons held for a number of frames. no buttons is a pause.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct MacroStep {
+    #[serde(default)]
+    pub buttons: Vec<Input>,
+    pub frames: u32,
+}
+
+impl Macro {
+    // the joypad state for every frame of the macro.
+    pub fn frames(&self) -> Vec<u8> {
+        self.steps
+            .iter()
+            .flat_map(|step| {
+                let state = step
+                    .buttons
+                    .iter()
+                    .fold(0, |state, input| state | input.bit());
+                std::iter::repeat_n(state, step.frames as usize)
+            })
+            .collect()
+    }
+}
+
+// what the emulator uses to keep itself running at the correct speed.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncMode {
+    // sleeps against the wall clock.
+    Time,
+    // follows the fill level of the audio buffer, so the sound card clock drives emulation.
+    #[default]
+    Audio,
+}
+
+// how screenshots and videos are saved.
 #[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct Capture {
+    // integer scale of screenshots and videos, 1 is the native 160x144.
+    pub scale: u32,
+    // screenshots are taken from the window, with the shader applied, instead of the framebuffer.
+    pub shader: bool,
+    pub video: VideoFormat,
+}
+
+impl Default for Capture {
+    fn default() -> Self {
+        Self {
+            scale: 1,
+            shader: false,
+            video: VideoFormat::Gif,
+        }
+    }
+}
+
+// gif is an animated image, y4m is raw video with the audio recorded to a WAV next to it.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoFormat {
+    Gif,
+    Y4m,
+}
+
+/* the trace log of the CPU, written to the traces folder. only instructions with a PC from
+from to to, both included, are logged, and only in ROM bank bank if it is given. */
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct Trace {
+    // starts tracing when a ROM is loaded, it can also be started from the Debug menu.
+    pub enabled: bool,
+    pub format: TraceFormat,
+    pub from: u16,
+    pub to: u16,
+    pub bank: Option<usize>,
+}
+
+impl Default for Trace {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            format: TraceFormat::Doctor,
+            from: 0x0000,
+            to: 0xffff,
+            bank: None,
+        }
+    }
+}
+
+// doctor is the format of gameboy-doctor, mnemonic adds the bank and the instruction.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TraceFormat {
+    Doctor,
+    Mnemonic,
+}
+
+// the palettes of the background and window, and of the sprites using OBP0 and OBP1.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct Palettes {
+    pub bg: Color,
+    pub obj0: Color,
+    pub obj1: Color,
+    // colors the layers from the title of the game when it is loaded, instead of the ones above.
+    pub auto: bool,
+    // palettes imported from .pal and .gpl files, picked like the presets.
+    pub library: Vec<NamedPalette>,
+}
+
+impl Default for Palettes {
+    fn default() -> Self {
+        Config::new().palettes
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct NamedPalette {
+    pub name: String,
+    pub colors: Color,
+}
+
+// the four shades of a palette, from lightest to darkest.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(default)]
 pub struct Color {
     pub id0: [u8; 3],
     pub id1: [u8; 3],
@@ -24,114 +289,632 @@ pub struct Color {
     pub id3: [u8; 3],
 }
 
+// speeds that can be picked in the settings.
+pub const SPEEDS: [f32; 7] = [0.25, 0.5, 1.0, 1.5, 2.0, 3.0, 4.0];
+
+/* every button can have several bindings, keyboard keys or gamepad inputs.
+gamepad inputs are named pad:buttonN, and pad:axisN+ / pad:axisN- for an axis pushed past the deadzone. */
 #[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(default)]
 pub struct Keybinds {
-    pub up: String,
-    pub down: String,
-    pub left: String,
-    pub right: String,
-    pub a: String,
-    pub b: String,
-    pub start: String,
-    pub select: String,
+    #[serde(deserialize_with = "bindings")]
+    pub up: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub down: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub left: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub right: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub a: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub b: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub start: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub select: Vec<String>,
+    // autofire, pressing the button turbo_rate times per second while held.
+    #[serde(deserialize_with = "bindings")]
+    pub turbo_a: Vec<String>,
+    #[serde(deserialize_with = "bindings")]
+    pub turbo_b: Vec<String>,
+    pub turbo_rate: u32,
+    // how far an axis has to be pushed (0 - 1) before it counts as pressed.
+    pub deadzone: f32,
+}
+
+impl Default for Keybinds {
+    fn default() -> Self {
+        Config::new().keybinds
+    }
+}
+
+impl Default for Color {
+    fn default() -> Self {
+        Config::new().palettes.bg
+    }
+}
+
+// a single key is taken as a list of one, as written in version 0 configs.
+fn bindings<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Bindings {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match Bindings::deserialize(deserializer)? {
+        Bindings::One(key) => vec![key],
+        Bindings::Many(keys) => keys,
+    })
+}
+
+fn keys(keys: &[&str]) -> Vec<String> {
+    keys.iter().map(|key| key.to_string()).collect()
+}
+
+// copies the fields of overrides into value, going into mappings that both have.
+fn merge(value: &mut Value, overrides: &Value) {
+    match (value, overrides) {
+        (Value::Mapping(value), Value::Mapping(overrides)) => {
+            for (key, field) in overrides.iter() {
+                match value.get_mut(key) {
+                    Some(old) => merge(old, field),
+                    None => {
+                        value.insert(key.clone(), field.clone());
+                    }
+                }
+            }
+        }
+        (value, overrides) => *value = overrides.clone(),
+    }
+}
+
+// the fields of value that are not the same in base, the opposite of merge.
+fn diff(base: &Value, value: &Value) -> Option<Value> {
+    match (base, value) {
+        (Value::Mapping(base), Value::Mapping(value)) => {
+            let mut changed = Mapping::new();
+            for (key, field) in value.iter() {
+                let field = match base.get(key) {
+                    Some(old) => diff(old, field),
+                    None => Some(field.clone()),
+                };
+                if let Some(field) = field {
+                    changed.insert(key.clone(), field);
+                }
+            }
+            match changed.is_empty() {
+                true => None,
+                false => Some(Value::Mapping(changed)),
+            }
+        }
+        (base, value) if base == value => None,
+        (_, value) => Some(value.clone()),
+    }
+}
+
+// version 1 -> 2: the single palette becomes the one of every layer, in profiles too.
+fn migrate_color(config: &mut Mapping) {
+    if let Some(color) = config.remove("color") {
+        let mut palettes = Mapping::new();
+        for layer in ["bg", "obj0", "obj1"] {
+            palettes.insert(layer.into(), color.clone());
+        }
+        config.insert("palettes".into(), Value::Mapping(palettes));
+    }
+
+    if let Some(Value::Sequence(profiles)) = config.get_mut("profiles") {
+        for profile in profiles.iter_mut() {
+            if let Some(Value::Mapping(config)) = profile.get_mut("config") {
+                migrate_color(config);
+            }
+        }
+    }
+}
+
+// version 0 -> 1: every button had a single key, it is now a list of bindings.
+fn migrate_bindings(config: &mut Mapping) {
+    let keybinds = match config.get_mut("keybinds").and_then(Value::as_mapping_mut) {
+        Some(keybinds) => keybinds,
+        None => return,
+    };
+
+    for (_, binding) in keybinds.iter_mut() {
+        if binding.is_string() {
+            *binding = Value::Sequence(vec![binding.clone()]);
+        }
+    }
 }
 
 impl Config {
     pub fn new() -> Config {
+        let grayscale = Color {
+            id0: [0xff, 0xff, 0xff], // white
+            id1: [0xcc, 0xcc, 0xcc], // light gray
+            id2: [0x77, 0x77, 0x77], // dark gray
+            id3: [0x00, 0x00, 0x00], // black
+        };
         Config {
+            version: CONFIG_VERSION,
             debug: false,
             audio: true,
-            color: Color {
-                id0: [0xff, 0xff, 0xff], // white
-                id1: [0xcc, 0xcc, 0xcc], // light gray
-                id2: [0x77, 0x77, 0x77], // dark gray
-                id3: [0x00, 0x00, 0x00], //
+            sync: SyncMode::Audio,
+            record_stems: false,
+            capture: Capture::default(),
+            trace: Trace::default(),
+            mixer: Mixer::default(),
+            shader: "base".to_string(),
+            speed: 1.0,
+            macros: Vec::new(),
+            hotkeys: Hotkeys::default(),
+            opposing: Opposing::default(),
+            profiles: Vec::new(),
+            sgb: true,
+            palettes: Palettes {
+                bg: grayscale,
+                obj0: grayscale,
+                obj1: grayscale,
+                auto: false,
+                library: Vec::new(),
             },
+            // gamepad defaults follow the xbox layout, with both the d-pad and the left stick.
             keybinds: Keybinds {
-                up: "up".to_string(),
-                down: "down".to_string(),
-                left: "left".to_string(),
-                right: "right".to_string(),
-                a: "z".to_string(),
-                b: "x".to_string(),
-                start: "enter".to_string(),
-                select: "shift".to_string(),
+                up: keys(&["up", "pad:axis7-", "pad:axis1-"]),
+                down: keys(&["down", "pad:axis7+", "pad:axis1+"]),
+                left: keys(&["left", "pad:axis6-", "pad:axis0-"]),
+                right: keys(&["right", "pad:axis6+", "pad:axis0+"]),
+                a: keys(&["z", "pad:button0"]),
+                b: keys(&["x", "pad:button1"]),
+                start: keys(&["enter", "pad:button7"]),
+                select: keys(&["shift", "pad:button6"]),
+                turbo_a: keys(&["a", "pad:button2"]),
+                turbo_b: keys(&["s", "pad:button3"]),
+                turbo_rate: 10,
+                deadzone: 0.4,
             },
         }
     }
 
+    // the profile picked by choice for a ROM, if any.
+    pub fn profile(
+        &self,
+        choice: &ProfileChoice,
+        title: &str,
+        crc32: &str,
+        sha1: &str,
+    ) -> Option<&Profile> {
+        match choice {
+            ProfileChoice::Auto => self
+                .profiles
+                .iter()
+                .find(|profile| profile.matches(title, crc32, sha1)),
+            ProfileChoice::Global => None,
+            ProfileChoice::Named(name) => self.profiles.iter().find(|p| &p.name == name),
+        }
+    }
+
+    // the config with the fields of the profile replaced. lists are replaced, mappings are merged.
+    pub fn with_profile(&self, profile: &Profile) -> Result<Config, String> {
+        let mut value = serde_yml::to_value(self).map_err(|e| e.to_string())?;
+        if let Value::Mapping(overrides) = &profile.config {
+            // profiles cannot hold other profiles.
+            let mut overrides = overrides.clone();
+            overrides.remove("profiles");
+            merge(&mut value, &Value::Mapping(overrides));
+        }
+        serde_yml::from_value(value).map_err(|e| format!("profile {}: {}", profile.name, e))
+    }
+
+    /* stores the fields in which config differs from this one as the overrides of the named profile,
+    so changes made while a profile is active only go to that profile. */
+    pub fn set_profile(&mut self, name: &str, config: &Config) -> Result<(), String> {
+        let mut global = self.clone();
+        global.profiles.clear();
+        let mut config = config.clone();
+        config.profiles.clear();
+
+        let base = serde_yml::to_value(&global).map_err(|e| e.to_string())?;
+        let value = serde_yml::to_value(&config).map_err(|e| e.to_string())?;
+        let overrides = diff(&base, &value).unwrap_or(Value::Mapping(Mapping::new()));
+
+        match self
+            .profiles
+            .iter_mut()
+            .find(|profile| profile.name == name)
+        {
+            Some(profile) => {
+                profile.config = overrides;
+                Ok(())
+            }
+            None => Err(format!("there is no profile named {}", name)),
+        }
+    }
+
     pub fn print(&self) -> String {
         let yaml = serde_yml::to_string(self).unwrap();
         println!("Serialized YAML:\n{}", yaml);
         yaml
     }
 
-    pub fn load() -> Config {
+    fn path() -> PathBuf {
         let mut path = config_local_dir().unwrap();
         path.push("kirboy/config");
-        let file = fs::read_to_string(&path);
-        match file {
-            Err(_) => {
+        path
+    }
+
+    /* reads the config file. older versions are upgraded and written back, after the old file
+    was backed up. a file that cannot be read is backed up and left alone, the defaults are used
+    until it is fixed. */
+    pub fn load() -> Config {
+        let path = Config::path();
+        let text = match fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
                 println!("no file");
                 let new_config = Config::new();
-                let parent_dir = Path::new(&path).parent().unwrap();
-                if !parent_dir.exists() {
-                    fs::create_dir_all(parent_dir).expect("Failed to create directory");
+                new_config.save();
+                return new_config;
+            }
+            // the file is there but can't be read, it is kept like one that can't be parsed.
+            Err(e) => {
+                let mut errors = vec![format!("{:?} could not be read: {}", path, e)];
+                if let Ok(backup) = Config::backup(&path, "broken") {
+                    errors.push(format!("a copy was saved as {:?}", backup));
                 }
-                fs::write(
-                    &path,
-                    serde_yml::to_string(&new_config).expect("config to string"),
-                );
-                new_config
+                errors.push("the default config is used until the file is fixed".to_string());
+                Config::report("Invalid Config", &errors);
+                return Config::new();
             }
+        };
 
-            Ok(_) => match serde_yml::from_str(&file.unwrap()) {
-                Ok(config) => config,
-                Err(_) => {
-                    println!("Invalid Config");
-                    let new_config = Config::new();
-                    let parent_dir = Path::new(&path).parent().unwrap();
-                    if !parent_dir.exists() {
-                        fs::create_dir_all(parent_dir).expect("Failed to create directory");
+        match Config::parse(&text) {
+            Ok((config, None)) => {
+                Config::report("Config Error", &config.validate());
+                config
+            }
+            Ok((config, Some(version))) => {
+                match Config::backup(&path, &format!("v{}", version)) {
+                    Ok(backup) => {
+                        println!(
+                            "Config upgraded from version {} to {}, the old one is kept as {:?}",
+                            version, CONFIG_VERSION, backup
+                        );
+                        config.save();
                     }
-                    fs::write(
-                        &path,
-                        serde_yml::to_string(&new_config).expect("config to string"),
-                    );
-                    new_config
+                    Err(e) => println!("Config could not be backed up, not upgrading it: {}", e),
+                }
+                Config::report("Config Error", &config.validate());
+                config
+            }
+            Err(error) => {
+                let mut errors = vec![error];
+                match Config::backup(&path, "broken") {
+                    Ok(backup) => errors.push(format!("a copy was saved as {:?}", backup)),
+                    Err(e) => errors.push(format!("a copy could not be saved: {}", e)),
                 }
+                errors.push("the default config is used until the file is fixed".to_string());
+                Config::report("Invalid Config", &errors);
+                Config::new()
+            }
+        }
+    }
+
+    // the names of the profiles in the config file, without reporting errors in it.
+    pub fn profile_names() -> Vec<String> {
+        Config::read()
+            .map(|config| config.profiles.into_iter().map(|p| p.name).collect())
+            .unwrap_or_default()
+    }
+
+    // the config in the file without writing anything, for the menus made before the controller loads it.
+    pub fn read() -> Option<Config> {
+        fs::read_to_string(Config::path())
+            .ok()
+            .and_then(|text| Config::parse(&text).ok())
+            .map(|(config, _)| config)
+    }
+
+    /* parses a config, upgrading it first when it is older than CONFIG_VERSION.
+    returns the version it was upgraded from. errors name the field and the line. */
+    fn parse(text: &str) -> Result<(Config, Option<u32>), String> {
+        let mut value: Value = serde_yml::from_str(text).map_err(|e| e.to_string())?;
+        if value.is_null() {
+            // an empty file is an empty config.
+            return Ok((Config::new(), None));
+        }
+
+        let version = match value.get("version") {
+            None => 0,
+            Some(version) => match version.as_u64() {
+                Some(version) => version as u32,
+                None => return Err(format!("version: {:?} is not a number", version)),
             },
+        };
+
+        if version >= CONFIG_VERSION {
+            if version > CONFIG_VERSION {
+                println!(
+                    "Config: version {} is newer than this build ({}), unknown fields are ignored",
+                    version, CONFIG_VERSION
+                );
+            }
+            return serde_yml::from_str(text)
+                .map(|config| (config, None))
+                .map_err(|e| e.to_string());
+        }
+
+        let mapping = match value.as_mapping_mut() {
+            Some(mapping) => mapping,
+            None => return Err("the config is not a mapping of fields".to_string()),
+        };
+        for migration in MIGRATIONS[version as usize..].iter() {
+            migration(mapping);
+        }
+        mapping.insert("version".into(), CONFIG_VERSION.into());
+
+        // parsed from text again, so the errors still have a line, of the upgraded config.
+        let upgraded = serde_yml::to_string(&value).map_err(|e| e.to_string())?;
+        serde_yml::from_str(&upgraded)
+            .map(|config| (config, Some(version)))
+            .map_err(|e| format!("{} (in the config upgraded from version {})", e, version))
+    }
+
+    // copies the config file next to itself, as config.<suffix>.bak.
+    fn backup(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+        let backup = path.with_extension(format!("{}.bak", suffix));
+        fs::copy(path, &backup)?;
+        Ok(backup)
+    }
+
+    fn report(summary: &str, errors: &[String]) {
+        if errors.is_empty() {
+            return;
+        }
+        for e in errors.iter() {
+            println!("Config: {}", e);
+        }
+        let _ = Notification::new()
+            .summary(summary)
+            .body(&errors.join("\n"))
+            .auto_icon()
+            .show();
+    }
+
+    // writes the config back to the config file.
+    pub fn save(&self) {
+        let path = Config::path();
+        if let Some(parent) = path.parent() {
+            if !parent.exists() {
+                fs::create_dir_all(parent).expect("Failed to create directory");
+            }
+        }
+
+        match serde_yml::to_string(self) {
+            Ok(yaml) => {
+                if let Err(e) = fs::write(&path, yaml) {
+                    println!("Config could not be saved: {}", e);
+                }
+            }
+            Err(e) => println!("Config could not be saved: {}", e),
         }
     }
 
     pub fn open() {
-        let mut path = config_local_dir().unwrap();
-        path.push("kirboy/config");
+        let path = Config::path();
 
         opener::open(&path).unwrap();
     }
 
-    pub fn get_input(&self, key: &String) -> Option<Input> {
-        if key == &self.keybinds.up {
-            Some(Input::Up)
-        } else if key == &self.keybinds.down {
-            Some(Input::Down)
-        } else if key == &self.keybinds.left {
-            Some(Input::Left)
-        } else if key == &self.keybinds.right {
-            Some(Input::Right)
-        } else if key == &self.keybinds.a {
+    // returns a folder inside the config directory, creating it if needed.
+    pub fn dir(name: &str) -> PathBuf {
+        let mut path = config_local_dir().unwrap();
+        path.push("kirboy");
+        path.push(name);
+        if !path.exists() {
+            fs::create_dir_all(&path).expect("Failed to create directory");
+        }
+        path
+    }
+
+    pub fn buttons(&self) -> [(&str, &Vec<String>, Input); 8] {
+        let keybinds = &self.keybinds;
+        [
+            ("up", &keybinds.up, Input::Up),
+            ("down", &keybinds.down, Input::Down),
+            ("left", &keybinds.left, Input::Left),
+            ("right", &keybinds.right, Input::Right),
+            ("a", &keybinds.a, Input::A),
+            ("b", &keybinds.b, Input::B),
+            ("select", &keybinds.select, Input::Select),
+            ("start", &keybinds.start, Input::Start),
+        ]
+    }
+
+    fn hotkeys(&self) -> [(&str, &Vec<String>, Hotkey); 8] {
+        let hotkeys = &self.hotkeys;
+        [
+            ("save_state", &hotkeys.save_state, Hotkey::SaveState),
+            ("load_state", &hotkeys.load_state, Hotkey::LoadState),
+            ("fast_forward", &hotkeys.fast_forward, Hotkey::FastForward),
+            ("pause", &hotkeys.pause, Hotkey::Pause),
+            ("screenshot", &hotkeys.screenshot, Hotkey::Screenshot),
+            ("record_video", &hotkeys.record_video, Hotkey::RecordVideo),
+            ("reset", &hotkeys.reset, Hotkey::Reset),
+            ("settings", &hotkeys.settings, Hotkey::Settings),
+        ]
+    }
+
+    // the bindings of a button.
+    pub fn button_mut(&mut self, input: Input) -> &mut Vec<String> {
+        let keybinds = &mut self.keybinds;
+        match input {
+            Input::Up => &mut keybinds.up,
+            Input::Down => &mut keybinds.down,
+            Input::Left => &mut keybinds.left,
+            Input::Right => &mut keybinds.right,
+            Input::A => &mut keybinds.a,
+            Input::B => &mut keybinds.b,
+            Input::Select => &mut keybinds.select,
+            Input::Start => &mut keybinds.start,
+        }
+    }
+
+    // modifiers bound to buttons on their own, which are held while the other buttons are pressed.
+    fn held_buttons(&self) -> u8 {
+        self.buttons()
+            .iter()
+            .fold(0, |bits, (_, keys, _)| bits | held(keys))
+    }
+
+    pub fn get_input(&self, key: &Key) -> Option<Input> {
+        let held = self.held_buttons();
+        self.buttons()
+            .into_iter()
+            .find(|(_, keys, _)| bound_holding(keys, key, held))
+            .map(|(_, _, input)| input)
+    }
+
+    // the button a key fires with turbo, if it is bound to one.
+    pub fn get_turbo(&self, key: &Key) -> Option<Input> {
+        let held = self.held_buttons();
+        if bound_holding(&self.keybinds.turbo_a, key, held) {
             Some(Input::A)
-        } else if key == &self.keybinds.b {
+        } else if bound_holding(&self.keybinds.turbo_b, key, held) {
             Some(Input::B)
-        } else if key == &self.keybinds.select {
-            Some(Input::Select)
-        } else if key == &self.keybinds.start {
-            Some(Input::Start)
         } else {
             None
         }
     }
+
+    pub fn get_macro(&self, key: &Key) -> Option<&Macro> {
+        self.macros.iter().find(|m| bound(&m.keys, key))
+    }
+
+    pub fn get_hotkey(&self, key: &Key) -> Option<Hotkey> {
+        self.hotkeys()
+            .into_iter()
+            .find(|(_, keys, _)| bound(keys, key))
+            .map(|(_, _, hotkey)| hotkey)
+    }
+
+    /* checks every binding, returning a message for each that can't be parsed and for keys bound
+    to more than one action. invalid bindings are ignored, the rest of the config still applies. */
+    pub fn validate(&self) -> Vec<String> {
+        let mut bindings: Vec<(String, &String)> = Vec::new();
+        for (name, keys, _) in self.buttons() {
+            bindings.extend(keys.iter().map(|key| (format!("keybinds.{}", name), key)));
+        }
+        bindings.extend(
+            (self.keybinds.turbo_a.iter()).map(|key| ("keybinds.turbo_a".to_string(), key)),
+        );
+        bindings.extend(
+            (self.keybinds.turbo_b.iter()).map(|key| ("keybinds.turbo_b".to_string(), key)),
+        );
+        for (name, keys, _) in self.hotkeys() {
+            bindings.extend(keys.iter().map(|key| (format!("hotkeys.{}", name), key)));
+        }
+        for m in self.macros.iter() {
+            bindings.extend(
+                m.keys
+                    .iter()
+                    .map(|key| (format!("macro '{}'", m.name), key)),
+            );
+        }
+
+        let mut errors = Vec::new();
+        // compared after parsing, so "Ctrl+A" and "control+a" are the same key.
+        let mut seen: Vec<(String, Binding)> = Vec::new();
+        for (action, key) in bindings {
+            let binding = match Binding::parse(key) {
+                Ok(binding) => binding,
+                Err(e) => {
+                    errors.push(format!("{}: {}", action, e));
+                    continue;
+                }
+            };
+
+            match seen.iter().find(|(_, other)| *other == binding) {
+                Some((other, _)) => errors.push(format!(
+                    "{}: '{}' is already bound to {}",
+                    action, key, other
+                )),
+                None => seen.push((action, binding)),
+            }
+        }
+
+        if !SHADER_LIST.iter().any(|&s| Shader::name(s) == self.shader) {
+            errors.push(format!("shader: unknown shader '{}'", self.shader));
+        }
+
+        if !(0.1..=8.0).contains(&self.speed) {
+            errors.push(format!("speed: {} is not between 0.1 and 8", self.speed));
+        }
+
+        if !(1..=8).contains(&self.capture.scale) {
+            errors.push(format!(
+                "capture.scale: {} is not between 1 and 8",
+                self.capture.scale
+            ));
+        }
+
+        if self.trace.from > self.trace.to {
+            errors.push(format!(
+                "trace.from: {:#06x} is after trace.to {:#06x}",
+                self.trace.from, self.trace.to
+            ));
+        }
+
+        if !(0.0..=1.0).contains(&self.mixer.master) {
+            errors.push(format!(
+                "mixer.master: {} is not between 0 and 1",
+                self.mixer.master
+            ));
+        }
+
+        if !(0.0..1.0).contains(&self.keybinds.deadzone) {
+            errors.push(format!(
+                "keybinds.deadzone: {} is not between 0 and 1",
+                self.keybinds.deadzone
+            ));
+        }
+
+        // errors of the global config are not repeated for every profile.
+        let global = errors.clone();
+        for (i, profile) in self.profiles.iter().enumerate() {
+            if profile.title.is_none() && profile.crc32.is_none() && profile.sha1.is_none() {
+                errors.push(format!(
+                    "profiles: {} needs a title, crc32 or sha1 to match",
+                    profile.name
+                ));
+            }
+            if self.profiles[..i].iter().any(|p| p.name == profile.name) {
+                errors.push(format!("profiles: {} is defined twice", profile.name));
+            }
+            match &profile.config {
+                Value::Mapping(_) | Value::Null => match self.with_profile(profile) {
+                    Ok(mut config) => {
+                        config.profiles.clear();
+                        errors.extend(
+                            config
+                                .validate()
+                                .into_iter()
+                                .filter(|e| !global.contains(e))
+                                .map(|e| format!("profile {}: {}", profile.name, e)),
+                        )
+                    }
+                    Err(e) => errors.push(e),
+                },
+                _ => errors.push(format!(
+                    "profiles: the config of {} is not a mapping of fields",
+                    profile.name
+                )),
+            }
+        }
+
+        errors
+    }
 }
 
 /*
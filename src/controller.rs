@@ -1,24 +1,80 @@
-use crate::config::Config;
+use crate::capture::{self, VideoRecorder};
+use crate::config::{Config, Hotkey, ProfileChoice, SyncMode, VideoFormat};
 use crate::emulator::{Emulator, EmulatorError};
+use crate::gamepad::{self, GamepadEvent};
+use crate::keybind::{self, Key};
+use crate::movie::{Movie, MovieState};
+use crate::palette;
 use crate::player::{CpalPlayer, Player};
+use crate::settings::{Change, Settings};
+use crate::system::apu::Mixer;
+use crate::system::ppu::{LAYER_OBJ0, LAYER_OBJ1};
+use crate::system::sgb;
+use crate::viewer::{View, Viewer};
 use notify_rust::Notification;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::mpsc::TryRecvError;
 use std::sync::mpsc::{Receiver, SyncSender, TrySendError};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 pub enum ControllerRequest {
-    KeyUp(String),
-    KeyDown(String),
+    KeyUp(Key),
+    KeyDown(Key),
+    Gamepad(GamepadEvent),
     New(PathBuf),
     Exit,
     LoadConfig,
     OpenConfig,
+    Record,
+    RecordVideo,
+    Mixer(MixerRequest),
+    // starts recording a movie, from the save state of the game when set, or stops the recording.
+    RecordMovie(bool),
+    PlayMovie(PathBuf),
+    ExportMovie(PathBuf),
+    Settings,
+    Profile(ProfileChoice),
+    ImportPalette(PathBuf),
+    // opens or closes a window inspecting the emulator.
+    Viewer(View, bool),
+    // a key pressed in the window of a viewer.
+    ViewerKey(View, Key),
+    // starts or stops the trace log of the CPU.
+    Trace(bool),
+    // restarts the current ROM with an IPS, UPS or BPS patch applied.
+    Patch(PathBuf),
+    // adds the cheats of a .cht file to the game.
+    ImportCheats(PathBuf),
+    OpenCheats,
+}
+
+// changes to the audio mixer. channels are indexed 0 - 3 for ch1 - ch4.
+pub enum MixerRequest {
+    Mute(usize, bool),
+    Solo(usize, bool),
+    Gain(usize, f32),
+    Volume(f32),
+    HighPass(bool),
+    LowPass(f32),
 }
 
 pub enum ControllerResponse {
     Title(String),
     Draw(Vec<u8>),
     EmulatorError(EmulatorError),
+    // the name of the shader to render with.
+    Shader(String),
+    // saves the window, with the shader, as a PNG at the path.
+    Screenshot(PathBuf),
+    // a frame for the window of a viewer.
+    View(View, Vec<u8>),
+    // the mixer of the config, for the checks of the audio menu.
+    Mixer(Mixer),
+    // whether the CPU is being traced, for the check of the Trace Log menu.
+    Trace(bool),
 }
 
 pub enum ControllerMode {
@@ -28,31 +84,101 @@ pub enum ControllerMode {
 
 pub struct Controller {
     pub emulator: Option<Box<Emulator>>,
+    // the ROM that is currently loaded, and the patch chosen for it.
+    path: Option<PathBuf>,
+    patch: Option<PathBuf>,
     player: Option<Box<dyn Player>>,
+    // the config file, and config with the profile of the current game layered on top.
+    global: Config,
     config: Config,
+    profile: ProfileChoice,
+    // name of the profile in config.
+    active: Option<String>,
     mode: ControllerMode,
+    // direction each gamepad axis is pushed in, by pad and axis.
+    axes: HashMap<(usize, u8), i8>,
+    paused: bool,
+    // the settings overlay, while it is open the emulator is paused.
+    settings: Option<Settings>,
+    // set when the overlay has to be drawn again.
+    redraw: bool,
+    // a shader to send to the window, after the config changed.
+    shader: Option<String>,
+    // the mixer for the checks of the audio menu, after the config changed.
+    menu_mixer: Option<Mixer>,
+    // whether a trace is running, for the Trace Log check, after it started or stopped.
+    menu_trace: Option<bool>,
+    // a screenshot for the window to take with the shader.
+    screenshot: Option<PathBuf>,
+    video: Option<VideoRecorder>,
+    // the emulator frame the video has been written up to.
+    video_frame: u64,
+    // set when the video started the audio recording, which it then stops with it.
+    video_audio: bool,
+    viewers: Vec<Viewer>,
+    // the viewer to send a frame of next, they take turns so they do not crowd out the screen.
+    next_viewer: usize,
 }
 
 impl Controller {
     pub fn new() -> Self {
+        let global = Config::load();
         Self {
             emulator: None,
-            config: Config::load(),
+            path: None,
+            patch: None,
+            config: global.clone(),
+            global,
+            profile: ProfileChoice::Auto,
+            active: None,
             player: None,
             mode: ControllerMode::Default,
+            axes: HashMap::new(),
+            paused: false,
+            settings: None,
+            redraw: false,
+            shader: None,
+            menu_mixer: None,
+            menu_trace: None,
+            screenshot: None,
+            video: None,
+            video_frame: 0,
+            video_audio: false,
+            viewers: Vec::new(),
+            next_viewer: 0,
         }
     }
 
     pub fn draw(&self) -> Vec<u8> {
-        let buffer = self.emulator.as_ref().unwrap().screen();
+        let emulator = self.emulator.as_ref().unwrap();
+        let buffer = emulator.screen();
+        // SGB games are colored by the game itself.
+        let mut frame = match emulator.sgb() {
+            Some(sgb) => sgb.render(&buffer),
+            None => self.colorize(&buffer, emulator.layers()),
+        };
+        if let Some(settings) = self.settings.as_ref() {
+            settings.draw(&mut frame, &self.config, self.active.as_deref());
+        }
+        frame
+    }
+
+    // colors the shades of the screen with the palette of the layer they come from.
+    fn colorize(&self, buffer: &[u8], layers: &[u8]) -> Vec<u8> {
+        let palettes = &self.config.palettes;
         let mut frame = Vec::new();
-        for &byte in buffer.iter() {
+        for (&byte, &layer) in buffer.iter().zip(layers) {
             let mut rgba: [u8; 4] = [0, 0, 0, 0xff];
+            let color = match layer {
+                LAYER_OBJ0 => &palettes.obj0,
+                LAYER_OBJ1 => &palettes.obj1,
+                _ => &palettes.bg,
+            };
             match byte {
-                0 => rgba[..3].copy_from_slice(&self.config.color.id0), // white
-                1 => rgba[..3].copy_from_slice(&self.config.color.id1), // light gray
-                2 => rgba[..3].copy_from_slice(&self.config.color.id2), // dark gray
-                3 => rgba[..3].copy_from_slice(&self.config.color.id3), // black
+                0 => rgba[..3].copy_from_slice(&color.id0), // white
+                1 => rgba[..3].copy_from_slice(&color.id1), // light gray
+                2 => rgba[..3].copy_from_slice(&color.id2), // dark gray
+                3 => rgba[..3].copy_from_slice(&color.id3), // black
 
                 _ => (),
             }
@@ -63,6 +189,691 @@ impl Controller {
         frame
     }
 
+    /* presses whatever the key is bound to - a hotkey, a button, autofire or a macro, in that order.
+    releasing a key lets go of everything bound to it, whatever modifiers are held, as they may
+    have been released first. */
+    fn key(&mut self, key: &Key, pressed: bool) {
+        if !pressed {
+            if self.settings.is_some() {
+                self.settings_key(key, false);
+            }
+            let key = Key {
+                modifiers: keybind::ANY,
+                ..key.clone()
+            };
+            if let Some(hotkey) = self.config.get_hotkey(&key) {
+                self.hotkey(hotkey, false);
+            }
+            let input = self.config.get_input(&key);
+            let turbo = self.config.get_turbo(&key);
+            if let Some(emulator) = self.emulator.as_mut() {
+                match (Controller::player(emulator, &key), input) {
+                    (Some(player), Some(input)) => emulator.player_key(player, input, false),
+                    _ => emulator.key_up(input),
+                }
+                if let Some(turbo) = turbo {
+                    emulator.turbo(turbo, false);
+                }
+            }
+            return;
+        }
+
+        if self.settings.is_some() {
+            self.settings_key(key, true);
+            return;
+        }
+
+        if let Some(hotkey) = self.config.get_hotkey(key) {
+            self.hotkey(hotkey, true);
+            return;
+        }
+
+        let emulator = match self.emulator.as_mut() {
+            Some(emulator) => emulator,
+            None => return,
+        };
+
+        if let Some(input) = self.config.get_input(key) {
+            match Controller::player(emulator, key) {
+                Some(player) => emulator.player_key(player, input, true),
+                None => emulator.key_down(Some(input)),
+            }
+        } else if let Some(input) = self.config.get_turbo(key) {
+            emulator.turbo(input, true);
+        } else if let Some(m) = self.config.get_macro(key) {
+            emulator.play_macro(m.frames());
+        }
+    }
+
+    /* with SGB multiplayer, gamepad 2 controls joypad 2 and so on. returns the joypad for
+    keys that do not go to the first one. */
+    fn player(emulator: &Emulator, key: &Key) -> Option<usize> {
+        match key.pad {
+            Some(pad) if pad > 0 && pad < emulator.players() => Some(pad),
+            _ => None,
+        }
+    }
+
+    // fast forward runs while held, the other actions happen on press.
+    fn hotkey(&mut self, hotkey: Hotkey, pressed: bool) {
+        if hotkey == Hotkey::FastForward {
+            if let Some(emulator) = self.emulator.as_mut() {
+                emulator.set_fast_forward(pressed);
+            }
+            return;
+        }
+
+        if !pressed {
+            return;
+        }
+
+        match hotkey {
+            Hotkey::SaveState => {
+                if let Some(emulator) = self.emulator.as_mut() {
+                    match emulator.save_state() {
+                        Ok(path) => println!("State saved to {:?}", path),
+                        Err(e) => println!("State could not be saved: {}", e),
+                    }
+                }
+            }
+            Hotkey::LoadState => {
+                if let Some(emulator) = self.emulator.as_mut() {
+                    match emulator.load_state() {
+                        Ok(path) => println!("State loaded from {:?}", path),
+                        Err(e) => println!("State could not be loaded: {}", e),
+                    }
+                }
+            }
+            Hotkey::Pause => {
+                self.paused = !self.paused;
+                println!("{}", if self.paused { "Paused" } else { "Resumed" });
+            }
+            Hotkey::Screenshot => match self.screenshot() {
+                Ok(Some(path)) => println!("Screenshot saved to {:?}", path),
+                Ok(None) => (),
+                Err(e) => println!("Screenshot could not be saved: {}", e),
+            },
+            Hotkey::RecordVideo => self.record_video(),
+            Hotkey::Reset => {
+                if self.reset().is_err() {
+                    println!("Emulator could not be reset");
+                }
+            }
+            Hotkey::Settings => self.toggle_settings(),
+            Hotkey::FastForward => (),
+        }
+    }
+
+    fn toggle_settings(&mut self) {
+        self.settings = match self.settings {
+            Some(_) => None,
+            None => Some(Settings::new()),
+        };
+        self.redraw = true;
+    }
+
+    /* hands a key press to the settings overlay. changes are validated right away, an invalid
+    config is rolled back and the error is shown instead. */
+    fn settings_key(&mut self, key: &Key, pressed: bool) {
+        let mut settings = match self.settings.take() {
+            Some(settings) => settings,
+            None => return,
+        };
+
+        let old = self.config.clone();
+        let change = settings.key(key, pressed, &mut self.config);
+        self.redraw = true;
+
+        match change {
+            Change::None => (),
+            Change::Close => return,
+            Change::Config | Change::Audio => {
+                let errors = self.config.validate();
+                if let Some(error) = errors.first() {
+                    settings.set_message(error.clone());
+                    self.config = old;
+                } else {
+                    if self.config.palettes.auto && !old.palettes.auto {
+                        if let Some(emulator) = self.emulator.as_ref() {
+                            palette::auto(&mut self.config.palettes, &emulator.title());
+                        }
+                    }
+                    self.save_config();
+                    if self.config.shader != old.shader {
+                        self.shader = Some(self.config.shader.clone());
+                    }
+                    self.menu_mixer = Some(self.config.mixer);
+                    if change == Change::Audio && self.emulator.is_some() {
+                        // the player has to be created or dropped, attach_player does either.
+                        self.attach_player();
+                    } else {
+                        self.apply_config();
+                    }
+                }
+            }
+        }
+
+        self.settings = Some(settings);
+    }
+
+    /* saves the current frame as a PNG in the screenshots folder, scaled by the capture scale.
+    with the shader the window takes it instead, as only it has the shaded frame. */
+    fn screenshot(&mut self) -> io::Result<Option<PathBuf>> {
+        let emulator = match self.emulator.as_ref() {
+            Some(emulator) => emulator,
+            None => return Ok(None),
+        };
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut path = Config::dir("screenshots");
+        path.push(format!("{}-{}.png", emulator.title().trim(), time));
+
+        if self.config.capture.shader {
+            self.screenshot = Some(path);
+            return Ok(None);
+        }
+
+        let frame = self.draw();
+        let (width, height) = sgb::frame_size(frame.len());
+        let scale = self.config.capture.scale as usize;
+        let frame = capture::scale(&frame, width, height, scale);
+        capture::save_png(&path, &frame, width * scale, height * scale)?;
+
+        Ok(Some(path))
+    }
+
+    /* starts recording a video into the videos folder, or stops the one being recorded.
+    a Y4M has no sound, so the audio is recorded to a WAV next to it from the same frame on. */
+    fn record_video(&mut self) {
+        if self.video.is_some() {
+            self.stop_video();
+            return;
+        }
+        let emulator = match self.emulator.as_ref() {
+            Some(emulator) => emulator,
+            None => return,
+        };
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let capture = self.config.capture;
+        let extension = match capture.video {
+            VideoFormat::Gif => "gif",
+            VideoFormat::Y4m => "y4m",
+        };
+        let mut path = Config::dir("videos");
+        path.push(format!(
+            "{}-{}.{}",
+            emulator.title().trim(),
+            time,
+            extension
+        ));
+
+        let frame = self.draw();
+        let size = sgb::frame_size(frame.len());
+        let video =
+            VideoRecorder::new(&path, capture.video, size.0, size.1, capture.scale as usize)
+                .and_then(|mut video| video.frame(&frame, size).map(|_| video));
+        let video = match video {
+            Ok(video) => video,
+            Err(e) => {
+                println!("Video recording failed: {}", e);
+                return;
+            }
+        };
+
+        let emulator = self.emulator.as_mut().unwrap();
+        self.video_frame = emulator.frames();
+        // audio that is already being recorded is left running, it belongs to the user.
+        self.video_audio = capture.video == VideoFormat::Y4m && !emulator.recording();
+        if self.video_audio {
+            if let Err(e) = emulator.start_recording(&path.with_extension("wav"), false) {
+                println!("Audio of the video could not be recorded: {}", e);
+                self.video_audio = false;
+            }
+        }
+
+        println!("Recording video to {:?}", path);
+        self.video = Some(video);
+    }
+
+    fn stop_video(&mut self) {
+        let video = match self.video.take() {
+            Some(video) => video,
+            None => return,
+        };
+
+        let path = video.path.clone();
+        match video.finish() {
+            Ok(frames) => println!("Video saved to {:?} ({} frames)", path, frames),
+            Err(e) => println!("Video could not be saved: {}", e),
+        }
+        if self.video_audio {
+            self.video_audio = false;
+            if let Some(Err(e)) = self.emulator.as_mut().map(|e| e.stop_recording()) {
+                println!("Audio of the video could not be saved: {}", e);
+            }
+        }
+    }
+
+    // writes the current frame to the video for every frame emulated since the last one.
+    fn video_frame(&mut self) {
+        let frames = match (self.video.as_ref(), self.emulator.as_ref()) {
+            (Some(_), Some(emulator)) => emulator.frames(),
+            _ => return,
+        };
+        if frames <= self.video_frame {
+            return;
+        }
+
+        let frame = self.draw();
+        let size = sgb::frame_size(frame.len());
+        let video = self.video.as_mut().unwrap();
+        while self.video_frame < frames {
+            self.video_frame += 1;
+            if let Err(e) = video.frame(&frame, size) {
+                println!("Video recording failed: {}", e);
+                self.stop_video();
+                return;
+            }
+        }
+    }
+
+    /* starts the trace log of the CPU into the traces folder, or stops it. the Trace Log check
+    follows whether it is running, as starting can fail. */
+    fn trace(&mut self, on: bool) {
+        let emulator = match self.emulator.as_mut() {
+            Some(emulator) => emulator,
+            None => {
+                self.menu_trace = Some(false);
+                return;
+            }
+        };
+        if let Some(tracer) = emulator.stop_trace() {
+            let path = tracer.path.clone();
+            match tracer.finish() {
+                Ok(lines) => println!("Trace saved to {:?} ({} instructions)", path, lines),
+                Err(e) => println!("Trace could not be saved: {}", e),
+            }
+        }
+        if on {
+            let time = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let mut path = Config::dir("traces");
+            path.push(format!("{}-{}.log", emulator.title().trim(), time));
+            match emulator.start_trace(&path, self.config.trace) {
+                Ok(_) => println!("Tracing to {:?}", path),
+                Err(e) => println!("Trace could not be started: {}", e),
+            }
+        }
+        self.menu_trace = Some(emulator.tracing());
+    }
+
+    fn viewer(&mut self, view: View, open: bool) {
+        self.viewers.retain(|viewer| viewer.view != view);
+        if open {
+            self.viewers.push(Viewer::new(view));
+        }
+        self.redraw = true;
+    }
+
+    fn viewer_key(&mut self, view: View, key: &Key) {
+        let name = match key.names.first() {
+            Some(name) => name.as_str(),
+            None => return,
+        };
+
+        if name == "s" {
+            match self.export_view(view) {
+                Ok(Some(path)) => println!("{} saved to {:?}", view.name(), path),
+                Ok(None) => (),
+                Err(e) => println!("{} could not be saved: {}", view.name(), e),
+            }
+            return;
+        }
+
+        let viewer = self.viewers.iter_mut().find(|viewer| viewer.view == view);
+        if let (Some(viewer), Some(emulator)) = (viewer, self.emulator.as_mut()) {
+            viewer.key(name, emulator);
+            self.redraw = true;
+        }
+    }
+
+    // saves a viewer as a PNG in the screenshots folder.
+    fn export_view(&self, view: View) -> io::Result<Option<PathBuf>> {
+        let emulator = match self.emulator.as_ref() {
+            Some(emulator) => emulator,
+            None => return Ok(None),
+        };
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut path = Config::dir("screenshots");
+        path.push(format!(
+            "{}-{}-{}.png",
+            emulator.title().trim(),
+            format!("{:?}", view).to_lowercase(),
+            time
+        ));
+
+        let frame = match self.viewers.iter().find(|viewer| viewer.view == view) {
+            Some(viewer) => viewer.draw(emulator, &self.config.palettes),
+            None => Viewer::new(view).draw(emulator, &self.config.palettes),
+        };
+        let (width, height) = view.size();
+        capture::save_png(&path, &frame, width, height)?;
+        Ok(Some(path))
+    }
+
+    // the frame of the viewer whose turn it is.
+    fn view(&mut self) -> Option<ControllerResponse> {
+        let emulator = self.emulator.as_ref()?;
+        self.next_viewer = (self.next_viewer + 1) % self.viewers.len().max(1);
+        let viewer = self.viewers.get(self.next_viewer)?;
+        let frame = viewer.draw(emulator, &self.config.palettes);
+        Some(ControllerResponse::View(viewer.view, frame))
+    }
+
+    // restarts the current ROM, keeping its save file.
+    fn reset(&mut self) -> Result<(), EmulatorError> {
+        let path = match self.path.as_ref() {
+            Some(path) => path.clone(),
+            None => return Err(EmulatorError::NoRom),
+        };
+
+        // dropping the emulator writes the save file first.
+        self.stop_video();
+        self.trace(false);
+        self.emulator = None;
+        let mut emulator = Emulator::new(&path, self.patch.as_deref())?;
+        emulator.load_save(&path)?;
+        self.emulator = Some(emulator);
+        self.attach_player();
+        Ok(())
+    }
+
+    // turns gamepad events into key presses. axes press a key when pushed past the deadzone.
+    fn gamepad(&mut self, event: GamepadEvent) {
+        match event {
+            GamepadEvent::Connected(pad, name) => println!("Gamepad {} connected: {}", pad, name),
+            GamepadEvent::Disconnected(pad) => println!("Gamepad {} disconnected", pad),
+            GamepadEvent::Button(pad, button, pressed) => {
+                self.key(&Key::pad(pad, gamepad::button_name(button)), pressed)
+            }
+            GamepadEvent::Axis(pad, axis, value) => {
+                let deadzone = self.config.keybinds.deadzone;
+                let direction = if value > deadzone {
+                    1
+                } else if value < -deadzone {
+                    -1
+                } else {
+                    0
+                };
+
+                let old = self.axes.insert((pad, axis), direction).unwrap_or(0);
+                if old != direction {
+                    if old != 0 {
+                        self.key(&Key::pad(pad, gamepad::axis_name(axis, old)), false);
+                    }
+                    if direction != 0 {
+                        self.key(&Key::pad(pad, gamepad::axis_name(axis, direction)), true);
+                    }
+                }
+            }
+        }
+    }
+
+    // creates the audio player for the current emulator, if audio is on.
+    fn attach_player(&mut self) {
+        self.player = if self.config.audio {
+            self.emulator
+                .as_mut()
+                .unwrap()
+                .audio()
+                .and_then(CpalPlayer::new)
+        } else {
+            None
+        };
+        if self.player.is_some() {
+            self.emulator
+                .as_mut()
+                .unwrap()
+                .sample(self.player.as_ref().unwrap().sample());
+            self.player.as_ref().unwrap().play();
+        }
+        self.apply_config();
+    }
+
+    // restarts the current ROM from power on without loading the save file, for movies.
+    fn power_on(&mut self) -> Result<(), EmulatorError> {
+        let path = match self.path.as_ref() {
+            Some(path) => path.clone(),
+            None => return Err(EmulatorError::NoRom),
+        };
+
+        self.stop_video();
+        self.trace(false);
+        self.emulator = Some(Emulator::new(&path, self.patch.as_deref())?);
+        self.attach_player();
+        Ok(())
+    }
+
+    /* starts recording a movie from power on, or from the save state of the game, or stops and
+    saves the one being recorded. */
+    fn record_movie(&mut self, from_state: bool) -> Result<(), EmulatorError> {
+        if let Some(movie) = self.emulator.as_mut().and_then(|e| e.stop_movie()) {
+            let time = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let mut path = Config::dir("movies");
+            path.push(format!("{}-{}.kmv", movie.title.trim(), time));
+
+            match movie.save(&path) {
+                Ok(_) => println!("Movie saved to {:?} ({} frames)", path, movie.frames.len()),
+                Err(e) => println!("Movie could not be saved: {}", e),
+            }
+            return Ok(());
+        }
+
+        let movie = match self.emulator.as_ref().map(|e| e.new_movie(from_state)) {
+            Some(Ok(movie)) => movie,
+            Some(Err(e)) => {
+                println!(
+                    "Movie could not be started, the save state could not be read: {}",
+                    e
+                );
+                return Ok(());
+            }
+            None => return Ok(()),
+        };
+
+        self.power_on()?;
+        match self
+            .emulator
+            .as_mut()
+            .unwrap()
+            .start_movie(MovieState::Recording(movie))
+        {
+            Ok(_) => println!("Movie recording started"),
+            Err(e) => println!("Movie could not be started, {}", e),
+        }
+        Ok(())
+    }
+
+    // plays a movie back from power on, or from the save state in it.
+    fn play_movie(&mut self, path: &Path) -> Result<(), EmulatorError> {
+        let movie = match Movie::load(path) {
+            Ok(movie) => movie,
+            Err(e) => {
+                println!("Movie could not be loaded: {}", e);
+                return Ok(());
+            }
+        };
+        // checked before the reset, so the game keeps running.
+        if let Some(emulator) = self.emulator.as_ref() {
+            if !movie.sha1.is_empty() && movie.sha1 != emulator.sha1() {
+                println!("Movie was recorded on another ROM ({})", movie.sha1);
+                return Ok(());
+            }
+        }
+
+        self.power_on()?;
+        match self
+            .emulator
+            .as_mut()
+            .unwrap()
+            .start_movie(MovieState::Playing { movie, frame: 0 })
+        {
+            Ok(_) => println!("Movie playback started"),
+            Err(e) => println!("Movie could not be played, {}", e),
+        }
+        Ok(())
+    }
+
+    /* applies the sync mode and mixer from the config to the emulator.
+    without a player nothing drains the audio buffer, so syncing falls back to time. */
+    fn apply_config(&mut self) {
+        let sync = match self.player {
+            Some(_) => self.config.sync,
+            None => SyncMode::Time,
+        };
+
+        if let Some(emulator) = self.emulator.as_mut() {
+            emulator.set_sync(sync);
+            emulator.set_mixer(self.config.mixer);
+            emulator.set_turbo_rate(self.config.keybinds.turbo_rate);
+            emulator.set_opposing(self.config.opposing);
+            emulator.set_speed(self.config.speed);
+            emulator.set_sgb(self.config.sgb);
+        }
+    }
+
+    // layers the chosen profile for the loaded ROM on top of the global config.
+    fn layer_profile(&mut self) {
+        self.config = self.global.clone();
+        self.active = None;
+
+        let emulator = match self.emulator.as_ref() {
+            Some(emulator) => emulator,
+            None => return,
+        };
+        let (title, crc32) = (emulator.title(), emulator.crc32());
+        let profile = self
+            .global
+            .profile(&self.profile, &title, &crc32, emulator.sha1());
+
+        if let Some(profile) = profile {
+            match self.global.with_profile(profile) {
+                Ok(config) => {
+                    println!("Profile {} active", profile.name);
+                    self.active = Some(profile.name.clone());
+                    self.config = config;
+                }
+                Err(e) => println!("Profile could not be applied: {}", e),
+            }
+        }
+
+        if self.config.palettes.auto {
+            palette::auto(&mut self.config.palettes, &title);
+        }
+    }
+
+    /* adds the palettes of a file to the library and colors the layers with them,
+    the first one for BG, then OBJ0 and OBJ1 when the file has them. */
+    fn import_palette(&mut self, path: &PathBuf) {
+        let imported = match palette::import(path) {
+            Ok(imported) => imported,
+            Err(e) => {
+                println!("Palette could not be imported: {}", e);
+                return;
+            }
+        };
+
+        for config in [&mut self.global, &mut self.config] {
+            let library = &mut config.palettes.library;
+            library.retain(|old| imported.iter().all(|new| new.name != old.name));
+            library.extend(imported.iter().cloned());
+        }
+
+        let palettes = &mut self.config.palettes;
+        palettes.bg = imported[0].colors;
+        palettes.obj0 = imported.get(1).unwrap_or(&imported[0]).colors;
+        palettes.obj1 = imported.get(2).unwrap_or(&imported[0]).colors;
+        palettes.auto = false;
+
+        println!("Imported {} palettes from {:?}", imported.len(), path);
+        self.save_config();
+        self.redraw = true;
+    }
+
+    // switches to another profile while the game is running.
+    fn switch_profile(&mut self, choice: ProfileChoice) {
+        self.profile = choice;
+        self.layer_profile();
+        self.shader = Some(self.config.shader.clone());
+        self.menu_mixer = Some(self.config.mixer);
+        self.redraw = true;
+
+        // audio can differ between profiles, attach_player creates or drops the player.
+        if self.emulator.is_some() && self.config.audio != self.player.is_some() {
+            self.attach_player();
+        } else {
+            self.apply_config();
+        }
+    }
+
+    // saves changes to the active profile, or to the global config when there is none.
+    fn save_config(&mut self) {
+        match self.active.as_ref() {
+            Some(name) => {
+                if let Err(e) = self.global.set_profile(name, &self.config) {
+                    println!("Config could not be saved: {}", e);
+                    return;
+                }
+            }
+            None => self.global = self.config.clone(),
+        }
+        self.global.save();
+    }
+
+    fn mixer(&mut self, request: MixerRequest) {
+        let mixer = &mut self.config.mixer;
+        match request {
+            MixerRequest::Mute(channel, on) => mixer.mute[channel] = on,
+            MixerRequest::Solo(channel, on) => mixer.solo[channel] = on,
+            MixerRequest::Gain(channel, gain) => mixer.gain[channel] = gain.max(0.0),
+            MixerRequest::Volume(volume) => mixer.master = volume.clamp(0.0, 1.0),
+            MixerRequest::HighPass(on) => mixer.high_pass = on,
+            MixerRequest::LowPass(cutoff) => mixer.low_pass = cutoff.max(0.0),
+        }
+
+        if let Some(emulator) = self.emulator.as_mut() {
+            emulator.set_mixer(self.config.mixer);
+        }
+        self.save_config();
+    }
+
+    // logs the audio buffer counters of the current emulator.
+    fn audio_stats(&self) {
+        if let (Some(emulator), Some(_)) = (self.emulator.as_ref(), self.player.as_ref()) {
+            let stats = emulator.audio_stats();
+            println!(
+                "audio buffer: {} underruns, {} overruns ({}/{} queued)",
+                stats.underruns, stats.overruns, stats.len, stats.capacity
+            );
+        }
+    }
+
     pub fn run_debug(
         &mut self,
         sender: SyncSender<ControllerResponse>,
@@ -92,21 +903,10 @@ impl Controller {
                     // Switch to new emulator
                     self.config = Config::load();
 
-                    match Emulator::new(&path) {
+                    match Emulator::new(&path, None) {
                         Ok(e) => {
                             self.emulator = Some(e);
-                            self.player = if self.config.audio {
-                                CpalPlayer::new(self.emulator.as_ref().unwrap().audio())
-                            } else {
-                                None
-                            };
-                            if self.player.is_some() {
-                                self.emulator
-                                    .as_mut()
-                                    .unwrap()
-                                    .sample(self.player.as_ref().unwrap().sample());
-                                self.player.as_ref().unwrap().play();
-                            }
+                            self.attach_player();
 
                             // set title
                             match sender.try_send(ControllerResponse::Title(
@@ -133,6 +933,8 @@ impl Controller {
                     // reload config file
 
                     self.config = Config::load();
+                    self.menu_mixer = Some(self.config.mixer);
+                    self.apply_config();
                 }
 
                 Ok(ControllerRequest::OpenConfig) => {
@@ -163,27 +965,29 @@ impl Controller {
             match receiver.try_recv() {
                 Ok(ControllerRequest::KeyDown(key)) => {
                     // Handle key down
-
-                    self.emulator
-                        .as_mut()
-                        .unwrap()
-                        .key_down(self.config.get_input(&key));
+                    self.key(&key, true);
                 }
                 Ok(ControllerRequest::KeyUp(key)) => {
                     // Handle key up
-
-                    self.emulator
-                        .as_mut()
-                        .unwrap()
-                        .key_up(self.config.get_input(&key));
+                    self.key(&key, false);
+                }
+                Ok(ControllerRequest::Gamepad(event)) => {
+                    // Handle gamepad input
+                    self.gamepad(event);
                 }
                 Ok(ControllerRequest::New(path)) => {
                     // Switch to new emulator
-                    self.config = Config::load();
+                    self.global = Config::load();
+                    self.config = self.global.clone();
 
-                    match Emulator::new(&path) {
+                    match Emulator::new(&path, None) {
                         Ok(e) => {
+                            self.audio_stats();
+                            self.stop_video();
+                            self.trace(false);
                             self.emulator = Some(e);
+                            self.path = Some(path.clone());
+                            self.patch = None;
                             match self.emulator.as_mut().unwrap().load_save(&path) {
                                 Ok(_) => (),
                                 Err(s) => {
@@ -196,18 +1000,11 @@ impl Controller {
                                     }
                                 }
                             }
-                            self.player = if self.config.audio {
-                                CpalPlayer::new(self.emulator.as_ref().unwrap().audio())
-                            } else {
-                                None
-                            };
-                            if self.player.is_some() {
-                                self.emulator
-                                    .as_mut()
-                                    .unwrap()
-                                    .sample(self.player.as_ref().unwrap().sample());
-                                self.player.as_ref().unwrap().play();
-                            }
+                            self.layer_profile();
+                            self.attach_player();
+                            self.shader = Some(self.config.shader.clone());
+                            self.menu_mixer = Some(self.config.mixer);
+                            self.trace(self.config.trace.enabled);
 
                             // set title
                             match sender.try_send(ControllerResponse::Title(
@@ -232,9 +1029,15 @@ impl Controller {
                     };
                 }
                 Ok(ControllerRequest::LoadConfig) => {
-                    // reload config file
+                    // reload config file, and the cheats, which may have been edited too
 
-                    self.config = Config::load();
+                    self.global = Config::load();
+                    self.switch_profile(self.profile.clone());
+                    if let Some(emulator) = self.emulator.as_mut() {
+                        if let Err(e) = emulator.load_cheats() {
+                            println!("Cheats could not be loaded: {}", e);
+                        }
+                    }
                 }
 
                 Ok(ControllerRequest::OpenConfig) => {
@@ -243,17 +1046,222 @@ impl Controller {
                     Config::open();
                 }
 
+                Ok(ControllerRequest::Mixer(request)) => {
+                    // change volume, mute or solo of the channels
+                    self.mixer(request);
+                }
+
+                Ok(ControllerRequest::Record) => {
+                    // start or stop recording the audio
+                    if let Some(emulator) = self.emulator.as_mut() {
+                        match emulator.record(self.config.record_stems) {
+                            Ok(true) => println!("Recording started"),
+                            Ok(false) => println!("Recording stopped"),
+                            Err(e) => println!("Recording failed: {}", e),
+                        }
+                    }
+                }
+
+                Ok(ControllerRequest::RecordVideo) => {
+                    // start or stop recording a video
+                    self.record_video();
+                }
+
+                Ok(ControllerRequest::RecordMovie(from_state)) => {
+                    // start or stop recording a movie
+                    if let Err(s) = self.record_movie(from_state) {
+                        match sender.try_send(ControllerResponse::EmulatorError(s)) {
+                            Err(TrySendError::Disconnected(_)) => {
+                                break;
+                            }
+                            Err(_) => (),
+                            Ok(_) => (),
+                        }
+                    }
+                }
+
+                Ok(ControllerRequest::PlayMovie(path)) => {
+                    // play back a movie
+                    if let Err(s) = self.play_movie(&path) {
+                        match sender.try_send(ControllerResponse::EmulatorError(s)) {
+                            Err(TrySendError::Disconnected(_)) => {
+                                break;
+                            }
+                            Err(_) => (),
+                            Ok(_) => (),
+                        }
+                    }
+                }
+
+                Ok(ControllerRequest::Profile(choice)) => {
+                    // layer another profile on top of the config
+                    self.switch_profile(choice);
+                }
+
+                Ok(ControllerRequest::ImportPalette(path)) => {
+                    // add the palettes of a .pal or .gpl file
+                    self.import_palette(&path);
+                }
+
+                Ok(ControllerRequest::Settings) => {
+                    // open or close the settings overlay
+                    self.toggle_settings();
+                }
+
+                Ok(ControllerRequest::Viewer(view, open)) => {
+                    // open or close a tile, map or OAM viewer
+                    self.viewer(view, open);
+                }
+
+                Ok(ControllerRequest::Trace(on)) => self.trace(on),
+
+                Ok(ControllerRequest::Patch(path)) if self.path.is_some() => {
+                    // restart with the patch, keeping the old one if it cannot be applied
+                    let old = self.patch.replace(path);
+                    if let Err(e) = self.reset() {
+                        self.patch = old;
+                        if let Err(TrySendError::Disconnected(_)) =
+                            sender.try_send(ControllerResponse::EmulatorError(e))
+                        {
+                            break;
+                        }
+                    }
+                }
+
+                Ok(ControllerRequest::ImportCheats(path)) => {
+                    // add cheats from a .cht file
+                    if let Some(emulator) = self.emulator.as_mut() {
+                        match emulator.import_cheats(&path) {
+                            Ok(count) => println!("Imported {} cheats from {:?}", count, path),
+                            Err(e) => println!("Cheats could not be imported: {}", e),
+                        }
+                        self.redraw = true;
+                    }
+                }
+
+                Ok(ControllerRequest::OpenCheats) => {
+                    // open the cheat file of the game, creating it if there is none yet
+                    if let Some(emulator) = self.emulator.as_ref() {
+                        let path = emulator.cheat_path();
+                        let opened = match path.exists() {
+                            true => Ok(()),
+                            false => emulator.save_cheats(),
+                        }
+                        .and_then(|_| opener::open(&path).map_err(io::Error::other));
+                        if let Err(e) = opened {
+                            println!("Cheat file could not be opened: {}", e);
+                        }
+                    }
+                }
+
+                Ok(ControllerRequest::ViewerKey(view, key)) => {
+                    // S saves a viewer as a PNG, the viewer handles the other keys
+                    self.viewer_key(view, &key);
+                }
+
+                Ok(ControllerRequest::ExportMovie(path)) => {
+                    // convert a movie into a .bk2 next to it
+                    let bk2 = path.with_extension("bk2");
+                    match Movie::load(&path).and_then(|movie| movie.export_bk2(&bk2)) {
+                        Ok(_) => println!("Movie exported to {:?}", bk2),
+                        Err(e) => println!("Movie could not be exported: {}", e),
+                    }
+                }
+
                 /*Ok(ControllerRequest::Save) => {
                     println!("{}", self.config.save)
                 }*/
                 Ok(ControllerRequest::Exit) => {
                     // Exits Emulator
+                    self.audio_stats();
+                    self.stop_video();
+                    self.trace(false);
                     break;
                 }
                 Err(TryRecvError::Disconnected) => break,
                 _ => (),
             }
 
+            // responses that do not fit in the channel are sent again on the next loop.
+            if let Some(shader) = self.shader.take() {
+                match sender.try_send(ControllerResponse::Shader(shader)) {
+                    Err(TrySendError::Disconnected(_)) => {
+                        break;
+                    }
+                    Err(TrySendError::Full(ControllerResponse::Shader(shader))) => {
+                        self.shader = Some(shader)
+                    }
+                    Err(_) => (),
+                    Ok(_) => (),
+                }
+            }
+
+            if let Some(mixer) = self.menu_mixer.take() {
+                match sender.try_send(ControllerResponse::Mixer(mixer)) {
+                    Err(TrySendError::Disconnected(_)) => {
+                        break;
+                    }
+                    Err(TrySendError::Full(ControllerResponse::Mixer(mixer))) => {
+                        self.menu_mixer = Some(mixer)
+                    }
+                    Err(_) => (),
+                    Ok(_) => (),
+                }
+            }
+
+            if let Some(tracing) = self.menu_trace.take() {
+                match sender.try_send(ControllerResponse::Trace(tracing)) {
+                    Err(TrySendError::Disconnected(_)) => {
+                        break;
+                    }
+                    Err(TrySendError::Full(ControllerResponse::Trace(tracing))) => {
+                        self.menu_trace = Some(tracing)
+                    }
+                    Err(_) => (),
+                    Ok(_) => (),
+                }
+            }
+
+            if let Some(path) = self.screenshot.take() {
+                match sender.try_send(ControllerResponse::Screenshot(path)) {
+                    Err(TrySendError::Disconnected(_)) => {
+                        break;
+                    }
+                    Err(TrySendError::Full(ControllerResponse::Screenshot(path))) => {
+                        self.screenshot = Some(path)
+                    }
+                    Err(_) => (),
+                    Ok(_) => (),
+                }
+            }
+
+            if self.redraw && self.emulator.is_some() {
+                match sender.try_send(ControllerResponse::Draw(self.draw())) {
+                    Err(TrySendError::Disconnected(_)) => {
+                        break;
+                    }
+                    Err(_) => (),
+                    Ok(_) => self.redraw = false,
+                }
+                // the viewers are drawn again too, as the emulator may be paused.
+                for _ in 0..self.viewers.len() {
+                    if let Some(view) = self.view() {
+                        if let Err(TrySendError::Disconnected(_)) = sender.try_send(view) {
+                            break;
+                        }
+                    }
+                }
+            }
+
+            let idle = self.paused || self.settings.is_some();
+            if let Some(emulator) = self.emulator.as_ref() {
+                emulator.set_audio_idle(idle);
+            }
+            if idle {
+                thread::sleep(Duration::from_millis(1));
+                continue;
+            }
+
             // Emulator update and draw logic
 
             match self.emulator {
@@ -268,9 +1276,16 @@ impl Controller {
                             Err(_) => (),
                             Ok(_) => (),
                         }
+
+                        if let Some(view) = self.view() {
+                            if let Err(TrySendError::Disconnected(_)) = sender.try_send(view) {
+                                break;
+                            }
+                        }
                     }
 
                     self.emulator.as_mut().unwrap().step();
+                    self.video_frame();
                 }
                 None => continue,
             }
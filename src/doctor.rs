@@ -0,0 +1,118 @@
+use crate::emulator::Emulator;
+use crate::movie::FRAME_CYCLES;
+use crate::system::cpu::CPUResult;
+use crate::trace;
+use std::collections::VecDeque;
+use std::fs;
+use std::path::Path;
+
+// the fields of a gameboy-doctor line, in the order they are written.
+const FIELDS: [&str; 11] = ["A", "F", "B", "C", "D", "E", "H", "L", "SP", "PC", "PCMEM"];
+
+// steps without an instruction before the ROM counts as stuck, 10 frames of 1 m-cycle steps.
+const HALT_LIMIT: u32 = FRAME_CYCLES / 4 * 10;
+
+/* runs the ROM without a window, comparing the state before every instruction with the
+lines of a gameboy-doctor log. stops at the first line that differs, showing the lines
+before it and which fields differ. returns whether the whole log matched. */
+pub fn run(rom: &Path, log: &Path, context: usize, stub_ly: bool) -> Result<bool, String> {
+    let text =
+        fs::read_to_string(log).map_err(|e| format!("{:?} could not be read: {}", log, e))?;
+    let reference: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    let mut emulator = Emulator::new(&rom.to_path_buf(), None)
+        .map_err(|_| format!("{:?} is not a ROM that can be run", rom))?;
+    emulator.set_headless(stub_ly);
+
+    let mut history: VecDeque<String> = VecDeque::with_capacity(context + 1);
+    let (mut matched, mut halted) = (0, 0);
+    while matched < reference.len() {
+        let line = trace::doctor(emulator.cpu());
+        match emulator.step().result {
+            CPUResult::Ins(_) | CPUResult::InsCB(_) => halted = 0,
+            _ => {
+                halted += 1;
+                if halted > HALT_LIMIT {
+                    println!("Stuck in HALT after {} instructions", matched);
+                    return Ok(false);
+                }
+                continue;
+            }
+        }
+
+        let expected = reference[matched];
+        let differences = differences(expected, &line);
+        if !differences.is_empty() {
+            report(&history, matched, expected, &line, &differences);
+            return Ok(false);
+        }
+
+        history.push_back(line);
+        if history.len() > context {
+            history.pop_front();
+        }
+        matched += 1;
+    }
+
+    println!("All {} instructions match", matched);
+    Ok(true)
+}
+
+fn field<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    line.split_whitespace()
+        .filter_map(|field| field.split_once(':'))
+        .find(|(key, _)| *key == name)
+        .map(|(_, value)| value)
+}
+
+// the fields that differ, fields missing from the reference are not compared.
+fn differences(expected: &str, line: &str) -> Vec<&'static str> {
+    FIELDS
+        .iter()
+        .filter(|&&name| match (field(expected, name), field(line, name)) {
+            (Some(a), Some(b)) => !a.eq_ignore_ascii_case(b),
+            _ => false,
+        })
+        .copied()
+        .collect()
+}
+
+// the instruction of a line, from the bytes in PCMEM.
+fn instruction(line: &str) -> String {
+    let bytes: Vec<u8> = field(line, "PCMEM")
+        .unwrap_or("")
+        .split(',')
+        .filter_map(|b| u8::from_str_radix(b, 16).ok())
+        .collect();
+    match bytes[..] {
+        [a, b, c, d] => trace::disassemble([a, b, c, d]),
+        _ => "?".to_string(),
+    }
+}
+
+fn report(history: &VecDeque<String>, index: usize, expected: &str, line: &str, fields: &[&str]) {
+    println!("Line {} of the log differs\n", index + 1);
+    for (i, previous) in history.iter().enumerate() {
+        let number = index - history.len() + i + 1;
+        println!("{:>8}  {}  {}", number, previous, instruction(previous));
+    }
+    println!("expected  {}  {}", expected, instruction(expected));
+    println!("     got  {}  {}", line, instruction(line));
+
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|&name| {
+            format!(
+                "{} {} != {}",
+                name,
+                field(line, name).unwrap_or(""),
+                field(expected, name).unwrap_or("")
+            )
+        })
+        .collect();
+    println!("\n{}", fields.join(", "));
+}
@@ -1,48 +1,111 @@
-use std::fs::OpenOptions;
-use std::io::Write;
-use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::collections::VecDeque;
+use std::io;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 use std::{
     fs::{self},
     io::Read,
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
 use std::{thread, time};
 
-use dirs::config_local_dir;
+use sha1::{Digest, Sha1};
 
-use crate::circular::Circular;
-use crate::system::cpu::{CPUState, CPU};
-use crate::system::joypad::Input;
+use crate::cheat::{self, Cheat, Code};
+use crate::config::{Config, SyncMode, Trace};
+use crate::memory::{self, Freeze};
+use crate::movie::{Movie, MovieState, FRAME_CYCLES, HASH_INTERVAL};
+use crate::patch;
+use crate::player::LATENCY;
+use crate::ring::{Consumer, RingStats};
+use crate::system::apu::{Mixer, Sample};
+use crate::system::cpu::{CPUResult, CPUState, CPU};
+use crate::system::joypad::{Input, Opposing};
 use crate::system::mbc::{self, MBCError};
+use crate::system::mmu::MMU;
+use crate::system::ppu::PPU;
+use crate::system::sgb::Sgb;
+use crate::trace::Tracer;
 
 pub const CLOCK_FREQUENCY: u32 = 4_194_304;
 pub const STEP_TIME: u32 = 12;
 pub const STEP_CYCLES: u32 = (STEP_TIME as f64 / (1000_f64 / CLOCK_FREQUENCY as f64)) as u32;
 
+// largest change to the audio output rate that dynamic rate control is allowed to make.
+pub const MAX_RATE_DELTA: f64 = 0.005;
+
 pub enum EmulatorError {
     InvalidFileExtension,
+    // reset or power on without a ROM loaded.
+    NoRom,
     InvalidSave,
     InvalidType(u8),
     InvalidCGB,
+    // the patch could not be applied, with the reason.
+    InvalidPatch(String),
 }
 pub struct Emulator {
     cpu: CPU,
     save: PathBuf,
     clock: u32,
     now: Instant,
-    state_buffer: Circular<CPUState>,
+    sync: SyncMode,
+    // fill level of the audio buffer at the last audio sync, to tell whether audio is flowing.
+    fill: usize,
+    // the trace log of the CPU, while it is on.
+    tracer: Option<Tracer>,
+    sha1: String,
+    crc32: u32,
+    // t-cycles into the current frame. input from movies, turbo and macros changes on frame boundaries.
+    frame_cycles: u32,
+    // frames emulated since power on, videos write one for each.
+    frames: u64,
+    // bytes the memory viewer holds at a value.
+    freezes: Vec<Freeze>,
+    // the cheats of the game, and the GameShark writes of the enabled ones.
+    cheats: Vec<Cheat>,
+    shark: Vec<(u16, u8)>,
+    movie: Option<MovieState>,
+    // buttons held by the player.
+    input: u8,
+    // buttons with autofire held, pressed for the first half of every turbo_period frames.
+    turbo: u8,
+    turbo_period: u32,
+    turbo_frame: u32,
+    // joypad state for each of the next frames of a running macro.
+    macro_frames: VecDeque<u8>,
+    // writes the cartridge RAM to the save file. off for movies, which start without one.
+    persist: bool,
+    // skips frame pacing, running as fast as possible.
+    fast_forward: bool,
+    // multiplier of the emulation speed. anything but 1 is paced by time, as audio sync needs real time.
+    speed: f32,
 }
 
 impl Emulator {
-    pub fn new(rom_path: &PathBuf) -> Result<Box<Emulator>, EmulatorError> {
+    /* patch is applied to the ROM after it is read, without a patch one with the name of the ROM
+    is used if there is one. the file of the ROM is never changed. a patch that was found rather
+    than chosen is skipped if it can't be applied. */
+    pub fn new(rom_path: &PathBuf, patch: Option<&Path>) -> Result<Box<Emulator>, EmulatorError> {
         if rom_path.extension().unwrap().to_str().unwrap() != "gb" {
             return Err(EmulatorError::InvalidFileExtension);
         }
         let ram_path = rom_path.with_extension("sav");
-        let rom: Vec<u8> = std::fs::read(rom_path).unwrap();
+        let mut rom: Vec<u8> = std::fs::read(rom_path).unwrap();
+
+        if let Some(path) = patch {
+            rom = patch::apply(&rom, path).map_err(EmulatorError::InvalidPatch)?;
+            println!("Applied patch {:?}", path);
+        } else if let Some(path) = patch::find(rom_path) {
+            match patch::apply(&rom, &path) {
+                Ok(patched) => {
+                    rom = patched;
+                    println!("Applied patch {:?}", path);
+                }
+                Err(e) => println!("Patch not applied, loading the ROM without it: {}", e),
+            }
+        }
 
-        let cartridge = match mbc::new(rom) {
+        let cartridge = match mbc::new(rom.clone()) {
             Ok(c) => c,
             Err(MBCError::CGB) => return Err(EmulatorError::InvalidCGB),
             Err(MBCError::MBCType(t)) => return Err(EmulatorError::InvalidType(t)),
@@ -50,14 +113,41 @@ impl Emulator {
         };
 
         let save = ram_path.clone();
+        let sha1 = format!("{:x}", Sha1::digest(&rom));
+        let crc32 = crc32fast::hash(&rom);
 
-        Ok(Box::new(Emulator {
+        let mut emulator = Box::new(Emulator {
             cpu: CPU::new(cartridge),
             save,
             clock: 0,
             now: Instant::now(),
-            state_buffer: Circular::new(500),
-        }))
+            sync: SyncMode::Time,
+            fill: 0,
+            tracer: None,
+            sha1,
+            crc32,
+            frame_cycles: 0,
+            frames: 0,
+            freezes: Vec::new(),
+            cheats: Vec::new(),
+            shark: Vec::new(),
+            movie: None,
+            input: 0,
+            turbo: 0,
+            turbo_period: 6,
+            turbo_frame: 0,
+            macro_frames: VecDeque::new(),
+            persist: true,
+            fast_forward: false,
+            speed: 1.0,
+        });
+
+        match emulator.load_cheats() {
+            Ok(0) => (),
+            Ok(count) => println!("{} cheats loaded", count),
+            Err(e) => println!("Cheats could not be loaded: {}", e),
+        }
+        Ok(emulator)
     }
 
     pub fn load_save(&mut self, rom_path: &PathBuf) -> Result<(), EmulatorError> {
@@ -86,36 +176,110 @@ impl Emulator {
         self.cpu.mmu.cartridge.title()
     }
 
+    // hashes of the whole ROM file, as hex.
+    pub fn sha1(&self) -> &str {
+        &self.sha1
+    }
+
+    pub fn crc32(&self) -> String {
+        format!("{:08x}", self.crc32)
+    }
+
     pub fn step(&mut self) -> CPUState {
         // makes the emulator run at proper speed
         if self.clock > (STEP_CYCLES) {
             self.clock -= STEP_CYCLES;
-            let now = time::Instant::now();
-            let d = now.duration_since(self.now);
-            let s = STEP_TIME.saturating_sub(d.as_millis() as u32) as u64;
-            thread::sleep(time::Duration::from_millis(s));
-            self.now = self
-                .now
-                .checked_add(time::Duration::from_millis(u64::from(STEP_TIME)))
-                .unwrap();
-
-            // If now is after the just updated target frame time, reset to
-            // avoid drift.
-            if now.checked_duration_since(self.now).is_some() {
-                self.now = now;
+            match self.sync {
+                _ if self.fast_forward => (),
+                _ if self.speed != 1.0 => self.sync_time(),
+                SyncMode::Time => self.sync_time(),
+                SyncMode::Audio => self.sync_audio(),
             }
         }
 
+        let line = self
+            .tracer
+            .as_ref()
+            .and_then(|tracer| tracer.line(&self.cpu));
         let cpu_state = self.cpu.step();
-
-        self.state_buffer.push(cpu_state);
+        if let (Some(line), CPUResult::Ins(_) | CPUResult::InsCB(_)) = (line, cpu_state.result) {
+            if let Err(e) = self.tracer.as_mut().unwrap().write(&line) {
+                println!("Trace stopped, it could not be written: {}", e);
+                self.tracer = None;
+            }
+        }
 
         let t_cycles = cpu_state.timing * 4;
         self.clock += t_cycles as u32;
 
+        self.frame_cycles += t_cycles as u32;
+        if self.frame_cycles >= FRAME_CYCLES {
+            self.frame_cycles -= FRAME_CYCLES;
+            self.frames += 1;
+            self.input_frame();
+        }
+
         cpu_state
     }
 
+    // sets how the emulator keeps time. audio sync should only be used while a player is draining the buffer.
+    pub fn set_sync(&mut self, sync: SyncMode) {
+        self.sync = sync;
+        self.now = Instant::now();
+        self.cpu.mmu.apu.adjust_rate(1.0);
+    }
+
+    pub fn set_fast_forward(&mut self, on: bool) {
+        self.fast_forward = on;
+        self.now = Instant::now();
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+        self.now = Instant::now();
+    }
+
+    fn sync_time(&mut self) {
+        let step = time::Duration::from_secs_f32(STEP_TIME as f32 / 1000.0 / self.speed);
+        let now = time::Instant::now();
+        let d = now.duration_since(self.now);
+        thread::sleep(step.saturating_sub(d));
+        self.now = self.now.checked_add(step).unwrap();
+
+        // If now is after the just updated target frame time, reset to
+        // avoid drift.
+        if now.checked_duration_since(self.now).is_some() {
+            self.now = now;
+        }
+    }
+
+    fn sync_audio(&mut self) {
+        let apu = &self.cpu.mmu.apu;
+        let fill = apu.buffered();
+        // with the APU off, or the player not draining the buffer, there is no fill level to follow.
+        if !apu.on() || fill == self.fill {
+            self.fill = fill;
+            self.sync_time();
+            return;
+        }
+
+        // blocks while more than the target latency is queued, for at most the latency itself.
+        let target = (apu.sample_rate() * LATENCY / 1000) as usize;
+        let start = Instant::now();
+        while self.cpu.mmu.apu.buffered() > target
+            && start.elapsed() < time::Duration::from_millis(LATENCY as u64)
+        {
+            thread::sleep(time::Duration::from_millis(1));
+        }
+        self.now = Instant::now();
+
+        // when the buffer is fuller than the target, slightly fewer samples are generated and vice versa,
+        // so the fill level settles around the target instead of drifting into an underrun.
+        self.fill = self.cpu.mmu.apu.buffered();
+        let error = ((self.fill as f64 - target as f64) / target as f64).clamp(-1.0, 1.0);
+        self.cpu.mmu.apu.adjust_rate(1.0 - MAX_RATE_DELTA * error);
+    }
+
     pub fn screen_updated(&mut self) -> bool {
         let updated = self.cpu.mmu.ppu.v_blank;
         self.cpu.mmu.ppu.v_blank = false;
@@ -126,23 +290,404 @@ impl Emulator {
         self.cpu.mmu.ppu.buffer.to_vec()
     }
 
+    // for the VRAM and OAM viewers.
+    pub fn ppu(&self) -> &PPU {
+        &self.cpu.mmu.ppu
+    }
+
+    pub fn mmu(&self) -> &MMU {
+        &self.cpu.mmu
+    }
+
+    // the memory viewer edits the memory and the frozen bytes together.
+    pub fn memory(&mut self) -> (&mut MMU, &mut Vec<Freeze>) {
+        (&mut self.cpu.mmu, &mut self.freezes)
+    }
+
+    pub fn freezes(&self) -> &[Freeze] {
+        &self.freezes
+    }
+
+    // the layer of every pixel of the screen, see ppu::LAYER_BG.
+    pub fn layers(&self) -> &[u8] {
+        &self.cpu.mmu.ppu.layers
+    }
+
+    // without a movie, held buttons reach the joypad right away instead of at the next frame.
     pub fn key_up(&mut self, key: Option<Input>) {
         if key.is_some() {
-            self.cpu.mmu.joypad.key_up(key.unwrap())
+            self.input &= !key.unwrap().bit();
+            if self.movie.is_none() {
+                self.cpu.mmu.joypad.key_up(key.unwrap());
+            }
         }
     }
 
     pub fn key_down(&mut self, key: Option<Input>) {
         if key.is_some() {
-            self.cpu.mmu.joypad.key_down(key.unwrap())
+            self.input |= key.unwrap().bit();
+            if self.movie.is_none() {
+                self.cpu.mmu.joypad.key_down(key.unwrap());
+            }
         }
     }
 
-    pub fn audio(&self) -> Arc<Mutex<Vec<(f32, f32)>>> {
-        self.cpu.mmu.apu.buffer.clone()
+    // holds or lets go of autofire for a button.
+    pub fn turbo(&mut self, key: Input, pressed: bool) {
+        if self.turbo == 0 {
+            self.turbo_frame = 0;
+        }
+        if pressed {
+            self.turbo |= key.bit();
+        } else {
+            self.turbo &= !key.bit();
+        }
+    }
+
+    pub fn set_opposing(&mut self, opposing: Opposing) {
+        self.cpu.mmu.joypad.opposing = opposing;
+    }
+
+    // SGB mode for cartridges that support it.
+    pub fn set_sgb(&mut self, on: bool) {
+        self.cpu.mmu.set_sgb(on);
+    }
+
+    pub fn sgb(&self) -> Option<&Sgb> {
+        self.cpu.mmu.sgb.as_ref()
+    }
+
+    // joypads the game reads, more than 1 after an SGB multiplayer request.
+    pub fn players(&self) -> usize {
+        self.cpu.mmu.joypad.players() as usize
+    }
+
+    // buttons of joypads 2 - 4 go to the joypad directly, movies only hold the first one.
+    pub fn player_key(&mut self, player: usize, input: Input, pressed: bool) {
+        self.cpu.mmu.joypad.set_player_input(player, input, pressed);
+    }
+
+    // presses per second of autofire.
+    pub fn set_turbo_rate(&mut self, rate: u32) {
+        self.turbo_period = (60 / rate.max(1)).max(2);
+    }
+
+    // starts a macro on the next frame, replacing one that is still running.
+    pub fn play_macro(&mut self, frames: Vec<u8>) {
+        self.macro_frames = frames.into();
+    }
+
+    fn turbo_state(&mut self) -> u8 {
+        if self.turbo == 0 {
+            return 0;
+        }
+
+        let on = self.turbo_frame % self.turbo_period < self.turbo_period / 2;
+        self.turbo_frame += 1;
+        if on {
+            self.turbo
+        } else {
+            0
+        }
+    }
+
+    /* called at the start of every frame. turbo, macros, cheats and frozen bytes are applied here, so they are frame accurate.
+    movies only replay the joypad, so frozen bytes are left alone while one runs, and there are no cheats then. */
+    fn input_frame(&mut self) {
+        for &(address, value) in self.shark.iter() {
+            self.cpu.mmu.write_byte(value, address);
+        }
+
+        let freezes = match self.movie {
+            Some(_) => &[][..],
+            None => &self.freezes[..],
+        };
+        for freeze in freezes.iter() {
+            memory::write(
+                &mut self.cpu.mmu,
+                freeze.region,
+                freeze.offset,
+                freeze.value,
+            );
+        }
+
+        let state = self.input | self.turbo_state() | self.macro_frames.pop_front().unwrap_or(0);
+
+        match self.movie {
+            Some(_) => self.movie_frame(state),
+            None => self.cpu.mmu.joypad.set_state(state),
+        }
+    }
+
+    /* starts a movie. must be called right after power on, a movie with a save state loads it first.
+    makes the run deterministic - the RTC follows emulated time and no save file is written.
+    cheats are off during the movie, as they are not part of it. */
+    pub fn start_movie(&mut self, movie: MovieState) -> Result<(), String> {
+        if let MovieState::Playing { movie, .. } = &movie {
+            // movies from other emulators can come without a hash.
+            if !movie.sha1.is_empty() && movie.sha1 != self.sha1 {
+                return Err(format!("it was recorded on another ROM ({})", movie.sha1));
+            }
+        }
+        let state = match &movie {
+            MovieState::Recording(movie) | MovieState::Playing { movie, .. } => {
+                movie.state.as_ref()
+            }
+        };
+        if let Some(state) = state {
+            self.cpu
+                .load_state(state)
+                .map_err(|e| format!("its save state could not be loaded: {}", e))?;
+        }
+
+        self.cpu.mmu.cartridge.set_deterministic();
+        self.persist = false;
+        self.frame_cycles = 0;
+        self.input = 0;
+        self.turbo = 0;
+        self.macro_frames.clear();
+        self.cpu.mmu.joypad.set_state(0);
+
+        self.movie = Some(movie);
+        self.apply_cheats();
+        Ok(())
+    }
+
+    // a new movie of the game, starting from the save state of the game when from_state is set.
+    pub fn new_movie(&self, from_state: bool) -> io::Result<Movie> {
+        let mut movie = Movie::new(self.title(), self.sha1.clone());
+        if from_state {
+            movie.state = Some(fs::read(self.state_path())?);
+        }
+        Ok(movie)
+    }
+
+    // stops the movie, returning it if it was being recorded.
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        match self.movie.take() {
+            Some(MovieState::Recording(movie)) => {
+                self.end_movie();
+                Some(movie)
+            }
+            Some(MovieState::Playing { .. }) => {
+                self.end_movie();
+                None
+            }
+            None => None,
+        }
+    }
+
+    // undoes start_movie, the RTC follows the system clock again and the save file is written.
+    fn end_movie(&mut self) {
+        self.movie = None;
+        self.cpu.mmu.cartridge.set_real_time();
+        self.persist = true;
+        self.apply_cheats();
+    }
+
+    // records the joypad state of the frame, or replaces it with the one from the movie.
+    fn movie_frame(&mut self, input: u8) {
+        match self.movie.as_mut() {
+            Some(MovieState::Recording(movie)) => {
+                if movie.frames.len() % HASH_INTERVAL == 0 {
+                    movie.hashes.push(self.cpu.hash());
+                }
+                movie.frames.push(input);
+                self.cpu.mmu.joypad.set_state(input);
+            }
+
+            Some(MovieState::Playing { movie, frame }) => {
+                if *frame >= movie.frames.len() {
+                    println!("Movie finished after {} frames", frame);
+                    self.end_movie();
+                    return;
+                }
+
+                if *frame % HASH_INTERVAL == 0 {
+                    if let Some(expected) = movie.hashes.get(*frame / HASH_INTERVAL) {
+                        if *expected != self.cpu.hash() {
+                            println!("Movie desynced at frame {}", frame);
+                        }
+                    }
+                }
+
+                self.cpu.mmu.joypad.set_state(movie.frames[*frame]);
+                *frame += 1;
+            }
+
+            None => (),
+        }
+    }
+
+    // takes the reading end of the audio buffer for the player.
+    pub fn audio(&mut self) -> Option<Consumer<Sample>> {
+        self.cpu.mmu.apu.consumer()
+    }
+
+    // underrun/overrun counters of the audio buffer, for diagnostics.
+    pub fn audio_stats(&self) -> RingStats {
+        self.cpu.mmu.apu.buffer_stats()
+    }
+
+    pub fn set_audio_idle(&self, idle: bool) {
+        self.cpu.mmu.apu.set_idle(idle);
+    }
+
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        self.cpu.mmu.apu.set_mixer(mixer);
+    }
+
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    pub fn recording(&self) -> bool {
+        self.cpu.mmu.apu.recording()
+    }
+
+    // records the audio into a WAV at path, used for the audio of videos.
+    pub fn start_recording(&mut self, path: &Path, stems: bool) -> io::Result<()> {
+        self.cpu.mmu.apu.start_recording(path, stems)
+    }
+
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        self.cpu.mmu.apu.stop_recording()
+    }
+
+    // toggles recording the audio into the recordings folder. returns whether it is recording afterwards.
+    pub fn record(&mut self, stems: bool) -> io::Result<bool> {
+        if self.recording() {
+            self.stop_recording()?;
+            return Ok(false);
+        }
+
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let mut path = Config::dir("recordings");
+        path.push(format!("{}-{}.wav", self.title().trim(), time));
+
+        println!("Recording to {:?}", path);
+        self.start_recording(&path, stems)?;
+        Ok(true)
+    }
+
+    pub fn tracing(&self) -> bool {
+        self.tracer.is_some()
+    }
+
+    // starts the trace log of the CPU, see Tracer.
+    pub fn start_trace(&mut self, path: &Path, trace: Trace) -> io::Result<()> {
+        self.tracer = Some(Tracer::new(path, trace)?);
+        Ok(())
+    }
+
+    pub fn stop_trace(&mut self) -> Option<Tracer> {
+        self.tracer.take()
+    }
+
+    // one cheat file per game, named like the save states.
+    pub fn cheat_path(&self) -> PathBuf {
+        let mut path = Config::dir("cheats");
+        path.push(format!("{}-{}.yml", self.title().trim(), &self.sha1[..8]));
+        path
+    }
+
+    pub fn load_cheats(&mut self) -> io::Result<usize> {
+        self.cheats = cheat::load(&self.cheat_path())?;
+        self.apply_cheats();
+        Ok(self.cheats.len())
+    }
+
+    pub fn save_cheats(&self) -> io::Result<()> {
+        cheat::save(&self.cheat_path(), &self.cheats)
+    }
+
+    pub fn cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    // adds the cheats of a .cht file to the cheat file of the game.
+    pub fn import_cheats(&mut self, path: &Path) -> io::Result<usize> {
+        let imported = cheat::import(path)?;
+        let count = imported.len();
+        self.cheats.extend(imported);
+        self.save_cheats()?;
+        self.apply_cheats();
+        Ok(count)
+    }
+
+    pub fn toggle_cheat(&mut self, i: usize) -> io::Result<()> {
+        if let Some(cheat) = self.cheats.get_mut(i) {
+            cheat.enabled = !cheat.enabled;
+            self.apply_cheats();
+            self.save_cheats()?;
+        }
+        Ok(())
+    }
+
+    // decodes the enabled cheats into ROM patches and writes. invalid codes are skipped, none apply during a movie.
+    fn apply_cheats(&mut self) {
+        self.cpu.mmu.patches.clear();
+        self.shark.clear();
+        if self.movie.is_some() {
+            return;
+        }
+
+        for c in self.cheats.iter().filter(|c| c.enabled) {
+            match cheat::parse(&c.code) {
+                Ok(codes) => {
+                    for code in codes {
+                        match code {
+                            Code::Genie(patch) => self.cpu.mmu.patches.push(patch),
+                            Code::Shark { address, value } => self.shark.push((address, value)),
+                        }
+                    }
+                }
+                Err(e) => println!("Cheat {} could not be applied: {}", c.name, e),
+            }
+        }
+    }
+
+    // one save state per game, named after the title and ROM hash.
+    fn state_path(&self) -> PathBuf {
+        let mut path = Config::dir("states");
+        path.push(format!("{}-{}.state", self.title().trim(), &self.sha1[..8]));
+        path
+    }
+
+    // save states can't be used during a movie, the run would no longer follow its input.
+    pub fn save_state(&mut self) -> io::Result<PathBuf> {
+        if self.movie.is_some() {
+            return Err(io::Error::other("save states can't be used during a movie"));
+        }
+
+        let path = self.state_path();
+        fs::write(&path, self.cpu.save_state())?;
+        Ok(path)
+    }
+
+    pub fn load_state(&mut self) -> io::Result<PathBuf> {
+        if self.movie.is_some() {
+            return Err(io::Error::other("save states can't be used during a movie"));
+        }
+
+        let path = self.state_path();
+        self.cpu
+            .load_state(&fs::read(&path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // buttons held now are pressed in the loaded state too.
+        self.cpu.mmu.joypad.set_state(self.input);
+        self.apply_cheats();
+        Ok(path)
     }
 
     pub fn save(&mut self) {
+        if !self.persist {
+            return;
+        }
+
         let data = self.cpu.mmu.cartridge.save_ram();
 
         if data.is_some() {
@@ -152,6 +697,19 @@ impl Emulator {
         println!("Saved");
     }
 
+    /* runs without pacing or a save file, for tools that run a ROM without a window. stub_ly
+    makes LY read 0x90, which the logs of gameboy-doctor expect. */
+    pub fn set_headless(&mut self, stub_ly: bool) {
+        self.cpu.mmu.cartridge.set_deterministic();
+        self.cpu.mmu.stub_ly = stub_ly;
+        self.persist = false;
+        self.fast_forward = true;
+    }
+
+    pub fn cpu(&self) -> &CPU {
+        &self.cpu
+    }
+
     // sets the internal APU sample rate.
     pub fn sample(&mut self, sample: u32) {
         self.cpu.mmu.apu.sample(sample);
@@ -161,20 +719,6 @@ impl Emulator {
 // dumps save when exit.
 impl Drop for Emulator {
     fn drop(&mut self) {
-        let mut log_path = config_local_dir().unwrap();
-        log_path.push("kirboy/log.txt");
-
-        fs::write(&log_path, "");
-
-        let mut file = OpenOptions::new().append(true).open(&log_path).unwrap();
-
-        // Iterates through the circular queue and write each String to the file
-        for i in self.state_buffer.iter() {
-            file.write(i.display().as_bytes()).unwrap();
-            file.write(b"\n").unwrap(); // Add newline after each string
-        }
-
-        //println!("{:?}", self.state_buffer);
         self.save();
     }
 }
@@ -0,0 +1,305 @@
+use std::sync::mpsc::SyncSender;
+
+use crate::controller::ControllerRequest;
+
+/* gamepad events, sent to the controller as they happen.
+buttons and axes are numbered like the Linux joystick driver does for an xbox pad, so that the same
+bindings work everywhere: buttons A 0, B 1, X 2, Y 3, LB 4, RB 5, back 6, start 7, guide 8, sticks 9 and 10.
+axes left stick 0/1, left trigger 2, right stick 3/4, right trigger 5, d-pad 6/7.
+axis values go from -1 to 1, with -1 being left or up. */
+#[derive(Debug, Clone)]
+pub enum GamepadEvent {
+    Connected(usize, String),
+    Disconnected(usize),
+    Button(usize, u8, bool),
+    Axis(usize, u8, f32),
+}
+
+// names used for gamepad inputs in the keybinds.
+pub fn button_name(button: u8) -> String {
+    format!("pad:button{}", button)
+}
+
+pub fn axis_name(axis: u8, direction: i8) -> String {
+    format!("pad:axis{}{}", axis, if direction < 0 { '-' } else { '+' })
+}
+
+// starts looking for gamepads in the background. pads can be plugged in and out at any time.
+pub fn spawn(sender: SyncSender<ControllerRequest>) {
+    #[cfg(target_os = "linux")]
+    linux::spawn(sender);
+
+    #[cfg(target_os = "windows")]
+    windows::spawn(sender);
+
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        drop(sender);
+        println!("Gamepads are not supported on this platform");
+    }
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::{
+        collections::HashSet,
+        fs::{self, File},
+        io::Read,
+        path::PathBuf,
+        sync::{mpsc::SyncSender, Arc, Mutex},
+        thread,
+        time::Duration,
+    };
+
+    use super::GamepadEvent;
+    use crate::controller::ControllerRequest;
+
+    // how often /dev/input is checked for new pads.
+    const SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+    const JS_EVENT_BUTTON: u8 = 0x01;
+    const JS_EVENT_AXIS: u8 = 0x02;
+    // set on the events describing the state of the pad when it is opened.
+    const JS_EVENT_INIT: u8 = 0x80;
+
+    /* uses the joystick interface (/dev/input/jsN). every pad gets its own thread blocking on reads,
+    which ends when the pad is unplugged. */
+    pub fn spawn(sender: SyncSender<ControllerRequest>) {
+        thread::spawn(move || {
+            let open: Arc<Mutex<HashSet<PathBuf>>> = Arc::new(Mutex::new(HashSet::new()));
+            let mut failed: HashSet<PathBuf> = HashSet::new();
+
+            loop {
+                let entries = match fs::read_dir("/dev/input") {
+                    Ok(entries) => entries,
+                    Err(_) => return,
+                };
+
+                for entry in entries.flatten() {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let pad = match name.strip_prefix("js").and_then(|n| n.parse().ok()) {
+                        Some(pad) => pad,
+                        None => continue,
+                    };
+
+                    let path = entry.path();
+                    if open.lock().unwrap().contains(&path) {
+                        continue;
+                    }
+
+                    let file = match File::open(&path) {
+                        Ok(file) => file,
+                        Err(e) => {
+                            if failed.insert(path.clone()) {
+                                println!("Gamepad {:?} cannot be opened: {}", path, e);
+                            }
+                            continue;
+                        }
+                    };
+                    failed.remove(&path);
+                    open.lock().unwrap().insert(path.clone());
+
+                    let sender = sender.clone();
+                    let open = open.clone();
+                    thread::spawn(move || {
+                        read(pad, &name, file, &sender);
+                        open.lock().unwrap().remove(&path);
+                    });
+                }
+
+                thread::sleep(SCAN_INTERVAL);
+            }
+        });
+    }
+
+    // the product name of the pad, as given by sysfs.
+    fn device_name(name: &str) -> String {
+        fs::read_to_string(format!("/sys/class/input/{}/device/name", name))
+            .map(|name| name.trim().to_string())
+            .unwrap_or_else(|_| name.to_string())
+    }
+
+    fn read(pad: usize, name: &str, mut file: File, sender: &SyncSender<ControllerRequest>) {
+        let send = |event| sender.send(ControllerRequest::Gamepad(event)).is_ok();
+
+        if !send(GamepadEvent::Connected(pad, device_name(name))) {
+            return;
+        }
+
+        let mut buttons: HashSet<u8> = HashSet::new();
+        let mut axes: HashSet<u8> = HashSet::new();
+
+        // struct js_event { u32 time; i16 value; u8 type; u8 number; }
+        let mut event = [0u8; 8];
+        while file.read_exact(&mut event).is_ok() {
+            let value = i16::from_le_bytes([event[4], event[5]]);
+            let number = event[7];
+
+            let sent = match event[6] & !JS_EVENT_INIT {
+                JS_EVENT_BUTTON => {
+                    if value != 0 {
+                        buttons.insert(number);
+                    } else {
+                        buttons.remove(&number);
+                    }
+                    send(GamepadEvent::Button(pad, number, value != 0))
+                }
+                JS_EVENT_AXIS => {
+                    axes.insert(number);
+                    send(GamepadEvent::Axis(
+                        pad,
+                        number,
+                        value as f32 / i16::MAX as f32,
+                    ))
+                }
+                _ => true,
+            };
+
+            if !sent {
+                return;
+            }
+        }
+
+        // the pad is gone, let go of everything it was holding.
+        for button in buttons {
+            send(GamepadEvent::Button(pad, button, false));
+        }
+        for axis in axes {
+            send(GamepadEvent::Axis(pad, axis, 0.0));
+        }
+        send(GamepadEvent::Disconnected(pad));
+    }
+}
+
+#[cfg(target_os = "windows")]
+mod windows {
+    use std::{sync::mpsc::SyncSender, thread, time::Duration};
+
+    use windows_sys::Win32::UI::Input::XboxController::{
+        XInputGetState, XINPUT_GAMEPAD, XINPUT_GAMEPAD_A, XINPUT_GAMEPAD_B, XINPUT_GAMEPAD_BACK,
+        XINPUT_GAMEPAD_DPAD_DOWN, XINPUT_GAMEPAD_DPAD_LEFT, XINPUT_GAMEPAD_DPAD_RIGHT,
+        XINPUT_GAMEPAD_DPAD_UP, XINPUT_GAMEPAD_LEFT_SHOULDER, XINPUT_GAMEPAD_LEFT_THUMB,
+        XINPUT_GAMEPAD_RIGHT_SHOULDER, XINPUT_GAMEPAD_RIGHT_THUMB, XINPUT_GAMEPAD_START,
+        XINPUT_GAMEPAD_X, XINPUT_GAMEPAD_Y, XINPUT_STATE,
+    };
+
+    use super::GamepadEvent;
+    use crate::controller::ControllerRequest;
+
+    const PADS: usize = 4;
+    const POLL_INTERVAL: Duration = Duration::from_millis(4);
+    // axis changes smaller than this are not sent.
+    const AXIS_STEP: f32 = 0.01;
+
+    // XInput button flags in the numbering of the Linux driver.
+    const BUTTONS: [(u16, u8); 10] = [
+        (XINPUT_GAMEPAD_A, 0),
+        (XINPUT_GAMEPAD_B, 1),
+        (XINPUT_GAMEPAD_X, 2),
+        (XINPUT_GAMEPAD_Y, 3),
+        (XINPUT_GAMEPAD_LEFT_SHOULDER, 4),
+        (XINPUT_GAMEPAD_RIGHT_SHOULDER, 5),
+        (XINPUT_GAMEPAD_BACK, 6),
+        (XINPUT_GAMEPAD_START, 7),
+        (XINPUT_GAMEPAD_LEFT_THUMB, 9),
+        (XINPUT_GAMEPAD_RIGHT_THUMB, 10),
+    ];
+
+    fn stick(value: i16) -> f32 {
+        value as f32 / i16::MAX as f32
+    }
+
+    fn trigger(value: u8) -> f32 {
+        value as f32 / 255.0 * 2.0 - 1.0
+    }
+
+    fn dpad(buttons: u16, negative: u16, positive: u16) -> f32 {
+        match (buttons & negative != 0, buttons & positive != 0) {
+            (true, false) => -1.0,
+            (false, true) => 1.0,
+            _ => 0.0,
+        }
+    }
+
+    // the axes of a pad, with the y axes flipped so that up is negative.
+    fn axes(pad: &XINPUT_GAMEPAD) -> [f32; 8] {
+        [
+            stick(pad.sThumbLX),
+            -stick(pad.sThumbLY),
+            trigger(pad.bLeftTrigger),
+            stick(pad.sThumbRX),
+            -stick(pad.sThumbRY),
+            trigger(pad.bRightTrigger),
+            dpad(
+                pad.wButtons,
+                XINPUT_GAMEPAD_DPAD_LEFT,
+                XINPUT_GAMEPAD_DPAD_RIGHT,
+            ),
+            dpad(
+                pad.wButtons,
+                XINPUT_GAMEPAD_DPAD_UP,
+                XINPUT_GAMEPAD_DPAD_DOWN,
+            ),
+        ]
+    }
+
+    // XInput has no events, so the pads are polled.
+    pub fn spawn(sender: SyncSender<ControllerRequest>) {
+        thread::spawn(move || {
+            let send = |event| sender.send(ControllerRequest::Gamepad(event)).is_ok();
+            let mut last: [Option<XINPUT_GAMEPAD>; PADS] = [None; PADS];
+
+            loop {
+                for pad in 0..PADS {
+                    let mut state: XINPUT_STATE = unsafe { std::mem::zeroed() };
+                    let connected = unsafe { XInputGetState(pad as u32, &mut state) } == 0;
+
+                    let old = match (last[pad], connected) {
+                        (None, false) => continue,
+                        (None, true) => {
+                            if !send(GamepadEvent::Connected(pad, format!("XInput {}", pad))) {
+                                return;
+                            }
+                            // compared with an idle pad, so everything held is sent below.
+                            unsafe { std::mem::zeroed() }
+                        }
+                        (Some(old), _) => old,
+                    };
+
+                    // a disconnected pad lets go of everything.
+                    let new = if connected {
+                        state.Gamepad
+                    } else {
+                        unsafe { std::mem::zeroed() }
+                    };
+
+                    for (flag, button) in BUTTONS {
+                        let (was, is) = (old.wButtons & flag != 0, new.wButtons & flag != 0);
+                        if was != is && !send(GamepadEvent::Button(pad, button, is)) {
+                            return;
+                        }
+                    }
+
+                    for (axis, (was, is)) in axes(&old).iter().zip(axes(&new)).enumerate() {
+                        if (was - is).abs() > AXIS_STEP
+                            && !send(GamepadEvent::Axis(pad, axis as u8, is))
+                        {
+                            return;
+                        }
+                    }
+
+                    if connected {
+                        last[pad] = Some(new);
+                    } else {
+                        last[pad] = None;
+                        if !send(GamepadEvent::Disconnected(pad)) {
+                            return;
+                        }
+                    }
+                }
+
+                thread::sleep(POLL_INTERVAL);
+            }
+        });
+    }
+}
@@ -0,0 +1,352 @@
+use crate::emulator::CLOCK_FREQUENCY;
+use crate::movie::FRAME_CYCLES;
+use crate::player::{CpalPlayer, LATENCY};
+use crate::system::apu::APU;
+use crate::system::bus::Bus;
+use crate::system::cpu::registers::Register;
+use crate::system::cpu::CPU;
+use std::fs;
+use std::io::{self, BufRead};
+use std::path::Path;
+use std::sync::mpsc::{self, TryRecvError};
+use std::{thread, time};
+
+const HEADER_SIZE: usize = 0x70;
+
+// the routines return here, it is never run, the player stops when PC gets to it.
+const RETURN: u16 = 0xf00d;
+
+// a routine taking longer than a second is stuck, and is cut off.
+const ROUTINE_LIMIT: u32 = CLOCK_FREQUENCY;
+
+// t-cycles of one tick of the timer, by the clock select bits of TAC.
+const TIMER_CLOCKS: [u32; 4] = [1024, 16, 64, 256];
+
+/* the header of a GBS file. the code is loaded at load, init is called with the track in A
+and play is called at the rate of the timer, or of vblank if the timer is off. */
+pub struct Header {
+    pub songs: u8,
+    pub first: u8,
+    pub load: u16,
+    pub init: u16,
+    pub play: u16,
+    pub sp: u16,
+    pub tma: u8,
+    pub tac: u8,
+    pub title: String,
+    pub author: String,
+    pub copyright: String,
+}
+
+impl Header {
+    pub fn parse(data: &[u8]) -> Result<Self, String> {
+        if data.len() < HEADER_SIZE || &data[0..3] != b"GBS" {
+            return Err("not a GBS file".to_string());
+        }
+        if data[3] != 1 {
+            return Err(format!("GBS version {} is not supported", data[3]));
+        }
+
+        let word = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+        let text = |i: usize| {
+            let bytes = &data[i..i + 32];
+            let end = bytes.iter().position(|&b| b == 0).unwrap_or(32);
+            String::from_utf8_lossy(&bytes[..end]).trim().to_string()
+        };
+        let header = Self {
+            songs: data[4],
+            first: data[5].max(1),
+            load: word(6),
+            init: word(8),
+            play: word(0xa),
+            sp: word(0xc),
+            tma: data[0xe],
+            tac: data[0xf],
+            title: text(0x10),
+            author: text(0x30),
+            copyright: text(0x50),
+        };
+        if header.songs == 0 {
+            return Err("the file has no songs".to_string());
+        }
+        if header.load < 0x400 || header.load >= 0x8000 {
+            return Err(format!("load address {:04X} is not in ROM", header.load));
+        }
+        Ok(header)
+    }
+}
+
+/* the memory map of a GBS player, ROM with switchable banks at 0x4000, RAM, the APU and the
+timer registers, which only set the rate of the play routine. */
+pub struct GbsBus {
+    rom: Vec<u8>,
+    bank: usize,
+    ram: Vec<u8>,
+    pub apu: APU,
+    tma: u8,
+    tac: u8,
+    inte: u8,
+    intf: u8,
+}
+
+impl Bus for GbsBus {
+    fn read_byte(&self, address: u16) -> u8 {
+        let address = address as usize;
+        match address {
+            0x0000..=0x3fff => self.rom.get(address).copied().unwrap_or(0xff),
+            0x4000..=0x7fff => self
+                .rom
+                .get(self.bank * 0x4000 + address - 0x4000)
+                .copied()
+                .unwrap_or(0xff),
+            0xe000..=0xfdff => self.ram[address - 0x2000],
+            0xff06 => self.tma,
+            0xff07 => self.tac,
+            0xff0f => 0xe0 | self.intf,
+            0xff10..=0xff3f => self.apu.read(address as u16),
+            0xffff => self.inte,
+            0xff00..=0xff7f => 0xff,
+            _ => self.ram[address],
+        }
+    }
+
+    fn write_byte(&mut self, value: u8, address: u16) {
+        match address {
+            // like MBC1, bank 0 is mapped as bank 1.
+            0x2000..=0x3fff => self.bank = (value as usize).max(1),
+            0x0000..=0x7fff => (),
+            0xe000..=0xfdff => self.ram[address as usize - 0x2000] = value,
+            0xff06 => self.tma = value,
+            0xff07 => self.tac = value,
+            0xff0f => self.intf = value & 0x1f,
+            0xff10..=0xff3f => self.apu.write(value, address),
+            0xffff => self.inte = value,
+            0xff00..=0xff7f => (),
+            _ => self.ram[address as usize] = value,
+        }
+    }
+
+    fn step(&mut self, m_cycles: u8) {
+        self.apu.step(m_cycles);
+    }
+
+    fn interrupts(&self) -> (u8, u8) {
+        (self.inte, self.intf)
+    }
+
+    fn acknowledge(&mut self, interrupt: u8) {
+        self.intf &= !(1 << interrupt);
+    }
+}
+
+// a GBS file loaded into the SM83 core, playing one of its tracks.
+pub struct Gbs {
+    pub header: Header,
+    cpu: CPU<GbsBus>,
+    // the track playing, from 0.
+    pub track: u8,
+}
+
+impl Gbs {
+    pub fn new(path: &Path) -> Result<Self, String> {
+        let data = fs::read(path).map_err(|e| format!("{:?} could not be read: {}", path, e))?;
+        let header = Header::parse(&data)?;
+
+        // the code goes at the load address, the RST vectors jump to the load address plus theirs.
+        let mut rom = vec![0; header.load as usize];
+        rom.extend_from_slice(&data[HEADER_SIZE..]);
+        for vector in (0..0x40).step_by(8) {
+            let [low, high] = (header.load + vector as u16).to_le_bytes();
+            rom[vector..vector + 3].copy_from_slice(&[0xc3, low, high]);
+        }
+
+        let bus = GbsBus {
+            rom,
+            bank: 1,
+            ram: vec![0; 0x10000],
+            apu: APU::new(),
+            tma: header.tma,
+            tac: header.tac,
+            inte: 0,
+            intf: 0,
+        };
+        let track = header.first - 1;
+        let mut gbs = Self {
+            header,
+            cpu: CPU::with_bus(bus),
+            track: 0,
+        };
+        gbs.start(track);
+        Ok(gbs)
+    }
+
+    pub fn apu(&mut self) -> &mut APU {
+        &mut self.cpu.mmu.apu
+    }
+
+    // clears the RAM and the sound, then calls init for the track.
+    pub fn start(&mut self, track: u8) {
+        self.track = track % self.header.songs;
+
+        let bus = &mut self.cpu.mmu;
+        bus.ram.iter_mut().for_each(|b| *b = 0);
+        bus.bank = 1;
+        bus.tma = self.header.tma;
+        bus.tac = self.header.tac;
+        bus.inte = 0;
+        bus.intf = 0;
+        // turning the APU off and on clears its registers.
+        bus.write_byte(0x00, 0xff26);
+        bus.write_byte(0x80, 0xff26);
+        bus.write_byte(0x77, 0xff24);
+        bus.write_byte(0xff, 0xff25);
+
+        self.cpu.ime = false;
+        self.cpu.halted = false;
+        self.cpu.registers.set(Register::A, self.track);
+        self.cpu.set_sp(self.header.sp);
+        self.call(self.header.init);
+    }
+
+    pub fn next(&mut self) {
+        self.start((self.track + 1) % self.header.songs);
+    }
+
+    pub fn previous(&mut self) {
+        self.start((self.track + self.header.songs - 1) % self.header.songs);
+    }
+
+    // t-cycles between calls of play, from the timer, or vblank if the timer is off.
+    fn period(&self) -> u32 {
+        let (tma, tac) = (self.cpu.mmu.tma, self.cpu.mmu.tac);
+        if tac & 0x04 == 0 {
+            return FRAME_CYCLES;
+        }
+        let period = (256 - tma as u32) * TIMER_CLOCKS[(tac & 0x3) as usize];
+        // bit 7 asks for the double speed of the CGB.
+        if tac & 0x80 != 0 {
+            period / 2
+        } else {
+            period
+        }
+    }
+
+    // runs a routine until it returns, returning the t-cycles it took.
+    fn call(&mut self, address: u16) -> u32 {
+        let sp = self.cpu.sp().wrapping_sub(2);
+        self.cpu.mmu.write_word(RETURN, sp);
+        self.cpu.set_sp(sp);
+        self.cpu.set_pc(address);
+
+        let mut cycles = 0;
+        while self.cpu.pc() != RETURN && cycles < ROUTINE_LIMIT {
+            cycles += self.cpu.step().timing as u32 * 4;
+        }
+        cycles
+    }
+
+    // calls play, then lets the sound run for the rest of the period. returns the t-cycles.
+    pub fn tick(&mut self) -> u32 {
+        let period = self.period();
+        let mut cycles = self.call(self.header.play);
+        while cycles < period {
+            let m_cycles = ((period - cycles) / 4).clamp(1, 255);
+            self.cpu.mmu.step(m_cycles as u8);
+            cycles += m_cycles * 4;
+        }
+        cycles
+    }
+
+    fn describe(&self) -> String {
+        format!("Track {}/{}", self.track + 1, self.header.songs)
+    }
+}
+
+// renders seconds of a track into a WAV, as fast as it can.
+pub fn render(path: &Path, track: Option<u8>, output: &Path, seconds: u32) -> Result<(), String> {
+    let mut gbs = Gbs::new(path)?;
+    if let Some(track) = track {
+        gbs.start(track);
+    }
+    gbs.apu()
+        .start_recording(output, false)
+        .map_err(|e| format!("{:?} could not be written: {}", output, e))?;
+
+    let total = seconds as u64 * CLOCK_FREQUENCY as u64;
+    let mut cycles = 0u64;
+    while cycles < total {
+        cycles += gbs.tick() as u64;
+    }
+    gbs.apu()
+        .stop_recording()
+        .map_err(|e| format!("{:?} could not be written: {}", output, e))?;
+    println!("{} rendered to {:?}", gbs.describe(), output);
+    Ok(())
+}
+
+/* plays the GBS file, reading commands from the terminal: n for the next track, p for the
+previous one, a number for that track and q to quit. */
+pub fn play(path: &Path, track: Option<u8>) -> Result<(), String> {
+    let mut gbs = Gbs::new(path)?;
+    if let Some(track) = track {
+        gbs.start(track);
+    }
+
+    let player = gbs
+        .apu()
+        .consumer()
+        .and_then(CpalPlayer::new)
+        .ok_or("no audio output")?;
+    gbs.apu().sample(player.sample());
+    player.play();
+
+    let header = &gbs.header;
+    println!("{}", header.title);
+    println!("{}", header.author);
+    println!("{}", header.copyright);
+    println!(
+        "n: next track, p: previous track, 1-{}: track, q: quit",
+        header.songs
+    );
+    println!("{}", gbs.describe());
+
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else { break };
+            if sender.send(line.trim().to_lowercase()).is_err() {
+                break;
+            }
+        }
+    });
+
+    let target = (gbs.apu().sample_rate() * LATENCY / 1000) as usize;
+    loop {
+        match receiver.try_recv() {
+            Ok(command) => {
+                match command.as_str() {
+                    "q" => break,
+                    "n" => gbs.next(),
+                    "p" => gbs.previous(),
+                    number => match number.parse::<u8>() {
+                        Ok(n) if (1..=gbs.header.songs).contains(&n) => gbs.start(n - 1),
+                        _ => {
+                            println!("Unknown command '{}'", number);
+                            continue;
+                        }
+                    },
+                }
+                println!("{}", gbs.describe());
+            }
+            // without a terminal the track keeps playing.
+            Err(TryRecvError::Empty) | Err(TryRecvError::Disconnected) => (),
+        }
+
+        // keeps about the latency of audio queued, like syncing the emulator to audio.
+        while gbs.apu().buffered() > target * 2 {
+            thread::sleep(time::Duration::from_millis(1));
+        }
+        gbs.tick();
+    }
+    Ok(())
+}
@@ -0,0 +1,330 @@
+/* bindings are written as an optional list of modifiers and a key, joined by +, like "ctrl+shift+f1".
+a key is one of
+- a key name, like "z", "enter", "f1" or "numpad4", following the keyboard layout.
+- a scan code, like "code:KeyZ", which is the same physical key on every layout.
+- a gamepad input, "pad:buttonN" or "pad:axisN+" / "pad:axisN-". */
+
+pub const CTRL: u8 = 0x1;
+pub const SHIFT: u8 = 0x2;
+pub const ALT: u8 = 0x4;
+pub const SUPER: u8 = 0x8;
+// a key with these modifiers matches its bindings whatever modifiers they have, for releases.
+pub const ANY: u8 = CTRL | SHIFT | ALT | SUPER;
+
+const MODIFIERS: [(&str, u8); 8] = [
+    ("ctrl", CTRL),
+    ("control", CTRL),
+    ("shift", SHIFT),
+    ("alt", ALT),
+    ("option", ALT),
+    ("super", SUPER),
+    ("cmd", SUPER),
+    ("meta", SUPER),
+];
+
+// names given to keys other than characters, see to_text in main.rs.
+pub const NAMED_KEYS: [&str; 40] = [
+    "enter",
+    "backspace",
+    "tab",
+    "space",
+    "escape",
+    "shift",
+    "ctrl",
+    "alt",
+    "super",
+    "capslock",
+    "up",
+    "down",
+    "left",
+    "right",
+    "insert",
+    "delete",
+    "home",
+    "end",
+    "pageup",
+    "pagedown",
+    "pause",
+    "printscreen",
+    "f1",
+    "f2",
+    "f3",
+    "f4",
+    "f5",
+    "f6",
+    "f7",
+    "f8",
+    "f9",
+    "f10",
+    "f11",
+    "f12",
+    "numpadenter",
+    "numpadadd",
+    "numpadsubtract",
+    "numpadmultiply",
+    "numpaddivide",
+    "numpaddecimal",
+];
+
+// a pressed or released key, with every name it can be bound by and the modifiers held with it.
+#[derive(Clone, Debug)]
+pub struct Key {
+    pub names: Vec<String>,
+    pub modifiers: u8,
+    // the gamepad the key is on, none for the keyboard.
+    pub pad: Option<usize>,
+}
+
+impl Key {
+    pub fn new(name: String) -> Self {
+        Self {
+            names: vec![name],
+            modifiers: 0,
+            pad: None,
+        }
+    }
+
+    pub fn pad(pad: usize, name: String) -> Self {
+        Self {
+            pad: Some(pad),
+            ..Key::new(name)
+        }
+    }
+}
+
+#[derive(PartialEq)]
+pub struct Binding {
+    modifiers: u8,
+    key: String,
+}
+
+impl Binding {
+    pub fn parse(binding: &str) -> Result<Binding, String> {
+        let binding = binding.trim();
+
+        // modifiers are taken from the front, so "+", "ctrl++" and "pad:axis1+" keep their + in the key.
+        let mut modifiers = 0;
+        let mut key = binding;
+        while let Some((modifier, rest)) = key.split_once('+') {
+            let bit = MODIFIERS
+                .iter()
+                .find(|(name, _)| name.eq_ignore_ascii_case(modifier.trim()));
+            match bit {
+                Some((_, bit)) if !rest.is_empty() => {
+                    modifiers |= bit;
+                    key = rest;
+                }
+                _ => break,
+            }
+        }
+
+        // scan codes are case sensitive, key names are not.
+        let key = if key.starts_with("code:") {
+            key.to_string()
+        } else {
+            key.to_lowercase()
+        };
+
+        if !valid_key(&key) {
+            return Err(format!(
+                "unknown key or modifier '{}' in '{}'",
+                key, binding
+            ));
+        }
+
+        Ok(Binding { modifiers, key })
+    }
+
+    /* true if the key is the bound one and exactly the modifiers of the binding are held, so "a" does
+    not fire on ctrl+a. a modifier key may count itself as held, and characters like "!" need shift
+    on most layouts, so those bits are left out of the comparison. */
+    pub fn matches(&self, key: &Key) -> bool {
+        if !key.names.contains(&self.key) {
+            return false;
+        }
+        if key.modifiers == ANY {
+            return true;
+        }
+
+        let ignored = ignored(&self.key, key);
+        key.modifiers & !ignored == self.modifiers & !ignored
+    }
+
+    // the bit of the modifier, if the binding is a modifier key on its own, like "shift".
+    pub fn modifier(&self) -> u8 {
+        MODIFIERS
+            .iter()
+            .find(|(name, _)| *name == self.key)
+            .map_or(0, |(_, bit)| *bit)
+    }
+
+    /* the binding of the key as it was pressed, with the modifiers held, written the way parse
+    reads it, like "ctrl+shift+f1". the modifiers matches leaves out are left out here too. */
+    pub fn text(key: &Key) -> Option<String> {
+        let name = key.names.first()?;
+        let modifiers = if name.starts_with("pad:") {
+            0
+        } else {
+            key.modifiers & !ignored(name, key)
+        };
+        let mut text: String = [
+            ("ctrl", CTRL),
+            ("shift", SHIFT),
+            ("alt", ALT),
+            ("super", SUPER),
+        ]
+        .iter()
+        .filter(|(_, bit)| modifiers & bit != 0)
+        .map(|(modifier, _)| format!("{}+", modifier))
+        .collect();
+        text.push_str(name);
+        Some(text)
+    }
+}
+
+// the modifier bits left out when the key is compared with a binding of the name.
+fn ignored(name: &str, key: &Key) -> u8 {
+    let mut ignored = MODIFIERS
+        .iter()
+        .filter(|(modifier, _)| key.names.iter().any(|n| n == modifier))
+        .fold(0, |bits, (_, bit)| bits | bit);
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        if !c.is_alphanumeric() {
+            ignored |= SHIFT;
+        }
+    }
+    ignored
+}
+
+fn valid_key(key: &str) -> bool {
+    if let Some(code) = key.strip_prefix("code:") {
+        return !code.is_empty() && code.chars().all(|c| c.is_ascii_alphanumeric());
+    }
+
+    if let Some(input) = key.strip_prefix("pad:") {
+        let number = match input.strip_prefix("button") {
+            Some(button) => button,
+            None => match input.strip_prefix("axis") {
+                Some(axis) => match axis.strip_suffix(['+', '-']) {
+                    Some(axis) => axis,
+                    None => return false,
+                },
+                None => return false,
+            },
+        };
+        return number.parse::<u8>().is_ok();
+    }
+
+    if let Some(digit) = key.strip_prefix("numpad") {
+        if digit.len() == 1 && digit.chars().all(|c| c.is_ascii_digit()) {
+            return true;
+        }
+    }
+
+    key.chars().count() == 1 || NAMED_KEYS.contains(&key)
+}
+
+// true if any of the bindings matches the key. invalid bindings never match, they are reported by Config::validate.
+pub fn bound(bindings: &[String], key: &Key) -> bool {
+    bindings
+        .iter()
+        .filter_map(|binding| Binding::parse(binding).ok())
+        .any(|binding| binding.matches(key))
+}
+
+// true for a modifier key, like shift, which is held for other keys. a gamepad input never is one.
+pub fn is_modifier(key: &Key) -> bool {
+    key.names
+        .first()
+        .is_some_and(|name| MODIFIERS.iter().any(|(modifier, _)| modifier == name))
+}
+
+// the modifiers among the bindings that are bound on their own, like shift for select.
+pub fn held(bindings: &[String]) -> u8 {
+    bindings
+        .iter()
+        .filter_map(|binding| Binding::parse(binding).ok())
+        .fold(0, |bits, binding| bits | binding.modifier())
+}
+
+/* like bound, but a key pressed with held modifiers that are buttons themselves also matches the
+bindings without them, so select bound to shift can be held while pressing a or start. */
+pub fn bound_holding(bindings: &[String], key: &Key, held: u8) -> bool {
+    if bound(bindings, key) {
+        return true;
+    }
+    if key.modifiers == ANY || key.modifiers & held == 0 {
+        return false;
+    }
+    let key = Key {
+        modifiers: key.modifiers & !held,
+        ..key.clone()
+    };
+    bound(bindings, &key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(names: &[&str], modifiers: u8) -> Key {
+        Key {
+            names: names.iter().map(|n| n.to_string()).collect(),
+            modifiers,
+            pad: None,
+        }
+    }
+
+    #[test]
+    fn modifiers_match_exactly() {
+        let a = Binding::parse("a").unwrap();
+        let ctrl_a = Binding::parse("Ctrl+A").unwrap();
+        assert!(a.matches(&key(&["a"], 0)));
+        assert!(!a.matches(&key(&["a"], CTRL)));
+        assert!(ctrl_a.matches(&key(&["a"], CTRL)));
+        assert!(!ctrl_a.matches(&key(&["a"], CTRL | SHIFT)));
+        assert!(ctrl_a == Binding::parse("control+a").unwrap());
+        // releases match whatever is held.
+        assert!(a.matches(&key(&["a"], ANY)));
+    }
+
+    #[test]
+    fn shifted_characters_and_modifier_keys() {
+        assert!(Binding::parse("!")
+            .unwrap()
+            .matches(&key(&["!", "code:Digit1"], SHIFT)));
+        assert!(Binding::parse("shift")
+            .unwrap()
+            .matches(&key(&["shift"], SHIFT)));
+        assert!(!Binding::parse("1")
+            .unwrap()
+            .matches(&key(&["!", "code:Digit1"], SHIFT)));
+    }
+
+    #[test]
+    fn select_held_and_a_pressed() {
+        let select = ["shift".to_string(), "pad:button6".to_string()];
+        let a = ["z".to_string()];
+        let ctrl_a = ["ctrl+z".to_string()];
+        let held = held(&select);
+        assert_eq!(held, SHIFT);
+        assert!(!bound(&a, &key(&["z"], SHIFT)));
+        assert!(bound_holding(&a, &key(&["z"], SHIFT), held));
+        // other modifiers still have to match.
+        assert!(!bound_holding(&a, &key(&["z"], CTRL | SHIFT), held));
+        assert!(bound_holding(&ctrl_a, &key(&["z"], CTRL | SHIFT), held));
+        assert!(bound_holding(&select, &key(&["shift"], SHIFT), held));
+    }
+
+    #[test]
+    fn text_of_a_pressed_key() {
+        let text = |names: &[&str], modifiers| Binding::text(&key(names, modifiers)).unwrap();
+        assert_eq!(text(&["f1"], CTRL | SHIFT), "ctrl+shift+f1");
+        assert_eq!(text(&["!", "code:Digit1"], SHIFT), "!");
+        assert_eq!(text(&["shift"], SHIFT), "shift");
+        assert!(Binding::parse(&text(&["a"], ALT))
+            .unwrap()
+            .matches(&key(&["a"], ALT)));
+    }
+}
@@ -2,7 +2,8 @@
 #![windows_subsystem = "windows"]
 //#![forbid(unsafe_code)]
 
-use controller::{Controller, ControllerRequest, ControllerResponse};
+use config::ProfileChoice;
+use controller::{Controller, ControllerRequest, ControllerResponse, MixerRequest};
 use dirs::download_dir;
 use emulator::EmulatorError;
 use error_iter::ErrorIter as _;
@@ -17,9 +18,10 @@ use std::thread;
 use system::mbc::new;
 use tao::dpi::LogicalSize;
 use tao::event::{ElementState, Event, WindowEvent};
-use tao::event_loop::{ControlFlow, EventLoopBuilder};
-use tao::keyboard::Key;
+use tao::event_loop::{ControlFlow, EventLoopBuilder, EventLoopWindowTarget};
+use tao::keyboard::{Key, KeyCode, KeyLocation, ModifiersState};
 use tao::window::{Window, WindowBuilder};
+use viewer::{View, VIEWS};
 
 use rfd::FileDialog;
 
@@ -28,13 +30,32 @@ use muda::{
     Menu, MenuEvent, MenuItem, PredefinedMenuItem, Submenu,
 };
 
-mod circular;
+mod blargg;
+mod capture;
+mod cheat;
+mod cli;
 mod config;
 mod controller;
+mod doctor;
 mod emulator;
+mod font;
+mod gamepad;
+mod gbs;
+mod keybind;
+mod memory;
+mod movie;
+mod palette;
+mod patch;
 mod player;
+mod profile;
 mod renderer;
+mod ring;
+mod settings;
+mod sm83;
 mod system;
+mod trace;
+mod viewer;
+mod wav;
 
 #[cfg(target_os = "macos")]
 use tao::platform::macos::WindowBuilderExtMacOS;
@@ -48,9 +69,20 @@ use tao::platform::windows::{
 const WIDTH: u32 = 160;
 const HEIGHT: u32 = 144;
 
+// names of the APU channels, as shown in the audio menu.
+const CHANNELS: [&str; 4] = ["Square 1", "Square 2", "Wave", "Noise"];
+
+// master volume presets in the audio menu.
+const VOLUMES: [u32; 5] = [100, 75, 50, 25, 0];
+
 fn main() -> Result<(), Error> {
     env_logger::init();
 
+    // subcommands run without a window, and exit when they are done.
+    if let Some(code) = cli::run() {
+        std::process::exit(code);
+    }
+
     let mut event_loop_builder = EventLoopBuilder::<MenuEvent>::with_user_event();
 
     let menu_bar = Menu::new();
@@ -90,6 +122,8 @@ fn main() -> Result<(), Error> {
         drop(controller);
     });
 
+    gamepad::spawn(input_sender.clone());
+
     let file = file_dialog(None);
 
     if file.is_none() {
@@ -132,7 +166,9 @@ fn main() -> Result<(), Error> {
         }
     };
 
-    let (mut pixels, mut renderer) = new_renderer(&window, None);
+    let (mut pixels, mut renderer) = new_renderer(&window, None, (WIDTH, HEIGHT));
+    // size of the frames being drawn, which grows when a game sends an SGB border.
+    let mut size = (WIDTH, HEIGHT);
 
     reload(file.unwrap(), &input_sender);
 
@@ -162,6 +198,8 @@ fn main() -> Result<(), Error> {
         }),
     );
 
+    let apply_patch = MenuItem::with_id("apply_patch", "Apply Patch...", true, None);
+
     let config_open = MenuItem::with_id(
         "config",
         "Config",
@@ -184,6 +222,37 @@ fn main() -> Result<(), Error> {
         }),
     );
 
+    let settings = MenuItem::with_id("settings", "Settings", true, None);
+    let import_palette = MenuItem::with_id("import_palette", "Import Palette...", true, None);
+
+    // the automatic and global choices, then one item per profile in the config.
+    let profile_auto = CheckMenuItem::with_id("profile_auto", "Automatic", true, true, None);
+    let profile_global =
+        CheckMenuItem::with_id("profile_global", "Global Config", true, false, None);
+    let profile_names = config::Config::profile_names();
+    let profiles: Vec<CheckMenuItem> = profile_names
+        .iter()
+        .enumerate()
+        .map(|(i, name)| CheckMenuItem::with_id(format!("profile{}", i), name, true, false, None))
+        .collect();
+
+    let record = CheckMenuItem::with_id("record", "Record Audio", true, false, None);
+    let record_video = CheckMenuItem::with_id("record_video", "Record Video", true, false, None);
+
+    let record_movie = CheckMenuItem::with_id("record_movie", "Record Movie", true, false, None);
+    let record_movie_state = CheckMenuItem::with_id(
+        "record_movie_state",
+        "Record Movie From State",
+        true,
+        false,
+        None,
+    );
+    let play_movie = MenuItem::with_id("play_movie", "Play Movie...", true, None);
+    let export_movie = MenuItem::with_id("export_movie", "Export Movie to BK2...", true, None);
+
+    let import_cheats = MenuItem::with_id("import_cheats", "Import Cheats...", true, None);
+    let open_cheats = MenuItem::with_id("open_cheats", "Open Cheat File", true, None);
+
     let shader_switch = MenuItem::with_id(
         "shader",
         "Switch Shader",
@@ -213,10 +282,79 @@ fn main() -> Result<(), Error> {
         ]);
     }
 
+    // the checks start from the config file, the controller sends the mixer again when it changes.
+    let mixer = config::Config::read().map(|c| c.mixer).unwrap_or_default();
+    let mute: Vec<CheckMenuItem> = CHANNELS
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            CheckMenuItem::with_id(
+                format!("mute{}", i),
+                format!("Mute {}", name),
+                true,
+                mixer.mute[i],
+                None,
+            )
+        })
+        .collect();
+
+    let solo: Vec<CheckMenuItem> = CHANNELS
+        .iter()
+        .enumerate()
+        .map(|(i, name)| {
+            CheckMenuItem::with_id(
+                format!("solo{}", i),
+                format!("Solo {}", name),
+                true,
+                mixer.solo[i],
+                None,
+            )
+        })
+        .collect();
+
+    let volume: Vec<MenuItem> = VOLUMES
+        .iter()
+        .map(|v| MenuItem::with_id(format!("volume{}", v), format!("{}%", v), true, None))
+        .collect();
+
+    let high_pass = CheckMenuItem::with_id("high_pass", "DMG Filter", true, mixer.high_pass, None);
+
+    let trace = CheckMenuItem::with_id("trace", "Trace Log", true, false, None);
+
+    let view_items: Vec<CheckMenuItem> = VIEWS
+        .iter()
+        .enumerate()
+        .map(|(i, view)| {
+            CheckMenuItem::with_id(format!("view{}", i), view.name(), true, false, None)
+        })
+        .collect();
+
     let file_m = Submenu::new("&File", true);
+    let audio_m = Submenu::new("&Audio", true);
+    let volume_m = Submenu::new("Volume", true);
     let window_m = Submenu::new("&Window", true);
-
-    file_m.append_items(&[&open, &config_open, &config_reload]);
+    let profile_m = Submenu::new("&Profile", true);
+    let debug_m = Submenu::new("&Debug", true);
+
+    file_m.append_items(&[
+        &open,
+        &apply_patch,
+        &config_open,
+        &config_reload,
+        &settings,
+        &import_palette,
+        &PredefinedMenuItem::separator(),
+        &record,
+        &record_video,
+        &PredefinedMenuItem::separator(),
+        &record_movie,
+        &record_movie_state,
+        &play_movie,
+        &export_movie,
+        &PredefinedMenuItem::separator(),
+        &import_cheats,
+        &open_cheats,
+    ]);
 
     window_m.append_items(&[
         &shader_switch,
@@ -227,7 +365,34 @@ fn main() -> Result<(), Error> {
         &PredefinedMenuItem::bring_all_to_front(None),
     ]);
 
-    menu_bar.append_items(&[&file_m, &window_m]);
+    for item in volume.iter() {
+        volume_m.append(item);
+    }
+    audio_m.append(&volume_m);
+    audio_m.append(&PredefinedMenuItem::separator());
+    for item in mute.iter() {
+        audio_m.append(item);
+    }
+    audio_m.append(&PredefinedMenuItem::separator());
+    for item in solo.iter() {
+        audio_m.append(item);
+    }
+    audio_m.append_items(&[&PredefinedMenuItem::separator(), &high_pass]);
+
+    profile_m.append_items(&[&profile_auto, &profile_global]);
+    if !profiles.is_empty() {
+        profile_m.append(&PredefinedMenuItem::separator());
+    }
+    for item in profiles.iter() {
+        profile_m.append(item);
+    }
+
+    for item in view_items.iter() {
+        debug_m.append(item);
+    }
+    debug_m.append_items(&[&PredefinedMenuItem::separator(), &trace]);
+
+    menu_bar.append_items(&[&file_m, &audio_m, &profile_m, &debug_m, &window_m]);
 
     #[cfg(target_os = "windows")]
     {
@@ -245,8 +410,12 @@ fn main() -> Result<(), Error> {
         window_m.set_as_windows_menu_for_nsapp();
     }
 
+    // the open viewer windows, see viewer::View.
+    let mut viewers: Vec<(View, Window, Pixels)> = Vec::new();
+
     // index of the shader being used.
     let mut shader = 0;
+    let mut modifiers = ModifiersState::empty();
 
     event_loop.run(move |event, event_loop, control_flow| {
         //*control_flow = ControlFlow::Poll;
@@ -257,6 +426,15 @@ fn main() -> Result<(), Error> {
             }
             Event::RedrawRequested(_) => match output_receiver.try_recv() {
                 Ok(ControllerResponse::Draw(buffer)) => {
+                    let (width, height) = system::sgb::frame_size(buffer.len());
+                    if size != (width as u32, height as u32) {
+                        size = (width as u32, height as u32);
+                        (pixels, renderer) = new_renderer(
+                            &window,
+                            Some(SHADER_LIST[shader % SHADER_LIST.len()]),
+                            size,
+                        );
+                    }
                     pixels.frame_mut().copy_from_slice(&buffer);
 
                     let render_result = pixels.render_with(|encoder, render_target, context| {
@@ -293,29 +471,110 @@ fn main() -> Result<(), Error> {
 
                 Ok(ControllerResponse::EmulatorError(e)) => notify(e),
 
+                Ok(ControllerResponse::View(view, frame)) => {
+                    if let Some((_, _, pixels)) = viewers.iter_mut().find(|(v, _, _)| *v == view) {
+                        if pixels.frame().len() == frame.len() {
+                            pixels.frame_mut().copy_from_slice(&frame);
+                        }
+                        if let Err(err) = pixels.render() {
+                            log_error("pixels.render", err);
+                        }
+                    }
+                }
+
+                Ok(ControllerResponse::Screenshot(path)) => {
+                    // the last frame as it is on screen, with the shader and at the size of the window.
+                    let (frame, width, height) = renderer.capture(&pixels);
+                    match capture::save_png(&path, &frame, width as usize, height as usize) {
+                        Ok(_) => println!("Screenshot saved to {:?}", path),
+                        Err(e) => println!("Screenshot could not be saved: {}", e),
+                    }
+                }
+
+                Ok(ControllerResponse::Mixer(mixer)) => {
+                    for (i, item) in mute.iter().enumerate() {
+                        item.set_checked(mixer.mute[i]);
+                    }
+                    for (i, item) in solo.iter().enumerate() {
+                        item.set_checked(mixer.solo[i]);
+                    }
+                    high_pass.set_checked(mixer.high_pass);
+                }
+
+                Ok(ControllerResponse::Trace(tracing)) => trace.set_checked(tracing),
+
+                Ok(ControllerResponse::Shader(name)) => {
+                    // only rebuilds the renderer when the shader is another one.
+                    if let Some(i) = SHADER_LIST.iter().position(|&s| Shader::name(s) == name) {
+                        if i != shader % SHADER_LIST.len() {
+                            shader = i;
+                            (pixels, renderer) = new_renderer(&window, Some(SHADER_LIST[i]), size);
+                        }
+                    }
+                }
+
                 _ => (),
             },
 
+            // keys pressed in a viewer go to the viewer instead of the game.
+            Event::WindowEvent {
+                event, window_id, ..
+            } if viewers.iter().any(|(_, w, _)| w.id() == window_id) => {
+                let i = viewers
+                    .iter()
+                    .position(|(_, w, _)| w.id() == window_id)
+                    .unwrap();
+                let view = viewers[i].0;
+                match event {
+                    WindowEvent::CloseRequested => {
+                        viewers.remove(i);
+                        view_items[VIEWS.iter().position(|&v| v == view).unwrap()]
+                            .set_checked(false);
+                        input_sender
+                            .send(ControllerRequest::Viewer(view, false))
+                            .expect("ControllerRequest Viewer cannot be sent");
+                    }
+                    WindowEvent::Resized(size) => {
+                        if let Err(err) = viewers[i].2.resize_surface(size.width, size.height) {
+                            log_error("pixels.resize_surface", err);
+                        }
+                    }
+                    WindowEvent::KeyboardInput { event: input, .. }
+                        if input.state == ElementState::Pressed =>
+                    {
+                        let key = to_key(
+                            &input.logical_key,
+                            input.physical_key,
+                            input.location,
+                            modifiers,
+                        );
+                        input_sender
+                            .send(ControllerRequest::ViewerKey(view, key))
+                            .expect("ControllerRequest ViewerKey cannot be sent");
+                    }
+                    _ => (),
+                }
+            }
+
             Event::WindowEvent { event, .. } => match event {
+                WindowEvent::ModifiersChanged(state) => modifiers = state,
+
                 WindowEvent::KeyboardInput { event: input, .. } => {
-                    match (input.state, input.logical_key) {
-                        (ElementState::Pressed, key) => match to_text(key) {
-                            Some(key) => {
-                                //let x = SHADER_LIST[shader % SHADER_LIST.len()];
-                                //(pixels, renderer) = new_renderer(&window, x);
-                                //shader += 1;
-                                input_sender
-                                    .send(ControllerRequest::KeyDown(key))
-                                    .expect("ControllerRequest KeyDown cannot be sent")
-                            }
-                            None => (),
-                        },
-                        (ElementState::Released, key) => match to_text(key) {
-                            Some(key) => input_sender
-                                .send(ControllerRequest::KeyUp(key))
-                                .expect("ControllerRequest KeyUp cannot be sent"),
-                            None => (),
-                        },
+                    let key = to_key(
+                        &input.logical_key,
+                        input.physical_key,
+                        input.location,
+                        modifiers,
+                    );
+                    match input.state {
+                        // held keys repeat, which would restart macros.
+                        ElementState::Pressed if input.repeat => (),
+                        ElementState::Pressed => input_sender
+                            .send(ControllerRequest::KeyDown(key))
+                            .expect("ControllerRequest KeyDown cannot be sent"),
+                        ElementState::Released => input_sender
+                            .send(ControllerRequest::KeyUp(key))
+                            .expect("ControllerRequest KeyUp cannot be sent"),
                         _ => (),
                     }
                 }
@@ -373,6 +632,15 @@ fn main() -> Result<(), Error> {
                         }
                         None => (),
                     }
+                } else if event.id == apply_patch.id() {
+                    if let Some(f) = FileDialog::new()
+                        .add_filter("patch", &["ips", "ups", "bps"])
+                        .pick_file()
+                    {
+                        input_sender
+                            .send(ControllerRequest::Patch(f))
+                            .expect("ControllerRequest Patch cannot be sent");
+                    }
                 } else if event.id == config_open.id() {
                     input_sender
                         .send(ControllerRequest::OpenConfig)
@@ -388,10 +656,126 @@ fn main() -> Result<(), Error> {
                     input_sender
                         .send(ControllerRequest::LoadConfig)
                         .expect("ControllerRequest LoadConfig cannot be sent");
+                } else if event.id == profile_auto.id()
+                    || event.id == profile_global.id()
+                    || profiles.iter().any(|p| event.id == p.id())
+                {
+                    // only the picked profile stays checked.
+                    let choice = if event.id == profile_auto.id() {
+                        ProfileChoice::Auto
+                    } else if event.id == profile_global.id() {
+                        ProfileChoice::Global
+                    } else {
+                        let i = profiles.iter().position(|p| event.id == p.id()).unwrap();
+                        ProfileChoice::Named(profile_names[i].clone())
+                    };
+                    profile_auto.set_checked(choice == ProfileChoice::Auto);
+                    profile_global.set_checked(choice == ProfileChoice::Global);
+                    for (item, name) in profiles.iter().zip(profile_names.iter()) {
+                        item.set_checked(choice == ProfileChoice::Named(name.clone()));
+                    }
+                    input_sender
+                        .send(ControllerRequest::Profile(choice))
+                        .expect("ControllerRequest Profile cannot be sent");
+                } else if event.id == import_palette.id() {
+                    if let Some(f) = FileDialog::new()
+                        .add_filter("palette", &["pal", "gpl"])
+                        .pick_file()
+                    {
+                        input_sender
+                            .send(ControllerRequest::ImportPalette(f))
+                            .expect("ControllerRequest ImportPalette cannot be sent");
+                    }
+                } else if event.id == settings.id() {
+                    input_sender
+                        .send(ControllerRequest::Settings)
+                        .expect("ControllerRequest Settings cannot be sent");
+                } else if event.id == record.id() {
+                    input_sender
+                        .send(ControllerRequest::Record)
+                        .expect("ControllerRequest Record cannot be sent");
+                } else if event.id == record_video.id() {
+                    input_sender
+                        .send(ControllerRequest::RecordVideo)
+                        .expect("ControllerRequest RecordVideo cannot be sent");
+                } else if event.id == record_movie.id() || event.id == record_movie_state.id() {
+                    // either item stops a recording started by the other one.
+                    let from_state = event.id == record_movie_state.id();
+                    if record_movie.is_checked() && record_movie_state.is_checked() {
+                        record_movie.set_checked(false);
+                        record_movie_state.set_checked(false);
+                    }
+                    input_sender
+                        .send(ControllerRequest::RecordMovie(from_state))
+                        .expect("ControllerRequest RecordMovie cannot be sent");
+                } else if event.id == play_movie.id() {
+                    if let Some(f) = movie_dialog(&["kmv", "bk2"]) {
+                        record_movie.set_checked(false);
+                        record_movie_state.set_checked(false);
+                        input_sender
+                            .send(ControllerRequest::PlayMovie(f))
+                            .expect("ControllerRequest PlayMovie cannot be sent");
+                    }
+                } else if event.id == export_movie.id() {
+                    if let Some(f) = movie_dialog(&["kmv"]) {
+                        input_sender
+                            .send(ControllerRequest::ExportMovie(f))
+                            .expect("ControllerRequest ExportMovie cannot be sent");
+                    }
+                } else if event.id == import_cheats.id() {
+                    if let Some(f) = FileDialog::new().add_filter("cheats", &["cht"]).pick_file() {
+                        input_sender
+                            .send(ControllerRequest::ImportCheats(f))
+                            .expect("ControllerRequest ImportCheats cannot be sent");
+                    }
+                } else if event.id == open_cheats.id() {
+                    input_sender
+                        .send(ControllerRequest::OpenCheats)
+                        .expect("ControllerRequest OpenCheats cannot be sent");
+                } else if event.id == trace.id() {
+                    input_sender
+                        .send(ControllerRequest::Trace(trace.is_checked()))
+                        .expect("ControllerRequest Trace cannot be sent");
+                } else if event.id == high_pass.id() {
+                    input_sender
+                        .send(ControllerRequest::Mixer(MixerRequest::HighPass(
+                            high_pass.is_checked(),
+                        )))
+                        .expect("ControllerRequest Mixer cannot be sent");
+                } else if let Some(i) = mute.iter().position(|m| event.id == m.id()) {
+                    input_sender
+                        .send(ControllerRequest::Mixer(MixerRequest::Mute(
+                            i,
+                            mute[i].is_checked(),
+                        )))
+                        .expect("ControllerRequest Mixer cannot be sent");
+                } else if let Some(i) = solo.iter().position(|m| event.id == m.id()) {
+                    input_sender
+                        .send(ControllerRequest::Mixer(MixerRequest::Solo(
+                            i,
+                            solo[i].is_checked(),
+                        )))
+                        .expect("ControllerRequest Mixer cannot be sent");
+                } else if let Some(i) = volume.iter().position(|m| event.id == m.id()) {
+                    input_sender
+                        .send(ControllerRequest::Mixer(MixerRequest::Volume(
+                            VOLUMES[i] as f32 / 100.0,
+                        )))
+                        .expect("ControllerRequest Mixer cannot be sent");
+                } else if let Some(i) = view_items.iter().position(|m| event.id == m.id()) {
+                    let (view, open) = (VIEWS[i], view_items[i].is_checked());
+                    viewers.retain(|(v, _, _)| *v != view);
+                    if open {
+                        let (window, pixels) = new_viewer(event_loop, view);
+                        viewers.push((view, window, pixels));
+                    }
+                    input_sender
+                        .send(ControllerRequest::Viewer(view, open))
+                        .expect("ControllerRequest Viewer cannot be sent");
                 } else if event.id == shader_switch.id() {
                     shader += 1;
                     (pixels, renderer) =
-                        new_renderer(&window, Some(SHADER_LIST[shader % SHADER_LIST.len()]));
+                        new_renderer(&window, Some(SHADER_LIST[shader % SHADER_LIST.len()]), size);
                 }
             }
             _ => (),
@@ -424,6 +808,13 @@ fn file_dialog(path: Option<PathBuf>) -> Option<PathBuf> {
     file
 }
 
+fn movie_dialog(extensions: &[&str]) -> Option<PathBuf> {
+    FileDialog::new()
+        .add_filter("movie", extensions)
+        .set_directory(config::Config::dir("movies"))
+        .pick_file()
+}
+
 pub fn reload(file: PathBuf, sender: &SyncSender<ControllerRequest>) {
     // Send the emulator instance to the event loop
 
@@ -435,11 +826,15 @@ pub fn reload(file: PathBuf, sender: &SyncSender<ControllerRequest>) {
     //sender.send(ControllerRequest::LoadConfig()).unwrap();
 }
 
-pub fn new_renderer(window: &Window, shader: Option<Shader>) -> (Pixels, Renderer) {
+pub fn new_renderer(
+    window: &Window,
+    shader: Option<Shader>,
+    (width, height): (u32, u32),
+) -> (Pixels, Renderer) {
     let window_size = window.inner_size();
     let pixels = {
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
-        Pixels::new(WIDTH, HEIGHT, surface_texture).expect("Pixels object cannot be created")
+        Pixels::new(width, height, surface_texture).expect("Pixels object cannot be created")
     };
 
     let shader = match shader {
@@ -452,28 +847,82 @@ pub fn new_renderer(window: &Window, shader: Option<Shader>) -> (Pixels, Rendere
         window_size.width,
         window_size.height,
         shader,
-        WIDTH,
-        HEIGHT,
+        width,
+        height,
     )
     .unwrap();
 
     return (pixels, renderer);
 }
 
+// opens the window of a viewer, at twice the size of its frames.
+fn new_viewer(event_loop: &EventLoopWindowTarget<MenuEvent>, view: View) -> (Window, Pixels) {
+    let (width, height) = view.size();
+    let window = WindowBuilder::new()
+        .with_title(view.name())
+        .with_inner_size(LogicalSize::new((width * 2) as f64, (height * 2) as f64))
+        .build(event_loop)
+        .unwrap();
+
+    let window_size = window.inner_size();
+    let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
+    let pixels = Pixels::new(width as u32, height as u32, surface_texture)
+        .expect("Pixels object cannot be created");
+    (window, pixels)
+}
+
 // converts the window Key Enums into a string standard, so that the frontend could be replaced without having a dependency on the controller itself.
-pub fn to_text<'a>(key: Key<'a>) -> Option<String> {
+pub fn to_text<'a>(key: &Key<'a>, location: KeyLocation) -> Option<String> {
     let text = match key {
-        Key::Character(ch) => ch,
+        // numpad keys are told apart from the main ones, 4 on the numpad is "numpad4".
+        Key::Character(ch) if location == KeyLocation::Numpad => {
+            let name = match *ch {
+                "+" => "add",
+                "-" => "subtract",
+                "*" => "multiply",
+                "/" => "divide",
+                "." | "," => "decimal",
+                ch => ch,
+            };
+            return Some(format!("numpad{}", name));
+        }
+        Key::Enter if location == KeyLocation::Numpad => "numpadenter",
+        // characters are lowercase, so shift doesn't change which key it is.
+        Key::Character(ch) => return Some(ch.to_lowercase()),
         Key::Enter => "enter",
         Key::Backspace => "backspace",
         Key::Tab => "tab",
         Key::Space => "space",
         Key::Escape => "escape",
         Key::Shift => "shift",
+        Key::Control => "ctrl",
+        Key::Alt => "alt",
+        Key::Super => "super",
+        Key::CapsLock => "capslock",
         Key::ArrowUp => "up",
         Key::ArrowDown => "down",
         Key::ArrowLeft => "left",
         Key::ArrowRight => "right",
+        Key::Insert => "insert",
+        Key::Delete => "delete",
+        Key::Home => "home",
+        Key::End => "end",
+        Key::PageUp => "pageup",
+        Key::PageDown => "pagedown",
+        Key::Pause => "pause",
+        Key::PrintScreen => "printscreen",
+        Key::F1 => "f1",
+        Key::F2 => "f2",
+        Key::F3 => "f3",
+        Key::F4 => "f4",
+        Key::F5 => "f5",
+        Key::F6 => "f6",
+        Key::F7 => "f7",
+        Key::F8 => "f8",
+        Key::F9 => "f9",
+        Key::F10 => "f10",
+        Key::F11 => "f11",
+        Key::F12 => "f12",
         _ => "",
     };
 
@@ -483,6 +932,37 @@ pub fn to_text<'a>(key: Key<'a>) -> Option<String> {
     }
 }
 
+// a key event for the controller, named by its text and by its scan code ("code:KeyZ").
+pub fn to_key(
+    key: &Key,
+    code: KeyCode,
+    location: KeyLocation,
+    modifiers: ModifiersState,
+) -> keybind::Key {
+    let mut names = Vec::new();
+    if let Some(text) = to_text(key, location) {
+        names.push(text);
+    }
+    if !matches!(code, KeyCode::Unidentified(_)) {
+        names.push(format!("code:{:?}", code));
+    }
+
+    let held = [
+        (modifiers.control_key(), keybind::CTRL),
+        (modifiers.shift_key(), keybind::SHIFT),
+        (modifiers.alt_key(), keybind::ALT),
+        (modifiers.super_key(), keybind::SUPER),
+    ];
+
+    keybind::Key {
+        names,
+        modifiers: held
+            .iter()
+            .fold(0, |bits, (on, bit)| if *on { bits | bit } else { bits }),
+        pad: None,
+    }
+}
+
 pub fn notify(e: EmulatorError) {
     println!("ERROR");
     match e {
@@ -494,6 +974,14 @@ pub fn notify(e: EmulatorError) {
                 .show()
                 .unwrap();
         }
+        EmulatorError::NoRom => {
+            Notification::new()
+                .summary("No ROM")
+                .body("There is no ROM loaded to restart.")
+                .auto_icon()
+                .show()
+                .unwrap();
+        }
         EmulatorError::InvalidFileExtension => {
             Notification::new()
                 .summary("File Error")
@@ -517,6 +1005,14 @@ pub fn notify(e: EmulatorError) {
                 .auto_icon()
                 .show()
                 .unwrap();
+        }
+        EmulatorError::InvalidPatch(reason) => {
+            Notification::new()
+                .summary("Patch Error")
+                .body(format!("The patch cannot be applied: {}.", reason).as_str())
+                .auto_icon()
+                .show()
+                .unwrap();
         } //_ => (),
     }
 }
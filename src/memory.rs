@@ -0,0 +1,451 @@
+use crate::font::{self, GLYPH_HEIGHT};
+use crate::system::mmu::MMU;
+
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+const COLUMN: usize = font::ADVANCE;
+const BYTES_PER_ROW: usize = 16;
+// rows of bytes or search results shown at once.
+pub const ROWS: usize = 32;
+
+const WHITE: [u8; 3] = [0xff, 0xff, 0xff];
+const GRAY: [u8; 3] = [0x88, 0x88, 0x88];
+const YELLOW: [u8; 3] = [0xff, 0xd8, 0x40];
+const CYAN: [u8; 3] = [0x40, 0xd0, 0xff];
+const GREEN: [u8; 3] = [0x60, 0xe0, 0x60];
+
+// the memory the viewer shows. ROM and cartridge RAM are shown whole, with every bank.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Region {
+    // the 64K the CPU sees, with the banks that are mapped right now.
+    Bus,
+    Rom,
+    CartRam,
+    Wram,
+    Hram,
+    Io,
+}
+
+pub const REGIONS: [Region; 6] = [
+    Region::Bus,
+    Region::Rom,
+    Region::CartRam,
+    Region::Wram,
+    Region::Hram,
+    Region::Io,
+];
+
+impl Region {
+    pub fn name(self) -> &'static str {
+        match self {
+            Region::Bus => "Bus",
+            Region::Rom => "ROM",
+            Region::CartRam => "Cart RAM",
+            Region::Wram => "WRAM",
+            Region::Hram => "HRAM",
+            Region::Io => "I/O",
+        }
+    }
+
+    pub fn len(self, mmu: &MMU) -> usize {
+        match self {
+            Region::Bus => 0x10000,
+            Region::Rom => mmu.cartridge.rom().len(),
+            Region::CartRam => mmu.cartridge.ram().len(),
+            Region::Wram => 0x2000,
+            Region::Hram => 0x7f,
+            Region::Io => 0x80,
+        }
+    }
+
+    // the address of an offset, with the bank in front for ROM and cartridge RAM.
+    pub fn label(self, offset: usize) -> String {
+        match self {
+            Region::Bus => format!("{:04X}", offset),
+            Region::Rom => {
+                let bank = offset / 0x4000;
+                let address = if bank == 0 {
+                    offset
+                } else {
+                    0x4000 + offset % 0x4000
+                };
+                format!("{:02X}:{:04X}", bank, address)
+            }
+            Region::CartRam => format!("{:02X}:{:04X}", offset / 0x2000, 0xa000 + offset % 0x2000),
+            Region::Wram => format!("{:04X}", 0xc000 + offset),
+            Region::Hram => format!("{:04X}", 0xff80 + offset),
+            Region::Io => format!("{:04X}", 0xff00 + offset),
+        }
+    }
+}
+
+// reads a byte of a region. offsets past the end read as 0xff, like open bus.
+pub fn read(mmu: &MMU, region: Region, offset: usize) -> u8 {
+    match region {
+        Region::Bus => mmu.read_byte(offset as u16),
+        Region::Rom => mmu.cartridge.rom().get(offset).copied().unwrap_or(0xff),
+        Region::CartRam => mmu.cartridge.ram().get(offset).copied().unwrap_or(0xff),
+        Region::Wram => mmu.read_byte(0xc000 + offset as u16),
+        Region::Hram => mmu.read_byte(0xff80 + offset as u16),
+        Region::Io => mmu.read_byte(0xff00 + offset as u16),
+    }
+}
+
+/* writes a byte of a region. writes to the bus go through the MMU like the CPU's, so a write
+to ROM addresses switches banks. editing the ROM itself is done in the ROM region. */
+pub fn write(mmu: &mut MMU, region: Region, offset: usize, value: u8) {
+    match region {
+        Region::Bus => mmu.write_byte(value, offset as u16),
+        Region::Rom => {
+            if let Some(byte) = mmu.cartridge.rom_mut().get_mut(offset) {
+                *byte = value;
+            }
+        }
+        Region::CartRam => {
+            if let Some(byte) = mmu.cartridge.ram_mut().get_mut(offset) {
+                *byte = value;
+            }
+        }
+        Region::Wram => mmu.write_byte(value, 0xc000 + offset as u16),
+        Region::Hram => mmu.write_byte(value, 0xff80 + offset as u16),
+        Region::Io => mmu.write_byte(value, 0xff00 + offset as u16),
+    }
+}
+
+// a byte that is written back to its value at the start of every frame.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Freeze {
+    pub region: Region,
+    pub offset: usize,
+    pub value: u8,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Compare {
+    Changed,
+    Unchanged,
+    Increased,
+    Decreased,
+}
+
+/* a cheat finder. it starts with every byte of a region, and every filter keeps the ones
+that changed the way asked since the last snapshot, until the address of a value is left. */
+pub struct Search {
+    pub region: Region,
+    // the offsets still matching, with their value at the last snapshot.
+    pub candidates: Vec<(usize, u8)>,
+}
+
+impl Search {
+    pub fn new(mmu: &MMU, region: Region) -> Self {
+        Self {
+            region,
+            candidates: (0..region.len(mmu))
+                .map(|offset| (offset, read(mmu, region, offset)))
+                .collect(),
+        }
+    }
+
+    pub fn filter(&mut self, mmu: &MMU, compare: Compare) {
+        let region = self.region;
+        self.candidates.retain_mut(|(offset, old)| {
+            let new = read(mmu, region, *offset);
+            let keep = match compare {
+                Compare::Changed => new != *old,
+                Compare::Unchanged => new == *old,
+                Compare::Increased => new > *old,
+                Compare::Decreased => new < *old,
+            };
+            *old = new;
+            keep
+        });
+    }
+}
+
+/* the hex view of the memory viewer. the arrows and page keys move the cursor, tab picks
+the next region and typing 2 hex digits writes a byte. N starts a search, which = (unchanged),
+X (changed), + and - (increased, decreased) narrow down. R lists the results and Z freezes
+the byte under the cursor. */
+pub struct MemoryView {
+    region: Region,
+    cursor: usize,
+    // the high nibble of the byte being typed.
+    nibble: Option<u8>,
+    search: Option<Search>,
+    // shows the results of the search instead of the bytes.
+    results: bool,
+    selected: usize,
+    message: Option<String>,
+}
+
+impl MemoryView {
+    pub fn new() -> Self {
+        Self {
+            region: Region::Bus,
+            cursor: 0,
+            nibble: None,
+            search: None,
+            results: false,
+            selected: 0,
+            message: None,
+        }
+    }
+
+    pub fn key(&mut self, name: &str, mmu: &mut MMU, freezes: &mut Vec<Freeze>) {
+        self.message = None;
+        let len = self.region.len(mmu).max(1);
+
+        if self.results {
+            let count = self.search.as_ref().map_or(0, |s| s.candidates.len());
+            match name {
+                "up" => self.selected = self.selected.saturating_sub(1),
+                "down" => self.selected = (self.selected + 1).min(count.saturating_sub(1)),
+                "pageup" => self.selected = self.selected.saturating_sub(ROWS),
+                "pagedown" => self.selected = (self.selected + ROWS).min(count.saturating_sub(1)),
+                // jumps to the result in the hex view.
+                "enter" => {
+                    if let Some(search) = self.search.as_ref() {
+                        if let Some(&(offset, _)) = search.candidates.get(self.selected) {
+                            self.region = search.region;
+                            self.cursor = offset;
+                        }
+                    }
+                    self.results = false;
+                }
+                "r" | "escape" => self.results = false,
+                "z" => {
+                    if let Some(search) = self.search.as_ref() {
+                        if let Some(&(offset, _)) = search.candidates.get(self.selected) {
+                            self.freeze(mmu, freezes, search.region, offset);
+                        }
+                    }
+                }
+                _ => (),
+            }
+            return;
+        }
+
+        if let Some(digit) = hex_digit(name) {
+            match self.nibble.take() {
+                None => self.nibble = Some(digit),
+                Some(high) => {
+                    let value = (high << 4) | digit;
+                    write(mmu, self.region, self.cursor, value);
+                    // frozen bytes keep the value typed in.
+                    for freeze in freezes.iter_mut() {
+                        if freeze.region == self.region && freeze.offset == self.cursor {
+                            freeze.value = value;
+                        }
+                    }
+                    self.cursor = (self.cursor + 1).min(len - 1);
+                }
+            }
+            return;
+        }
+        self.nibble = None;
+
+        match name {
+            "left" => self.cursor = self.cursor.saturating_sub(1),
+            "right" => self.cursor = (self.cursor + 1).min(len - 1),
+            "up" => self.cursor = self.cursor.saturating_sub(BYTES_PER_ROW),
+            "down" => self.cursor = (self.cursor + BYTES_PER_ROW).min(len - 1),
+            "pageup" => self.cursor = self.cursor.saturating_sub(BYTES_PER_ROW * ROWS),
+            "pagedown" => self.cursor = (self.cursor + BYTES_PER_ROW * ROWS).min(len - 1),
+            "home" => self.cursor = 0,
+            "end" => self.cursor = len - 1,
+            "tab" => {
+                let i = REGIONS.iter().position(|&r| r == self.region).unwrap_or(0);
+                self.region = REGIONS[(i + 1) % REGIONS.len()];
+                self.cursor = 0;
+            }
+            "n" => {
+                let search = Search::new(mmu, self.region);
+                self.message = Some(format!(
+                    "Search started with {} bytes of {}",
+                    search.candidates.len(),
+                    self.region.name()
+                ));
+                self.search = Some(search);
+            }
+            "=" | "x" | "+" | "-" => {
+                let compare = match name {
+                    "=" => Compare::Unchanged,
+                    "x" => Compare::Changed,
+                    "+" => Compare::Increased,
+                    _ => Compare::Decreased,
+                };
+                self.message = Some(match self.search.as_mut() {
+                    Some(search) => {
+                        search.filter(mmu, compare);
+                        format!("{:?}: {} left", compare, search.candidates.len())
+                    }
+                    None => "Press N to start a search".to_string(),
+                });
+                self.selected = 0;
+            }
+            "r" => self.results = self.search.is_some(),
+            "z" => self.freeze(mmu, freezes, self.region, self.cursor),
+            _ => (),
+        }
+    }
+
+    /* freezes a byte at its current value, or lets go of it when it is already frozen. writes to
+    ROM on the bus go to the MBC and switch banks, so those freeze the byte of the bank mapped now. */
+    fn freeze(&mut self, mmu: &MMU, freezes: &mut Vec<Freeze>, region: Region, offset: usize) {
+        let (region, offset) = match (region, offset) {
+            (Region::Bus, 0x0000..=0x3fff) => (Region::Rom, offset),
+            (Region::Bus, 0x4000..=0x7fff) => {
+                let (rom_bank, _) = mmu.cartridge.banks();
+                (Region::Rom, rom_bank * 0x4000 + offset - 0x4000)
+            }
+            _ => (region, offset),
+        };
+        let label = format!("{} {}", region.name(), region.label(offset));
+        match freezes
+            .iter()
+            .position(|f| f.region == region && f.offset == offset)
+        {
+            Some(i) => {
+                freezes.remove(i);
+                self.message = Some(format!("{} unfrozen", label));
+            }
+            None => {
+                let value = read(mmu, region, offset);
+                freezes.push(Freeze {
+                    region,
+                    offset,
+                    value,
+                });
+                self.message = Some(format!("{} frozen at {:02X}", label, value));
+            }
+        }
+    }
+
+    // draws the view into an RGBA frame, with the mapped banks on top and the keys at the bottom.
+    pub fn draw(&self, frame: &mut [u8], width: usize, mmu: &MMU, freezes: &[Freeze]) {
+        let (rom_bank, ram_bank) = mmu.cartridge.banks();
+        let header = format!(
+            "{} (tab)   ROM bank {:02X}   RAM bank {:02X}   {} frozen",
+            self.region.name(),
+            rom_bank,
+            ram_bank,
+            freezes.len()
+        );
+        font::draw_text(frame, width, 2, 2, &header, YELLOW);
+
+        match self.results {
+            true => self.draw_results(frame, width, mmu),
+            false => self.draw_bytes(frame, width, mmu, freezes),
+        }
+
+        let bottom = 2 + (ROWS + 2) * LINE_HEIGHT;
+        let help = match self.results {
+            true => "Enter go to  Z freeze  R back  S save",
+            false => "0-F edit  N search  = X + - filter  R results  Z freeze  S save",
+        };
+        font::draw_text(frame, width, 2, bottom, help, GRAY);
+        if let Some(message) = &self.message {
+            font::draw_text(frame, width, 2, bottom + LINE_HEIGHT, message, WHITE);
+        }
+    }
+
+    // rows of 16 bytes and their characters. frozen bytes are cyan, search results green.
+    fn draw_bytes(&self, frame: &mut [u8], width: usize, mmu: &MMU, freezes: &[Freeze]) {
+        let len = self.region.len(mmu);
+        let cursor_row = self.cursor / BYTES_PER_ROW;
+        // keeps the cursor on screen, a few rows from the top.
+        let first_row = cursor_row.saturating_sub(ROWS / 4);
+        let candidates: Vec<usize> = match self.search.as_ref() {
+            Some(search) if search.region == self.region && search.candidates.len() <= 0x1000 => {
+                search
+                    .candidates
+                    .iter()
+                    .map(|&(offset, _)| offset)
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        for row in 0..ROWS {
+            let start = (first_row + row) * BYTES_PER_ROW;
+            if start >= len {
+                break;
+            }
+            let y = 2 + (row + 1) * LINE_HEIGHT;
+            font::draw_text(frame, width, 2, y, &self.region.label(start), GRAY);
+
+            let mut text = String::new();
+            for offset in start..(start + BYTES_PER_ROW).min(len) {
+                let value = read(mmu, self.region, offset);
+                let x = 2 + (9 + (offset - start) * 3) * COLUMN;
+                let color = if offset == self.cursor {
+                    YELLOW
+                } else if freezes
+                    .iter()
+                    .any(|f| f.region == self.region && f.offset == offset)
+                {
+                    CYAN
+                } else if candidates.binary_search(&offset).is_ok() {
+                    GREEN
+                } else {
+                    WHITE
+                };
+                let byte = match (offset == self.cursor, self.nibble) {
+                    (true, Some(high)) => format!("{:X}_", high),
+                    _ => format!("{:02X}", value),
+                };
+                font::draw_text(frame, width, x, y, &byte, color);
+                text.push(match value {
+                    0x20..=0x7e => value as char,
+                    _ => '.',
+                });
+            }
+            let x = 2 + (9 + BYTES_PER_ROW * 3 + 1) * COLUMN;
+            font::draw_text(frame, width, x, y, &text, GRAY);
+        }
+    }
+
+    // the results of the search, with the value at the last snapshot and the one now.
+    fn draw_results(&self, frame: &mut [u8], width: usize, mmu: &MMU) {
+        let search = match self.search.as_ref() {
+            Some(search) => search,
+            None => return,
+        };
+
+        let first = self.selected.saturating_sub(ROWS / 4);
+        for (row, &(offset, old)) in search.candidates.iter().skip(first).take(ROWS).enumerate() {
+            let line = format!(
+                "{}{} {}: {:02X} (was {:02X})",
+                if first + row == self.selected {
+                    ">"
+                } else {
+                    " "
+                },
+                search.region.name(),
+                search.region.label(offset),
+                read(mmu, search.region, offset),
+                old
+            );
+            let color = if first + row == self.selected {
+                YELLOW
+            } else {
+                WHITE
+            };
+            font::draw_text(frame, width, 2, 2 + (row + 1) * LINE_HEIGHT, &line, color);
+        }
+    }
+}
+
+fn hex_digit(name: &str) -> Option<u8> {
+    match name.len() {
+        1 => u8::from_str_radix(name, 16).ok(),
+        _ => None,
+    }
+}
+
+// the size of the frames of the memory viewer.
+pub fn size() -> (usize, usize) {
+    (
+        4 + (9 + BYTES_PER_ROW * 4 + 1) * COLUMN,
+        4 + (ROWS + 4) * LINE_HEIGHT,
+    )
+}
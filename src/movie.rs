@@ -0,0 +1,201 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+use zip::{write::FileOptions, ZipArchive, ZipWriter};
+
+use crate::system::joypad::Input;
+
+// t-cycles in one frame. movies advance by fixed frames, so they stay in sync with the LCD off.
+pub const FRAME_CYCLES: u32 = 70224;
+
+// frames between two state hashes.
+pub const HASH_INTERVAL: usize = 60;
+
+// buttons of the BizHawk GB controller in log order, with the mnemonic used for each.
+const BK2_KEYS: [(&str, char, Option<Input>); 9] = [
+    ("Up", 'U', Some(Input::Up)),
+    ("Down", 'D', Some(Input::Down)),
+    ("Left", 'L', Some(Input::Left)),
+    ("Right", 'R', Some(Input::Right)),
+    ("Start", 'S', Some(Input::Start)),
+    ("Select", 's', Some(Input::Select)),
+    ("B", 'B', Some(Input::B)),
+    ("A", 'A', Some(Input::A)),
+    ("Power", 'P', None),
+];
+
+pub enum MovieError {
+    IO(io::Error),
+    Invalid(String),
+}
+
+impl From<io::Error> for MovieError {
+    fn from(e: io::Error) -> Self {
+        MovieError::IO(e)
+    }
+}
+
+impl std::fmt::Display for MovieError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MovieError::IO(e) => write!(f, "{}", e),
+            MovieError::Invalid(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+// inputs recorded from power on or from a save state, one joypad state byte per frame.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Movie {
+    pub title: String,
+    // SHA1 of the ROM the movie was recorded on.
+    pub sha1: String,
+    // the save state the movie starts from, none when it starts from power on.
+    pub state: Option<Vec<u8>>,
+    pub frames: Vec<u8>,
+    // state hashes taken every HASH_INTERVAL frames, before that frame's input is applied.
+    pub hashes: Vec<u64>,
+}
+
+impl Movie {
+    pub fn new(title: String, sha1: String) -> Self {
+        Self {
+            title,
+            sha1,
+            state: None,
+            frames: Vec::new(),
+            hashes: Vec::new(),
+        }
+    }
+
+    // loads a movie, either in the native format or a BizHawk .bk2.
+    pub fn load(path: &Path) -> Result<Movie, MovieError> {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("bk2") => Movie::import_bk2(path),
+            _ => bincode::deserialize(&fs::read(path)?)
+                .map_err(|e| MovieError::Invalid(e.to_string())),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), MovieError> {
+        let data = bincode::serialize(self).map_err(|e| MovieError::Invalid(e.to_string()))?;
+        fs::write(path, data)?;
+        Ok(())
+    }
+
+    // reads the input log of a .bk2 archive. the buttons are found through the LogKey line.
+    pub fn import_bk2(path: &Path) -> Result<Movie, MovieError> {
+        let mut archive =
+            ZipArchive::new(File::open(path)?).map_err(|e| MovieError::Invalid(e.to_string()))?;
+
+        let mut header = String::new();
+        if let Ok(mut file) = archive.by_name("Header.txt") {
+            file.read_to_string(&mut header)?;
+        }
+
+        let mut log = String::new();
+        archive
+            .by_name("Input Log.txt")
+            .map_err(|_| MovieError::Invalid("no Input Log.txt in archive".to_string()))?
+            .read_to_string(&mut log)?;
+
+        let field = |name: &str| {
+            header
+                .lines()
+                .find_map(|line| line.strip_prefix(name))
+                .map(|value| value.trim().to_string())
+                .unwrap_or_default()
+        };
+
+        let mut movie = Movie::new(field("GameName"), field("SHA1").to_lowercase());
+        let mut keys: Vec<Option<Input>> = Vec::new();
+
+        for line in log.lines() {
+            if let Some(key) = line.strip_prefix("LogKey:") {
+                keys = key
+                    .trim_start_matches('#')
+                    .split(['|', '#'])
+                    .filter(|name| !name.is_empty())
+                    .map(|name| {
+                        BK2_KEYS
+                            .iter()
+                            .find(|(key, _, _)| key.eq_ignore_ascii_case(name.trim()))
+                            .and_then(|(_, _, input)| *input)
+                    })
+                    .collect();
+            } else if line.starts_with('|') {
+                if keys.is_empty() {
+                    return Err(MovieError::Invalid("input log has no LogKey".to_string()));
+                }
+
+                let state = line.chars().filter(|c| *c != '|').zip(keys.iter()).fold(
+                    0,
+                    |state, (c, input)| match input {
+                        Some(input) if c != '.' && c != ' ' => state | input.bit(),
+                        _ => state,
+                    },
+                );
+                movie.frames.push(state);
+            }
+        }
+
+        Ok(movie)
+    }
+
+    // writes the movie as a .bk2 archive for BizHawk (Gambatte core).
+    pub fn export_bk2(&self, path: &Path) -> Result<(), MovieError> {
+        if self.state.is_some() {
+            return Err(MovieError::Invalid(
+                "movies that start from a save state can't be exported".to_string(),
+            ));
+        }
+        let mut zip = ZipWriter::new(File::create(path)?);
+        let options = FileOptions::default();
+        let invalid = |e: zip::result::ZipError| MovieError::Invalid(e.to_string());
+
+        zip.start_file("Header.txt", options).map_err(invalid)?;
+        write!(
+            zip,
+            "MovieVersion BizHawk v2.0.0\nPlatform GB\nGameName {}\nSHA1 {}\nCore Gambatte\n",
+            self.title,
+            self.sha1.to_uppercase()
+        )?;
+
+        zip.start_file("Input Log.txt", options).map_err(invalid)?;
+        let names: Vec<&str> = BK2_KEYS.iter().map(|(name, _, _)| *name).collect();
+        write!(zip, "[Input]\nLogKey:#{}|\n", names.join("|"))?;
+        for state in self.frames.iter() {
+            let line: String = BK2_KEYS
+                .iter()
+                .map(|(_, c, input)| match input {
+                    Some(input) if state & input.bit() != 0 => *c,
+                    _ => '.',
+                })
+                .collect();
+            writeln!(zip, "|{}|", line)?;
+        }
+        writeln!(zip, "[/Input]")?;
+
+        zip.start_file("Comments.txt", options).map_err(invalid)?;
+        zip.start_file("Subtitles.txt", options).map_err(invalid)?;
+        zip.start_file("SyncSettings.json", options)
+            .map_err(invalid)?;
+        write!(
+            zip,
+            "{{\"o\":{{\"$type\":\"Gameboy+GambatteSyncSettings\"}}}}"
+        )?;
+
+        zip.finish().map_err(invalid)?;
+        Ok(())
+    }
+}
+
+// where the emulator is with a movie.
+pub enum MovieState {
+    Recording(Movie),
+    Playing { movie: Movie, frame: usize },
+}
@@ -0,0 +1,126 @@
+use crate::config::{Color, NamedPalette, Palettes};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const fn color(id0: u32, id1: u32, id2: u32, id3: u32) -> Color {
+    const fn rgb(hex: u32) -> [u8; 3] {
+        [(hex >> 16) as u8, (hex >> 8) as u8, hex as u8]
+    }
+    Color {
+        id0: rgb(id0),
+        id1: rgb(id1),
+        id2: rgb(id2),
+        id3: rgb(id3),
+    }
+}
+
+// palettes that can be picked for every layer, from lightest to darkest.
+pub const PRESETS: [(&str, Color); 12] = [
+    ("Grayscale", color(0xffffff, 0xcccccc, 0x777777, 0x000000)),
+    ("DMG", color(0x9bbc0f, 0x8bac0f, 0x306230, 0x0f380f)),
+    ("Pocket", color(0xc4cfa1, 0x8b956d, 0x4d533c, 0x1f1f1f)),
+    ("Light", color(0x00b581, 0x009a71, 0x00694a, 0x005138)),
+    ("Brown", color(0xffffff, 0xffad63, 0x843100, 0x000000)),
+    ("Red", color(0xffffff, 0xff8484, 0x943a3a, 0x000000)),
+    ("Green", color(0xffffff, 0x7bff31, 0x008400, 0x000000)),
+    ("Blue", color(0xffffff, 0x63a5ff, 0x0000ff, 0x000000)),
+    ("Orange", color(0xffffff, 0xffff00, 0xff0000, 0x000000)),
+    ("Yellow", color(0xffffff, 0xffff00, 0x7b4a00, 0x000000)),
+    ("Pastel", color(0xffffa5, 0xff9494, 0x9494ff, 0x000000)),
+    ("Inverted", color(0x000000, 0x008484, 0xffde00, 0xffffff)),
+];
+
+// BG, OBJ0 and OBJ1 presets that auto-colorization picks from, combinations of our own.
+const AUTO: [[&str; 3]; 8] = [
+    ["Brown", "Brown", "Brown"],
+    ["Red", "Green", "Blue"],
+    ["Pastel", "Red", "Blue"],
+    ["Yellow", "Red", "Green"],
+    ["Green", "Red", "Blue"],
+    ["Blue", "Red", "Green"],
+    ["Orange", "Blue", "Green"],
+    ["Grayscale", "Red", "Blue"],
+];
+
+pub fn preset(name: &str) -> Option<Color> {
+    PRESETS
+        .iter()
+        .find(|(preset, _)| preset.eq_ignore_ascii_case(name))
+        .map(|(_, color)| *color)
+}
+
+/* colors the layers from the title of the cartridge header. the sum of the title bytes picks one
+of the combinations, so every game keeps its colors. it is an arbitrary mapping, not the table of
+the CGB boot ROM or the palettes of the SGB, so games don't get the colors of real hardware. */
+pub fn auto(palettes: &mut Palettes, title: &str) {
+    let checksum = title.trim().bytes().fold(0u32, |sum, b| sum + b as u32);
+    let [bg, obj0, obj1] = AUTO[checksum as usize % AUTO.len()].map(|name| preset(name).unwrap());
+    palettes.bg = bg;
+    palettes.obj0 = obj0;
+    palettes.obj1 = obj1;
+}
+
+/* reads the palettes of a JASC .pal, a binary .pal of RGB triples or a GIMP .gpl file.
+every 4 colors are a palette, so a file with 12 colors has one for BG, OBJ0 and OBJ1. */
+pub fn import(path: &Path) -> io::Result<Vec<NamedPalette>> {
+    let data = fs::read(path)?;
+    let text = String::from_utf8_lossy(&data);
+
+    let colors = if text.starts_with("JASC-PAL") {
+        // header, version and the number of colors come first.
+        parse_lines(text.lines().skip(3))
+    } else if text.starts_with("GIMP Palette") {
+        parse_lines(text.lines().skip(1).filter(|line| {
+            !line.starts_with('#') && !line.starts_with("Name:") && !line.starts_with("Columns:")
+        }))
+    } else if data.len() % 3 == 0 {
+        data.chunks(3).map(|rgb| [rgb[0], rgb[1], rgb[2]]).collect()
+    } else {
+        return Err(invalid("not a JASC, GIMP or RGB palette"));
+    };
+
+    if colors.len() < 4 {
+        return Err(invalid("a palette needs at least 4 colors"));
+    }
+
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    Ok(colors
+        .chunks_exact(4)
+        .take(3)
+        .enumerate()
+        .map(|(i, c)| NamedPalette {
+            name: match i {
+                0 => name.clone(),
+                _ => format!("{} {}", name, i + 1),
+            },
+            colors: Color {
+                id0: c[0],
+                id1: c[1],
+                id2: c[2],
+                id3: c[3],
+            },
+        })
+        .collect())
+}
+
+// lines of "r g b", anything after the third number is a comment or the name of the color.
+fn parse_lines<'a>(lines: impl Iterator<Item = &'a str>) -> Vec<[u8; 3]> {
+    lines
+        .filter_map(|line| {
+            let mut numbers = line.split_whitespace().map(|n| n.parse::<u8>());
+            match (numbers.next(), numbers.next(), numbers.next()) {
+                (Some(Ok(r)), Some(Ok(g)), Some(Ok(b))) => Some([r, g, b]),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
@@ -0,0 +1,362 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// formats of the patches, in the order they are looked for next to a ROM.
+const EXTENSIONS: [&str; 3] = ["ips", "ups", "bps"];
+
+// the largest Game Boy ROM, 512 banks of MBC5. patches that make larger ones are refused before allocating.
+const MAX_SIZE: usize = 8 * 1024 * 1024;
+
+// a patch with the name of the ROM, like game.ips next to game.gb.
+pub fn find(rom_path: &Path) -> Option<PathBuf> {
+    EXTENSIONS
+        .iter()
+        .map(|extension| rom_path.with_extension(extension))
+        .find(|path| path.is_file())
+}
+
+/* returns the ROM with the patch at the path applied, the format is told by the header of the
+patch. the header checksum is fixed afterwards, as the boot ROM locks up on a wrong one. */
+pub fn apply(rom: &[u8], path: &Path) -> Result<Vec<u8>, String> {
+    let patch = fs::read(path).map_err(|e| format!("{:?} could not be read: {}", path, e))?;
+
+    let mut patched = if patch.starts_with(b"PATCH") {
+        ips(rom, &patch)?
+    } else if patch.starts_with(b"UPS1") {
+        ups(rom, &patch)?
+    } else if patch.starts_with(b"BPS1") {
+        bps(rom, &patch)?
+    } else {
+        return Err(format!("{:?} is not an IPS, UPS or BPS patch", path));
+    };
+
+    fix_header(&mut patched);
+    Ok(patched)
+}
+
+// the byte at 0x14d is checked against the bytes of the header from 0x134 to 0x14c.
+fn fix_header(rom: &mut [u8]) {
+    if rom.len() < 0x150 {
+        return;
+    }
+    rom[0x14d] = rom[0x134..=0x14c]
+        .iter()
+        .fold(0u8, |sum, &b| sum.wrapping_sub(b).wrapping_sub(1));
+}
+
+// reads the fields of a patch, failing instead of panicking at its end.
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8], position: usize) -> Self {
+        Self { data, position }
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let end = self.position + length;
+        let bytes = self
+            .data
+            .get(self.position..end)
+            .ok_or("the patch ends too early")?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    // big endian number of the given size, used by IPS.
+    fn number(&mut self, size: usize) -> Result<usize, String> {
+        Ok(self
+            .bytes(size)?
+            .iter()
+            .fold(0, |n, &b| (n << 8) | b as usize))
+    }
+
+    // the variable length numbers of UPS and BPS, 7 bits per byte with the last one marked.
+    fn varint(&mut self) -> Result<usize, String> {
+        let (mut n, mut shift) = (0usize, 1usize);
+        loop {
+            let b = self.byte()?;
+            n = n
+                .checked_add((b & 0x7f) as usize * shift)
+                .ok_or("the patch has a number that is too large")?;
+            if b & 0x80 != 0 {
+                return Ok(n);
+            }
+            shift = shift
+                .checked_shl(7)
+                .filter(|&s| s < 1 << 40)
+                .ok_or("the patch has a number that is too large")?;
+            n += shift;
+        }
+    }
+}
+
+/* records of a 3 byte offset and a 2 byte length, followed by the bytes. a length of 0 is a
+run of one byte instead. after EOF there may be a size to cut the ROM to. */
+fn ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let mut target = rom.to_vec();
+    let mut reader = Reader::new(patch, 5);
+
+    loop {
+        let offset = reader.number(3)?;
+        if offset == 0x454f46 {
+            break;
+        }
+        let (length, bytes) = match reader.number(2)? {
+            0 => {
+                let length = reader.number(2)?;
+                (length, vec![reader.byte()?; length])
+            }
+            length => (length, reader.bytes(length)?.to_vec()),
+        };
+        if offset + length > MAX_SIZE {
+            return Err(too_large());
+        }
+
+        if target.len() < offset + length {
+            target.resize(offset + length, 0);
+        }
+        target[offset..offset + length].copy_from_slice(&bytes);
+    }
+
+    if let Ok(size) = reader.number(3) {
+        target.truncate(size);
+    }
+    Ok(target)
+}
+
+// the CRC32s at the end of UPS and BPS patches, of the source, the target and the patch.
+fn checksums(rom: &[u8], patch: &[u8]) -> Result<u32, String> {
+    if patch.len() < 16 {
+        return Err("the patch ends too early".to_string());
+    }
+    let footer = &patch[patch.len() - 12..];
+    let crc = |i: usize| u32::from_le_bytes(footer[i..i + 4].try_into().unwrap());
+
+    if crc32fast::hash(&patch[..patch.len() - 4]) != crc(8) {
+        return Err("the patch is damaged, its checksum does not match".to_string());
+    }
+    if crc32fast::hash(rom) != crc(0) {
+        return Err("the patch is for a different ROM, the checksum does not match".to_string());
+    }
+    Ok(crc(4))
+}
+
+fn too_large() -> String {
+    "the patched ROM would be larger than a Game Boy ROM can be".to_string()
+}
+
+fn check_target(target: &[u8], crc: u32) -> Result<(), String> {
+    match crc32fast::hash(target) == crc {
+        true => Ok(()),
+        false => Err("the patched ROM does not match the checksum of the patch".to_string()),
+    }
+}
+
+/* the sizes of the source and the target, then runs of bytes to xor with the source, each
+after a number of bytes to skip and ending with a 0. */
+fn ups(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let target_crc = checksums(rom, patch)?;
+    let mut reader = Reader::new(&patch[..patch.len() - 12], 4);
+
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    if source_size != rom.len() {
+        return Err("the patch is for a ROM of a different size".to_string());
+    }
+    if target_size > MAX_SIZE {
+        return Err(too_large());
+    }
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+    let mut position = 0;
+    while reader.position < reader.data.len() {
+        position += reader.varint()?;
+        loop {
+            let b = reader.byte()?;
+            if b == 0 {
+                position += 1;
+                break;
+            }
+            if let Some(t) = target.get_mut(position) {
+                *t ^= b;
+            }
+            position += 1;
+        }
+    }
+
+    check_target(&target, target_crc)?;
+    Ok(target)
+}
+
+/* the sizes and metadata, then actions that build the target: copying from the same place of
+the source, from the patch, or from a relative place of the source or the target so far. */
+fn bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    let target_crc = checksums(rom, patch)?;
+    let mut reader = Reader::new(&patch[..patch.len() - 12], 4);
+
+    let source_size = reader.varint()?;
+    let target_size = reader.varint()?;
+    let metadata = reader.varint()?;
+    reader.bytes(metadata)?;
+    if source_size != rom.len() {
+        return Err("the patch is for a ROM of a different size".to_string());
+    }
+    if target_size > MAX_SIZE {
+        return Err(too_large());
+    }
+
+    let broken = || "the patch copies from outside of the ROM".to_string();
+    let mut target: Vec<u8> = Vec::with_capacity(target_size);
+    let (mut source_offset, mut target_offset) = (0isize, 0isize);
+    while reader.position < reader.data.len() {
+        let action = reader.varint()?;
+        let length = (action >> 2) + 1;
+        if target.len() + length > target_size {
+            return Err("the patch writes past the end of the ROM".to_string());
+        }
+
+        match action & 0x3 {
+            // source read
+            0 => {
+                let start = target.len();
+                target.extend_from_slice(rom.get(start..start + length).ok_or_else(broken)?);
+            }
+            // target read
+            1 => target.extend_from_slice(reader.bytes(length)?),
+            // source copy
+            2 => {
+                source_offset += relative(reader.varint()?);
+                let start = usize::try_from(source_offset).map_err(|_| broken())?;
+                target.extend_from_slice(rom.get(start..start + length).ok_or_else(broken)?);
+                source_offset += length as isize;
+            }
+            // target copy, byte by byte as it may overlap with what it writes
+            _ => {
+                target_offset += relative(reader.varint()?);
+                for _ in 0..length {
+                    let b = usize::try_from(target_offset)
+                        .ok()
+                        .and_then(|i| target.get(i).copied())
+                        .ok_or_else(broken)?;
+                    target.push(b);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err("the patched ROM is not the size of the patch".to_string());
+    }
+    check_target(&target, target_crc)?;
+    Ok(target)
+}
+
+// offsets of copies, the lowest bit is the sign.
+fn relative(n: usize) -> isize {
+    let offset = (n >> 1) as isize;
+    if n & 1 != 0 {
+        -offset
+    } else {
+        offset
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn varint(mut n: usize, out: &mut Vec<u8>) {
+        loop {
+            let b = (n & 0x7f) as u8;
+            n >>= 7;
+            if n == 0 {
+                out.push(0x80 | b);
+                return;
+            }
+            out.push(b);
+            n -= 1;
+        }
+    }
+
+    // the header with the sizes, the body, and the footer with the checksums.
+    fn patch(magic: &[u8], rom: &[u8], target: &[u8], target_size: usize, body: &[u8]) -> Vec<u8> {
+        let mut patch = magic.to_vec();
+        varint(rom.len(), &mut patch);
+        varint(target_size, &mut patch);
+        if magic == b"BPS1" {
+            varint(0, &mut patch);
+        }
+        patch.extend_from_slice(body);
+        patch.extend_from_slice(&crc32fast::hash(rom).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(&patch).to_le_bytes());
+        patch
+    }
+
+    #[test]
+    fn ips_records_and_runs() {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0, 0, 2, 0, 2, 7, 8]);
+        patch.extend_from_slice(&[0, 0, 6, 0, 0, 0, 2, 9]);
+        patch.extend_from_slice(b"EOF");
+        assert_eq!(ips(&[1; 4], &patch), Ok(vec![1, 1, 7, 8, 0, 0, 9, 9]));
+
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x80, 0, 0, 0, 1, 1]);
+        assert_eq!(ips(&[1; 4], &patch), Err(too_large()));
+    }
+
+    #[test]
+    fn ups_xors_the_source() {
+        let rom = [1, 2, 3, 4];
+        let target = [1, 7, 3, 4, 5];
+        let mut body = Vec::new();
+        varint(1, &mut body);
+        body.extend_from_slice(&[2 ^ 7, 0]);
+        varint(1, &mut body);
+        body.extend_from_slice(&[5, 0]);
+        let good = patch(b"UPS1", &rom, &target, target.len(), &body);
+        assert_eq!(ups(&rom, &good), Ok(target.to_vec()));
+
+        let mut damaged = good.clone();
+        damaged[7] ^= 1;
+        assert!(ups(&rom, &damaged).unwrap_err().contains("damaged"));
+        assert!(ups(&[1, 2, 3, 5], &good)
+            .unwrap_err()
+            .contains("different ROM"));
+
+        let oversized = patch(b"UPS1", &rom, &target, MAX_SIZE + 1, &body);
+        assert_eq!(ups(&rom, &oversized), Err(too_large()));
+    }
+
+    #[test]
+    fn bps_actions() {
+        let rom = [1, 2, 3, 4];
+        let target = [1, 2, 9, 9, 3, 4, 1, 2];
+        let mut body = Vec::new();
+        // source read of 2, target read of 2, source copy of 2 from 2, target copy of 2 from 0.
+        varint(4, &mut body);
+        varint(5, &mut body);
+        body.extend_from_slice(&[9, 9]);
+        for n in [6, 4, 7, 0] {
+            varint(n, &mut body);
+        }
+        let good = patch(b"BPS1", &rom, &target, target.len(), &body);
+        assert_eq!(bps(&rom, &good), Ok(target.to_vec()));
+
+        let wrong = patch(b"BPS1", &rom, &[0; 8], target.len(), &body);
+        assert!(bps(&rom, &wrong).unwrap_err().contains("does not match"));
+
+        let oversized = patch(b"BPS1", &rom, &target, MAX_SIZE + 1, &body);
+        assert_eq!(bps(&rom, &oversized), Err(too_large()));
+    }
+}
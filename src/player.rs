@@ -1,17 +1,21 @@
-use std::sync::{Arc, Mutex};
-
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use cpal::{BufferSize, Sample, SampleRate, Stream, StreamConfig};
 
+use crate::ring::Consumer;
+use crate::system::apu;
+
 pub const SAMPLE_RATE: u32 = 48000;
 
+// amount of audio (in milliseconds) kept queued for the player when syncing to audio.
+pub const LATENCY: u32 = 40;
+
 pub struct CpalPlayer {
     pub stream: Stream,
     config: StreamConfig,
 }
 
 impl CpalPlayer {
-    pub fn new(audio_buffer: Arc<Mutex<Vec<(f32, f32)>>>) -> Option<Box<dyn Player>> {
+    pub fn new(audio_buffer: Consumer<apu::Sample>) -> Option<Box<dyn Player>> {
         let host = cpal::default_host();
         let device = host
             .default_output_device()
@@ -37,20 +41,11 @@ impl CpalPlayer {
                     .build_output_stream(
                         &config,
                         move |data: &mut [f32], _: &cpal::OutputCallbackInfo| {
-                            if let Ok(mut buffer) = audio_buffer.try_lock() {
-                                let len = std::cmp::min(data.len() / 2, buffer.len());
-                                if len == 0 {
-                                    return;
-                                }
-                                for (i, (data_l, data_r)) in buffer.drain(..len).enumerate() {
-                                    data[i * 2 + 0] = data_l;
-                                    data[i * 2 + 1] = data_r;
-                                }
-                            } else {
-                                // Play silence if we can't acquire the lock
-                                for sample in data.iter_mut() {
-                                    *sample = 0.0;
-                                }
+                            // plays silence for any sample the buffer cannot provide.
+                            for frame in data.chunks_mut(2) {
+                                let (data_l, data_r) = audio_buffer.pop().unwrap_or((0.0, 0.0));
+                                frame[0] = data_l;
+                                frame[1] = data_r;
                             }
                         },
                         err_fn,
@@ -64,20 +59,11 @@ impl CpalPlayer {
                     .build_output_stream(
                         &config,
                         move |data: &mut [f64], _: &cpal::OutputCallbackInfo| {
-                            if let Ok(mut buffer) = audio_buffer.try_lock() {
-                                let len = std::cmp::min(data.len() / 2, buffer.len());
-                                if len == 0 {
-                                    return;
-                                }
-                                for (i, (data_l, data_r)) in buffer.drain(..len).enumerate() {
-                                    data[i * 2 + 0] = data_l.to_sample::<f64>();
-                                    data[i * 2 + 1] = data_r.to_sample::<f64>();
-                                }
-                            } else {
-                                // Play silence if we can't acquire the lock
-                                for sample in data.iter_mut() {
-                                    *sample = 0.0;
-                                }
+                            // plays silence for any sample the buffer cannot provide.
+                            for frame in data.chunks_mut(2) {
+                                let (data_l, data_r) = audio_buffer.pop().unwrap_or((0.0, 0.0));
+                                frame[0] = data_l.to_sample::<f64>();
+                                frame[1] = data_r.to_sample::<f64>();
                             }
                         },
                         err_fn,
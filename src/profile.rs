@@ -0,0 +1,98 @@
+use crate::movie::FRAME_CYCLES;
+use crate::system::bus::Instrumented;
+use crate::system::cpu::{CPUResult, CPU};
+use crate::system::mbc::{self, MBCError};
+use crate::system::mmu::MMU;
+use crate::trace;
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+// an instruction that ran, by bank and address.
+struct Hot {
+    bytes: [u8; 4],
+    count: u64,
+    cycles: u64,
+}
+
+/* runs a ROM without a window or input for a number of frames, on an instrumented MMU.
+prints the instructions that took the most cycles and the addresses read and written most. */
+pub fn run(rom_path: &Path, frames: u64, top: usize) -> Result<bool, String> {
+    let rom = fs::read(rom_path).map_err(|e| format!("{:?} could not be read: {}", rom_path, e))?;
+    let cartridge = match mbc::new(rom) {
+        Ok(cartridge) => cartridge,
+        Err(MBCError::CGB) => return Err("the cartridge is for the CGB only".to_string()),
+        Err(MBCError::MBCType(t)) => return Err(format!("cartridge type {} is not supported", t)),
+        Err(MBCError::RAMLength) => return Err("the cartridge RAM is not valid".to_string()),
+    };
+    let mut cpu = CPU::with_bus(Instrumented::new(MMU::init(cartridge), false));
+
+    let mut hot: HashMap<(usize, u16), Hot> = HashMap::new();
+    let (mut cycles, mut idle) = (0u64, 0u64);
+    let total = frames * FRAME_CYCLES as u64;
+    while cycles < total {
+        let pc = cpu.pc();
+        let bank = match pc {
+            0x4000..=0x7fff => cpu.mmu.bus.cartridge.banks().0,
+            _ => 0,
+        };
+        // read past the instrumented bus, so they are not counted.
+        let bytes = [0, 1, 2, 3].map(|i| cpu.mmu.bus.read_byte(pc.wrapping_add(i)));
+
+        let state = cpu.step();
+        let t_cycles = state.timing as u64 * 4;
+        cycles += t_cycles;
+        match state.result {
+            CPUResult::Ins(_) | CPUResult::InsCB(_) => {
+                let entry = hot.entry((bank, pc)).or_insert(Hot {
+                    bytes,
+                    count: 0,
+                    cycles: 0,
+                });
+                entry.count += 1;
+                entry.cycles += t_cycles;
+            }
+            _ => idle += t_cycles,
+        }
+    }
+
+    println!(
+        "{} frames, {} cycles, {:.1}% halted or dispatching interrupts\n",
+        frames,
+        cycles,
+        idle as f64 * 100.0 / cycles as f64
+    );
+
+    let mut instructions: Vec<(&(usize, u16), &Hot)> = hot.iter().collect();
+    instructions.sort_by_key(|(_, entry)| Reverse(entry.cycles));
+    println!("Instructions by cycles");
+    for ((bank, pc), hot) in instructions.into_iter().take(top) {
+        println!(
+            "  {:02X}:{:04X}  {:<20} {:>10} times {:>12} cycles {:>5.1}%",
+            bank,
+            pc,
+            trace::disassemble(hot.bytes),
+            hot.count,
+            hot.cycles,
+            hot.cycles as f64 * 100.0 / cycles as f64
+        );
+    }
+
+    // reads include the fetches of the instructions.
+    let bus = &cpu.mmu;
+    let reads: Vec<(u16, u64)> = (0..=0xffff).map(|a| (a, bus.reads(a))).collect();
+    let writes: Vec<(u16, u64)> = (0..=0xffff).map(|a| (a, bus.writes(a))).collect();
+    for (title, mut counts) in [
+        ("Addresses by reads", reads),
+        ("Addresses by writes", writes),
+    ] {
+        counts.retain(|&(_, count)| count > 0);
+        counts.sort_by_key(|&(_, count)| Reverse(count));
+        println!("\n{}", title);
+        for (address, count) in counts.into_iter().take(top) {
+            println!("  {:04X}  {:>12}", address, count);
+        }
+    }
+    Ok(true)
+}
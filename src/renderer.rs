@@ -330,6 +330,80 @@ impl Renderer {
         rpass.draw(0..3, 0..1);
     }
 
+    /* renders the last frame into a texture the size of the window and reads it back,
+    for screenshots with the shader. returns the RGBA pixels, width and height. */
+    pub fn capture(&mut self, pixels: &Pixels) -> (Vec<u8>, u32, u32) {
+        let device = pixels.device();
+        let format = pixels.render_texture_format();
+        let size = wgpu::Extent3d {
+            width: self.width,
+            height: self.height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("Capture texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // rows of a texture copy have to be aligned to 256 bytes.
+        let row = self.width * 4;
+        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let padded = row.div_ceil(align) * align;
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Capture buffer"),
+            size: (padded * self.height) as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("Capture encoder"),
+        });
+        self.render(&mut encoder, &view);
+        encoder.copy_texture_to_buffer(
+            texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded),
+                    rows_per_image: None,
+                },
+            },
+            size,
+        );
+        pixels.queue().submit(Some(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        slice.map_async(wgpu::MapMode::Read, |_| ());
+        device.poll(wgpu::Maintain::Wait);
+
+        // surfaces are often BGRA, the channels are swapped back to RGBA.
+        let bgra = matches!(
+            format,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb
+        );
+        let mut frame = Vec::with_capacity((row * self.height) as usize);
+        for line in slice.get_mapped_range().chunks(padded as usize) {
+            for pixel in line[..row as usize].chunks(4) {
+                match bgra {
+                    true => frame.extend_from_slice(&[pixel[2], pixel[1], pixel[0], 0xff]),
+                    false => frame.extend_from_slice(&[pixel[0], pixel[1], pixel[2], 0xff]),
+                }
+            }
+        }
+        buffer.unmap();
+
+        (frame, self.width, self.height)
+    }
+
     pub fn reset(&mut self) {
         self.time = 0.0
     }
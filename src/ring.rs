@@ -0,0 +1,124 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+use crossbeam::queue::ArrayQueue;
+
+/* lock-free ring buffer for passing samples from the APU to the audio thread.
+the queue is split into a Producer and a Consumer half so there is only ever one writer and one reader. */
+struct Ring<T> {
+    queue: ArrayQueue<T>,
+    // times the ring ran empty while the producer was feeding it, once per run of missing samples.
+    underruns: AtomicU64,
+    // samples the producer dropped while the ring was full.
+    overruns: AtomicU64,
+    // set by the producer while it has nothing to give, like when the emulator is paused.
+    idle: AtomicBool,
+    // the last pop found the ring empty.
+    starved: AtomicBool,
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RingStats {
+    pub len: usize,
+    pub capacity: usize,
+    pub underruns: u64,
+    pub overruns: u64,
+}
+
+pub struct Producer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+pub struct Consumer<T> {
+    ring: Arc<Ring<T>>,
+}
+
+pub fn ring<T>(capacity: usize) -> (Producer<T>, Consumer<T>) {
+    let ring = Arc::new(Ring {
+        queue: ArrayQueue::new(capacity),
+        underruns: AtomicU64::new(0),
+        overruns: AtomicU64::new(0),
+        idle: AtomicBool::new(false),
+        starved: AtomicBool::new(false),
+    });
+
+    (Producer { ring: ring.clone() }, Consumer { ring })
+}
+
+impl<T> Ring<T> {
+    fn stats(&self) -> RingStats {
+        RingStats {
+            len: self.queue.len(),
+            capacity: self.queue.capacity(),
+            underruns: self.underruns.load(Ordering::Relaxed),
+            overruns: self.overruns.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl<T> Producer<T> {
+    // pushes a value, dropping it if the ring is full.
+    pub fn push(&self, value: T) -> bool {
+        match self.ring.queue.push(value) {
+            Ok(_) => true,
+            Err(_) => {
+                self.ring.overruns.fetch_add(1, Ordering::Relaxed);
+                false
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ring.queue.len()
+    }
+
+    pub fn stats(&self) -> RingStats {
+        self.ring.stats()
+    }
+
+    // while idle, running empty is on purpose and not counted as an underrun.
+    pub fn set_idle(&self, idle: bool) {
+        self.ring.idle.store(idle, Ordering::Relaxed);
+    }
+}
+
+impl<T> Consumer<T> {
+    pub fn pop(&self) -> Option<T> {
+        let value = self.ring.queue.pop();
+        let starved = value.is_none();
+        if starved
+            && !self.ring.starved.swap(true, Ordering::Relaxed)
+            && !self.ring.idle.load(Ordering::Relaxed)
+        {
+            self.ring.underruns.fetch_add(1, Ordering::Relaxed);
+        } else if !starved {
+            self.ring.starved.store(false, Ordering::Relaxed);
+        }
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ring;
+
+    #[test]
+    fn underruns_are_counted_once_and_not_while_idle() {
+        let (producer, consumer) = ring(4);
+        producer.push(1);
+        for _ in 0..3 {
+            consumer.pop();
+        }
+        assert_eq!(producer.stats().underruns, 1);
+
+        producer.push(2);
+        consumer.pop();
+        producer.set_idle(true);
+        for _ in 0..3 {
+            consumer.pop();
+        }
+        assert_eq!(producer.stats().underruns, 1);
+    }
+}
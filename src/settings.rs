@@ -0,0 +1,301 @@
+use crate::config::{Color, Config, SPEEDS};
+use crate::font::{self, ADVANCE, GLYPH_HEIGHT};
+use crate::keybind::{self, Binding, Key};
+use crate::palette::PRESETS;
+use crate::renderer::{Shader, SHADER_LIST};
+use crate::system::joypad::Input;
+use crate::system::ppu::{LAYER_BG, LAYER_OBJ0, LAYER_OBJ1};
+use crate::system::sgb;
+
+// the text fits the game screen, the shade covers the whole frame.
+const WIDTH: usize = 160;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+const COLUMNS: usize = WIDTH / ADVANCE;
+// items shown at once, between the title and the message. the list scrolls with the selection.
+const ROWS: usize = 14;
+
+const WHITE: [u8; 3] = [0xff, 0xff, 0xff];
+const GRAY: [u8; 3] = [0xaa, 0xaa, 0xaa];
+const YELLOW: [u8; 3] = [0xff, 0xd8, 0x40];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Item {
+    // the palette of one of the ppu layers.
+    Palette(u8),
+    AutoPalette,
+    Shader,
+    Speed,
+    Audio,
+    Volume,
+    Button(Input),
+}
+
+const ITEMS: [Item; 16] = [
+    Item::Palette(LAYER_BG),
+    Item::Palette(LAYER_OBJ0),
+    Item::Palette(LAYER_OBJ1),
+    Item::AutoPalette,
+    Item::Shader,
+    Item::Speed,
+    Item::Audio,
+    Item::Volume,
+    Item::Button(Input::Up),
+    Item::Button(Input::Down),
+    Item::Button(Input::Left),
+    Item::Button(Input::Right),
+    Item::Button(Input::A),
+    Item::Button(Input::B),
+    Item::Button(Input::Select),
+    Item::Button(Input::Start),
+];
+
+// what the controller has to do after a key was handled by the settings.
+#[derive(PartialEq)]
+pub enum Change {
+    None,
+    Close,
+    // the config was changed and has to be validated, saved and applied.
+    Config,
+    // like Config, but audio was turned on or off.
+    Audio,
+}
+
+/* the settings overlay drawn over the game. it is navigated with the directions of the joypad,
+A or enter picks an item and B or escape closes it. */
+pub struct Settings {
+    selected: usize,
+    // true while waiting for the key to bind to the selected button.
+    capturing: bool,
+    // a modifier pressed while capturing, bound on its own if it is let go before another key.
+    modifier: Option<Key>,
+    message: Option<String>,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self {
+            selected: 0,
+            capturing: false,
+            modifier: None,
+            message: None,
+        }
+    }
+
+    pub fn set_message(&mut self, message: String) {
+        self.message = Some(message);
+    }
+
+    /* handles a key pressed or let go. only capturing looks at keys let go, so a modifier held
+    for the key to bind can be bound on its own too. */
+    pub fn key(&mut self, key: &Key, pressed: bool, config: &mut Config) -> Change {
+        let is = |name: &str| key.names.iter().any(|n| n == name);
+
+        if self.capturing {
+            let key = match (pressed, self.modifier.take()) {
+                (false, Some(modifier)) if modifier.names == key.names => modifier,
+                (false, modifier) => {
+                    self.modifier = modifier;
+                    return Change::None;
+                }
+                (true, _) if keybind::is_modifier(key) => {
+                    self.modifier = Some(key.clone());
+                    return Change::None;
+                }
+                (true, _) if is("escape") => {
+                    self.capturing = false;
+                    self.message = None;
+                    return Change::None;
+                }
+                (true, _) => key.clone(),
+            };
+            self.capturing = false;
+            self.message = None;
+            return match ITEMS[self.selected] {
+                Item::Button(input) => Self::bind(&key, config.button_mut(input)),
+                _ => Change::None,
+            };
+        }
+        if !pressed {
+            return Change::None;
+        }
+        self.message = None;
+
+        match config.get_input(key) {
+            Some(Input::Up) => {
+                self.selected = (self.selected + ITEMS.len() - 1) % ITEMS.len();
+                Change::None
+            }
+            Some(Input::Down) => {
+                self.selected = (self.selected + 1) % ITEMS.len();
+                Change::None
+            }
+            Some(Input::Left) => self.adjust(config, -1),
+            Some(Input::Right) => self.adjust(config, 1),
+            Some(Input::B) => Change::Close,
+            _ if is("escape") => Change::Close,
+            input if input == Some(Input::A) || is("enter") => match ITEMS[self.selected] {
+                Item::Button(input) => {
+                    self.capturing = true;
+                    self.message = Some(format!(
+                        "press a key for {:?}, modifiers can be held",
+                        input
+                    ));
+                    Change::None
+                }
+                _ => self.adjust(config, 1),
+            },
+            _ => Change::None,
+        }
+    }
+
+    // steps the selected item to its next (1) or previous (-1) value.
+    fn adjust(&mut self, config: &mut Config, direction: isize) -> Change {
+        match ITEMS[self.selected] {
+            Item::Palette(layer) => {
+                let palettes = palettes(config);
+                let color = layer_color(config, layer);
+                let current = palettes.iter().position(|(_, c)| c == color);
+                *color = palettes[step(current, palettes.len(), direction)].1;
+                // picking a palette by hand turns off picking them by title.
+                config.palettes.auto = false;
+                Change::Config
+            }
+            Item::AutoPalette => {
+                config.palettes.auto = !config.palettes.auto;
+                Change::Config
+            }
+            Item::Shader => {
+                let current = SHADER_LIST
+                    .iter()
+                    .position(|&shader| Shader::name(shader) == config.shader);
+                let shader = SHADER_LIST[step(current, SHADER_LIST.len(), direction)];
+                config.shader = Shader::name(shader);
+                Change::Config
+            }
+            Item::Speed => {
+                let current = SPEEDS.iter().position(|&speed| speed == config.speed);
+                config.speed = SPEEDS[step(current, SPEEDS.len(), direction)];
+                Change::Config
+            }
+            Item::Audio => {
+                config.audio = !config.audio;
+                Change::Audio
+            }
+            Item::Volume => {
+                let volume = config.mixer.master + 0.1 * direction as f32;
+                // rounded so that repeated steps do not drift.
+                config.mixer.master = ((volume * 10.0).round() / 10.0).clamp(0.0, 1.0);
+                Change::Config
+            }
+            Item::Button(_) => Change::None,
+        }
+    }
+
+    /* binds the key, with the modifiers held, to a button. it replaces the bindings of the same
+    kind, so binding a key keeps the gamepad bindings and the other way around. */
+    fn bind(key: &Key, bindings: &mut Vec<String>) -> Change {
+        let binding = match Binding::text(key) {
+            Some(binding) => binding,
+            None => return Change::None,
+        };
+
+        let pad = binding.starts_with("pad:");
+        bindings.retain(|binding| binding.starts_with("pad:") != pad);
+        bindings.push(binding);
+        Change::Config
+    }
+
+    // draws the overlay over an RGBA frame of the screen, with the name of the profile being edited.
+    pub fn draw(&self, frame: &mut [u8], config: &Config, profile: Option<&str>) {
+        let (width, height) = sgb::frame_size(frame.len());
+        font::shade(frame, width, 0, 0, width, height);
+        let title = match profile {
+            Some(profile) => format!("Settings: {}", profile),
+            None => "Settings".to_string(),
+        };
+        let title: String = title.chars().take(COLUMNS).collect();
+        font::draw_text(frame, width, 2, 2, &title, YELLOW);
+
+        let first = (self.selected + 1).saturating_sub(ROWS);
+        for (i, item) in ITEMS.iter().enumerate().skip(first).take(ROWS) {
+            let value = match item {
+                Item::Palette(layer) => {
+                    let color = match *layer {
+                        LAYER_OBJ0 => &config.palettes.obj0,
+                        LAYER_OBJ1 => &config.palettes.obj1,
+                        _ => &config.palettes.bg,
+                    };
+                    palettes(config)
+                        .into_iter()
+                        .find(|(_, c)| c == color)
+                        .map_or("Custom".to_string(), |(name, _)| name)
+                }
+                Item::AutoPalette => (if config.palettes.auto { "on" } else { "off" }).to_string(),
+                Item::Shader => config.shader.clone(),
+                Item::Speed => format!("{}x", config.speed),
+                Item::Audio => (if config.audio { "on" } else { "off" }).to_string(),
+                Item::Volume => format!("{:.0}%", config.mixer.master * 100.0),
+                Item::Button(input) => match config.buttons().iter().find(|(_, _, i)| i == input) {
+                    Some((_, bindings, _)) => bindings.join(", "),
+                    None => String::new(),
+                },
+            };
+            let label = match item {
+                Item::Palette(LAYER_OBJ0) => "OBJ0 Palette".to_string(),
+                Item::Palette(LAYER_OBJ1) => "OBJ1 Palette".to_string(),
+                Item::Palette(_) => "BG Palette".to_string(),
+                Item::AutoPalette => "Auto Palette".to_string(),
+                Item::Button(input) => format!("{:?}", input),
+                _ => format!("{:?}", item),
+            };
+
+            let selected = i == self.selected;
+            let mut line = format!("{}{}: {}", if selected { ">" } else { " " }, label, value);
+            if line.chars().count() > COLUMNS {
+                line = line.chars().take(COLUMNS - 2).collect();
+                line.push_str("..");
+            }
+
+            let color = if selected { WHITE } else { GRAY };
+            let y = 2 + (i - first + 1) * LINE_HEIGHT;
+            font::draw_text(frame, width, 2, y, &line, color);
+        }
+
+        if let Some(message) = &self.message {
+            let y = 2 + (ROWS + 1) * LINE_HEIGHT;
+            let message: String = message.chars().take(COLUMNS).collect();
+            font::draw_text(frame, width, 2, y, &message, YELLOW);
+        }
+    }
+}
+
+// the presets, then the palettes imported into the library.
+fn palettes(config: &Config) -> Vec<(String, Color)> {
+    PRESETS
+        .iter()
+        .map(|(name, color)| (name.to_string(), *color))
+        .chain(
+            config
+                .palettes
+                .library
+                .iter()
+                .map(|palette| (palette.name.clone(), palette.colors)),
+        )
+        .collect()
+}
+
+fn layer_color(config: &mut Config, layer: u8) -> &mut Color {
+    match layer {
+        LAYER_OBJ0 => &mut config.palettes.obj0,
+        LAYER_OBJ1 => &mut config.palettes.obj1,
+        _ => &mut config.palettes.bg,
+    }
+}
+
+// the index after current, wrapping around. values not in the list start from the first one.
+fn step(current: Option<usize>, len: usize, direction: isize) -> usize {
+    match current {
+        Some(i) => (i as isize + direction).rem_euclid(len as isize) as usize,
+        None => 0,
+    }
+}
@@ -0,0 +1,301 @@
+use crate::system::bus::{Access, Flat, Instrumented};
+use crate::system::cpu::registers::Register;
+use crate::system::cpu::CPU;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// an m-cycle of a test, the address and data on the bus and the pins, null when nothing is on it.
+type Cycle = Option<(Option<u16>, Option<u8>, String)>;
+
+/* the single step tests of the SM83, a JSON file per opcode with tests that set the registers
+and RAM, run one instruction and list the state after it and the bus activity of every
+m-cycle. cycles are [address, value, pins], pins like "r-m" for a read and "-wm" for a write.
+
+the tests model the prefetch of the SM83: the opcode at pc - 1 has been fetched already, and the
+last m-cycle fetches the next one, so pc is one past the instruction and ends one past the next.
+the core fetches the opcode in the first m-cycle instead, so it runs from pc - 1, and its first
+access stands for the last one of the test. */
+#[derive(Deserialize)]
+struct Test {
+    name: String,
+    initial: State,
+    #[serde(rename = "final")]
+    expected: State,
+    cycles: Vec<Cycle>,
+}
+
+#[derive(Deserialize)]
+struct State {
+    pc: u16,
+    sp: u16,
+    a: u8,
+    b: u8,
+    c: u8,
+    d: u8,
+    e: u8,
+    f: u8,
+    h: u8,
+    l: u8,
+    ime: u8,
+    #[serde(default)]
+    ie: Option<u8>,
+    ram: Vec<(u16, u8)>,
+}
+
+const REGISTERS: [(Register, &str); 8] = [
+    (Register::A, "A"),
+    (Register::B, "B"),
+    (Register::C, "C"),
+    (Register::D, "D"),
+    (Register::E, "E"),
+    (Register::F, "F"),
+    (Register::H, "H"),
+    (Register::L, "L"),
+];
+
+fn registers(state: &State) -> [u8; 8] {
+    [
+        state.a, state.b, state.c, state.d, state.e, state.f, state.h, state.l,
+    ]
+}
+
+// runs a test, returning what differs from the expected state.
+fn run_test(test: &Test) -> Vec<String> {
+    let initial = &test.initial;
+    let mut bus = Flat::new();
+    for &(address, value) in initial.ram.iter() {
+        bus.memory[address as usize] = value;
+    }
+    if let Some(ie) = initial.ie {
+        bus.memory[0xffff] = ie;
+    }
+
+    // a flat 64K of RAM, with every access kept to compare with the cycles.
+    let mut cpu = CPU::with_bus(Instrumented::new(bus, true));
+    for ((register, _), value) in REGISTERS.into_iter().zip(registers(initial)) {
+        cpu.registers.set(register, value);
+    }
+    cpu.set_pc(initial.pc.wrapping_sub(1));
+    cpu.set_sp(initial.sp);
+    cpu.ime = initial.ime != 0;
+
+    let timing = cpu.step().timing;
+
+    let expected = &test.expected;
+    let mut errors = Vec::new();
+    for ((register, name), value) in REGISTERS.into_iter().zip(registers(expected)) {
+        let got = cpu.registers.get(register);
+        if got != value {
+            errors.push(format!("{} {:02X} != {:02X}", name, got, value));
+        }
+    }
+    let pc = cpu.pc().wrapping_add(1);
+    for (name, got, value) in [("PC", pc, expected.pc), ("SP", cpu.sp(), expected.sp)] {
+        if got != value {
+            errors.push(format!("{} {:04X} != {:04X}", name, got, value));
+        }
+    }
+    if cpu.ime != (expected.ime != 0) {
+        errors.push(format!("IME {} != {}", cpu.ime as u8, expected.ime));
+    }
+    for &(address, value) in expected.ram.iter() {
+        let got = cpu.mmu.bus.memory[address as usize];
+        if got != value {
+            errors.push(format!("({:04X}) {:02X} != {:02X}", address, got, value));
+        }
+    }
+
+    if timing as usize != test.cycles.len() {
+        errors.push(format!("m-cycles {} != {}", timing, test.cycles.len()));
+    }
+    let mut activity: Vec<Access> = test
+        .cycles
+        .iter()
+        .flatten()
+        .filter_map(|(address, value, pins)| {
+            let write = pins.contains('w');
+            match (address, value) {
+                (Some(address), Some(value)) if write || pins.contains('r') => Some(Access {
+                    address: *address,
+                    value: *value,
+                    write,
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+    // the fetch of the opcode is left out of both, the one of the core and the one of the next.
+    activity.pop();
+    let mut accesses = cpu.mmu.take_log();
+    if !accesses.is_empty() {
+        accesses.remove(0);
+    }
+    if let Some(i) =
+        (0..activity.len().max(accesses.len())).find(|&i| activity.get(i) != accesses.get(i))
+    {
+        let show = |access: Option<&Access>| match access {
+            Some(a) => format!(
+                "{} {:04X} {:02X}",
+                if a.write { "write" } else { "read" },
+                a.address,
+                a.value
+            ),
+            None => "nothing".to_string(),
+        };
+        errors.push(format!(
+            "bus access {}: {} != {}",
+            i + 1,
+            show(accesses.get(i)),
+            show(activity.get(i))
+        ));
+    }
+    errors
+}
+
+// the JSON files at the paths, and in the folders among them.
+fn files(paths: &[PathBuf]) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            let entries =
+                fs::read_dir(path).map_err(|e| format!("{:?} could not be read: {}", path, e))?;
+            let mut jsons: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.extension().is_some_and(|e| e == "json"))
+                .collect();
+            jsons.sort();
+            files.extend(jsons);
+        } else {
+            files.push(path.clone());
+        }
+    }
+    Ok(files)
+}
+
+// runs the tests of a file, returning how many passed and the failures to show.
+fn run_file(path: &Path, verbose: bool) -> Result<(usize, usize, Vec<String>), String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("{:?} could not be read: {}", path, e))?;
+    let tests: Vec<Test> =
+        serde_json::from_str(&text).map_err(|e| format!("{:?} is not a test file: {}", path, e))?;
+
+    let mut failures = Vec::new();
+    let mut failed = 0;
+    for test in tests.iter() {
+        let errors = run_test(test);
+        if errors.is_empty() {
+            continue;
+        }
+        // the first failure of a file is always shown, the others only when verbose.
+        if failed == 0 || verbose {
+            failures.push(format!("  {}: {}", test.name, errors.join(", ")));
+        }
+        failed += 1;
+    }
+    Ok((tests.len() - failed, tests.len(), failures))
+}
+
+/* runs the tests of every file, printing a line per file with the tests that passed.
+returns whether all of them passed. */
+pub fn run(paths: &[PathBuf], verbose: bool) -> Result<bool, String> {
+    let files = files(paths)?;
+    if files.is_empty() {
+        return Err("no test files found".to_string());
+    }
+
+    let (mut passed, mut total, mut failed_files) = (0, 0, 0);
+    for path in files.iter() {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        let (file_passed, file_total, failures) = run_file(path, verbose)?;
+        if file_passed != file_total {
+            failed_files += 1;
+        }
+        println!("{}: {}/{}", name, file_passed, file_total);
+        for failure in failures.iter() {
+            println!("{}", failure);
+        }
+        passed += file_passed;
+        total += file_total;
+    }
+
+    println!(
+        "{}/{} tests passed, {} of {} files have failures",
+        passed,
+        total,
+        failed_files,
+        files.len()
+    );
+    Ok(passed == total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run_test, Test};
+    use std::path::Path;
+
+    /* a few tests in the format of the suite, worked out by hand from the instruction timings:
+    NOP, LD (HL),A which writes, JP nn with an idle m-cycle, and SWAP A and SET 0,(HL) of the
+    CB opcodes. */
+    const FIXTURES: &str = r#"[
+        {
+            "name": "00 nop",
+            "initial": {"pc": 257, "sp": 65534, "a": 1, "b": 0, "c": 19, "d": 0, "e": 216, "f": 176,
+                "h": 1, "l": 77, "ime": 0, "ie": 0, "ram": [[256, 0], [257, 0]]},
+            "final": {"pc": 258, "sp": 65534, "a": 1, "b": 0, "c": 19, "d": 0, "e": 216, "f": 176,
+                "h": 1, "l": 77, "ime": 0, "ram": [[256, 0], [257, 0]]},
+            "cycles": [[257, 0, "r-m"]]
+        },
+        {
+            "name": "77 ld (hl),a",
+            "initial": {"pc": 49153, "sp": 65534, "a": 90, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0,
+                "h": 208, "l": 16, "ime": 0, "ram": [[49152, 119], [49153, 0], [53264, 0]]},
+            "final": {"pc": 49154, "sp": 65534, "a": 90, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0,
+                "h": 208, "l": 16, "ime": 0, "ram": [[49152, 119], [53264, 90]]},
+            "cycles": [[53264, 90, "-wm"], [49153, 0, "r-m"]]
+        },
+        {
+            "name": "c3 jp nn",
+            "initial": {"pc": 1025, "sp": 65534, "a": 0, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0,
+                "h": 0, "l": 0, "ime": 0, "ram": [[1024, 195], [1025, 0], [1026, 64], [16384, 0]]},
+            "final": {"pc": 16385, "sp": 65534, "a": 0, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0,
+                "h": 0, "l": 0, "ime": 0, "ram": [[1024, 195], [1025, 0], [1026, 64]]},
+            "cycles": [[1025, 0, "r-m"], [1026, 64, "r-m"], null, [16384, 0, "r-m"]]
+        },
+        {
+            "name": "cb 37 swap a",
+            "initial": {"pc": 513, "sp": 65534, "a": 241, "b": 0, "c": 0, "d": 0, "e": 0, "f": 240,
+                "h": 0, "l": 0, "ime": 0, "ram": [[512, 203], [513, 55], [514, 0]]},
+            "final": {"pc": 515, "sp": 65534, "a": 31, "b": 0, "c": 0, "d": 0, "e": 0, "f": 0,
+                "h": 0, "l": 0, "ime": 0, "ram": [[512, 203], [513, 55]]},
+            "cycles": [[513, 55, "r-m"], [514, 0, "r-m"]]
+        },
+        {
+            "name": "cb c6 set 0,(hl)",
+            "initial": {"pc": 769, "sp": 65534, "a": 0, "b": 0, "c": 0, "d": 0, "e": 0, "f": 128,
+                "h": 193, "l": 35, "ime": 0, "ram": [[768, 203], [769, 198], [770, 0], [49443, 66]]},
+            "final": {"pc": 771, "sp": 65534, "a": 0, "b": 0, "c": 0, "d": 0, "e": 0, "f": 128,
+                "h": 193, "l": 35, "ime": 0, "ram": [[768, 203], [769, 198], [49443, 67]]},
+            "cycles": [[769, 198, "r-m"], [49443, 66, "r-m"], [49443, 67, "-wm"], [770, 0, "r-m"]]
+        }
+    ]"#;
+
+    #[test]
+    fn fixtures() {
+        let tests: Vec<Test> = serde_json::from_str(FIXTURES).unwrap();
+        for test in tests.iter() {
+            assert_eq!(run_test(test), Vec::<String>::new(), "{}", test.name);
+        }
+    }
+
+    /* the whole suite, which is not part of the repository. clone
+    https://github.com/SingleStepTests/sm83 and copy its v1 folder to tests/sm83, then run
+    cargo test single_step -- --ignored. */
+    #[test]
+    #[ignore]
+    fn single_step() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/sm83");
+        assert!(dir.is_dir(), "{:?} is missing", dir);
+        assert_eq!(super::run(&[dir], false), Ok(true));
+    }
+}
@@ -1,3 +1,5 @@
+use serde::{Deserialize, Serialize};
+
 pub trait Channel {
     fn read(&self, address: u16) -> u8;
 
@@ -7,9 +9,16 @@ pub trait Channel {
 
     fn step(&mut self, t_cycles: u32);
 
+    // clocked by the frame sequencer. disables the channel when the length counter runs out.
+    fn length_step(&mut self);
+
+    // clears the registers when the APU is turned off. on the DMG the length counters are kept.
+    fn power_off(&mut self);
+
     // fn trigger() ->
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Envelope {
     pub initial_volume: u8,
     pub volume: u8,
@@ -18,6 +27,8 @@ pub struct Envelope {
     // 0 disables.
     pub period: u8,
     pub clock: u8,
+    // stops once the volume reaches 0 or 15.
+    pub running: bool,
 }
 
 impl Envelope {
@@ -28,47 +39,78 @@ impl Envelope {
             direction: false,
             period: 0,
             clock: 0,
+            running: false,
         }
     }
 
     pub fn read(&self) -> u8 {
-        (self.initial_volume & 0xf) << 4 | (self.direction as u8) << 3 | self.clock & 0x7
+        (self.initial_volume & 0xf) << 4 | (self.direction as u8) << 3 | self.period & 0x7
     }
 
-    pub fn write(&mut self, value: u8) {
+    /* zombie mode - writing NRx2 while the channel is on changes the current volume on the DMG.
+    if the old period was 0 and the envelope is still running, volume goes up by 1, otherwise if it was
+    decreasing it goes up by 2. if the direction changed the volume becomes 16 - volume. */
+    pub fn write(&mut self, value: u8, channel_on: bool) {
+        let direction = value & 0x8 == 0x8;
+
+        if channel_on {
+            // the volume is a 4 bit register, so the changes wrap around.
+            let mut volume = self.volume;
+            if self.period == 0 && self.running {
+                volume = volume.wrapping_add(1) & 0xf;
+            } else if !self.direction {
+                volume = volume.wrapping_add(2) & 0xf;
+            }
+
+            if direction != self.direction {
+                volume = 16u8.wrapping_sub(volume) & 0xf;
+            }
+
+            self.volume = volume;
+        }
+
         self.initial_volume = value >> 4;
-        self.direction = value & 0x8 == 0x8;
+        self.direction = direction;
         self.period = value & 0x7;
-        self.volume = self.initial_volume;
     }
 
     pub fn trigger(&mut self) {
         self.volume = self.initial_volume;
         self.clock = self.period;
+        self.running = true;
     }
 
     pub fn step(&mut self) {
-        if self.period != 0 {
-            if self.clock > 0 {
-                self.clock -= 1
-            } else {
-                self.clock = self.period;
-                if (self.volume < 0xF && self.direction) || (self.volume > 0x0 && !self.direction) {
-                    if self.direction {
-                        self.volume += 1
-                    } else {
-                        self.volume -= 1
-                    }
+        if self.period == 0 {
+            return;
+        }
+
+        if self.clock > 0 {
+            self.clock -= 1
+        }
+
+        if self.clock == 0 {
+            self.clock = self.period;
+            if self.running {
+                if self.volume < 0xF && self.direction {
+                    self.volume += 1
+                } else if self.volume > 0x0 && !self.direction {
+                    self.volume -= 1
+                } else {
+                    self.running = false;
                 }
             }
         }
     }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Length {
     pub clock: u16,
     pub on: bool,
     pub max: u16,
+    // last step of the frame sequencer, used for the extra length clocking.
+    pub frame: u8,
 }
 
 impl Length {
@@ -77,29 +119,48 @@ impl Length {
             clock: 0,
             on: false,
             max,
+            frame: 0,
         }
     }
 
-    pub fn active(&self) -> bool {
-        self.clock > 0
-    }
-
     pub fn set(&mut self, clock: u16) {
         self.clock = self.max - clock;
     }
 
-    pub fn step(&mut self) {
+    // true if the next frame sequencer step will not clock the length counters.
+    fn first_half(&self) -> bool {
+        self.frame.is_multiple_of(2)
+    }
+
+    // returns true when the counter has just run out.
+    pub fn step(&mut self) -> bool {
         if self.on && self.clock != 0 {
             self.clock -= 1;
-            if self.clock == 0 {
-                self.on = false
-            }
+            return self.clock == 0;
         }
+        false
     }
 
+    /* handles the length enable bit of NRx4. enabling it during the first half of a length period
+    clocks the counter once more. returns true if that extra clock ran the counter out. */
+    pub fn enable(&mut self, on: bool) -> bool {
+        let was_on = self.on;
+        self.on = on;
+
+        if !was_on && self.on && self.first_half() && self.clock != 0 {
+            self.clock -= 1;
+            return self.clock == 0;
+        }
+        false
+    }
+
+    // reloads an empty counter, one less if the extra clocking applies.
     pub fn trigger(&mut self) {
         if self.clock == 0 {
             self.clock = self.max;
+            if self.on && self.first_half() {
+                self.clock -= 1;
+            }
         }
     }
 }
@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::CLOCK_FREQUENCY;
+
+// runtime controls applied when the four channels are mixed together.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone)]
+#[serde(default)]
+pub struct Mixer {
+    // master volume, from 0 to 1.
+    pub master: f32,
+    // gain of ch1 - ch4.
+    pub gain: [f32; 4],
+    pub mute: [bool; 4],
+    // when any channel is soloed, only soloed channels are heard.
+    pub solo: [bool; 4],
+    // removes the dc offset like the output capacitor of the DMG.
+    pub high_pass: bool,
+    // cutoff of the output low-pass filter in hz. 0 disables it.
+    pub low_pass: f32,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Self {
+            master: 1.0,
+            gain: [1.0; 4],
+            mute: [false; 4],
+            solo: [false; 4],
+            high_pass: true,
+            low_pass: 0.0,
+        }
+    }
+}
+
+impl Mixer {
+    // the effective gain of a channel after mute and solo.
+    pub fn channel(&self, channel: usize) -> f32 {
+        let soloed = self.solo.iter().any(|s| *s);
+        if self.mute[channel] || (soloed && !self.solo[channel]) {
+            0.0
+        } else {
+            self.gain[channel].max(0.0)
+        }
+    }
+}
+
+// filter state for one side of the output.
+#[derive(Copy, Clone, Default)]
+pub struct Filter {
+    capacitor: f32,
+    low: f32,
+}
+
+impl Filter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /* filters a buffer in place.
+    high-pass - the DMG capacitor charge factor is 0.999958 per clock, raised to the number of clocks per sample.
+    low-pass - a one pole filter at the given cutoff. */
+    pub fn apply(&mut self, buffer: &mut [f32], mixer: &Mixer, sample_rate: u32) {
+        let charge = 0.999958_f32.powf(CLOCK_FREQUENCY as f32 / sample_rate as f32);
+        let alpha = 1.0 - (-2.0 * std::f32::consts::PI * mixer.low_pass / sample_rate as f32).exp();
+        let master = mixer.master.clamp(0.0, 1.0);
+
+        for sample in buffer.iter_mut() {
+            let mut out = *sample;
+
+            if mixer.high_pass {
+                let high = out - self.capacitor;
+                self.capacitor = out - high * charge;
+                out = high;
+            }
+
+            if mixer.low_pass > 0.0 {
+                self.low += alpha * (out - self.low);
+                out = self.low;
+            }
+
+            *sample = out * master;
+        }
+    }
+}
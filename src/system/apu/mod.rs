@@ -1,89 +1,261 @@
-use std::sync::{Arc, Mutex};
+use std::{io, path::Path};
 
 use blip_buf::BlipBuf;
 use channel::Channel;
+use mixer::Filter;
 use noise::Noise;
+use recorder::Recorder;
+use serde::{Deserialize, Serialize};
 use square::Square;
 use timer::Timer;
 use wave::Wave;
 
-use crate::{emulator::CLOCK_FREQUENCY, player::SAMPLE_RATE};
+use crate::{
+    emulator::CLOCK_FREQUENCY,
+    player::SAMPLE_RATE,
+    ring::{ring, Consumer, Producer, RingStats},
+};
 
 mod channel;
+mod mixer;
 mod noise;
+mod recorder;
 mod square;
 mod timer;
 mod wave;
 
+pub use mixer::Mixer;
+
 const APU_FREQUENCY: u32 = CLOCK_FREQUENCY / 512;
-//#[derive(Copy, Clone, Debug)]
+
+// capacity of the ring buffer between the APU and the player, in stereo samples.
+const BUFFER_SIZE: usize = 1 << 16;
+
+/* bits of NR10 - NR52 that always read back as 1. unused addresses read as 0xff.
+https://gbdev.gg8.se/wiki/articles/Gameboy_sound_hardware#Register_Reading */
+const READ_MASK: [u8; 0x17] = [
+    0x80, 0x3f, 0x00, 0xff, 0xbf, // NR10 - NR14
+    0xff, 0x3f, 0x00, 0xff, 0xbf, // NR20 - NR24
+    0x7f, 0xff, 0x9f, 0xff, 0xbf, // NR30 - NR34
+    0xff, 0xff, 0x00, 0x00, 0xbf, // NR40 - NR44
+    0x00, 0x00, 0x70, // NR50 - NR52
+];
+
+// a stereo (left, right) sample.
+pub type Sample = (f32, f32);
+/* only the emulated hardware is part of save states. the output side - sample rate, buffer, recorder,
+mixer and filters - is skipped and kept from the running APU when a state is loaded. */
+#[derive(Serialize, Deserialize)]
 pub struct APU {
     on: bool,
     sequencer: Sequencer,
     panning: u8,
     volume_left: u8,
     volume_right: u8,
+    // the Vin bits of NR50, only kept to be read back.
+    vin: u8,
     timer: Timer,
     ch1: Square,
     ch2: Square,
     ch3: Wave,
     ch4: Noise,
-    pub buffer: Arc<Mutex<Vec<(f32, f32)>>>,
+    #[serde(skip)]
+    sample_rate: u32,
+    #[serde(skip, default = "unplugged")]
+    buffer: Producer<Sample>,
+    #[serde(skip)]
+    consumer: Option<Consumer<Sample>>,
+    #[serde(skip)]
+    recorder: Option<Recorder>,
+    #[serde(skip)]
+    mixer: Mixer,
+    #[serde(skip)]
+    filter_l: Filter,
+    #[serde(skip)]
+    filter_r: Filter,
 }
 
 impl APU {
     pub fn new() -> Self {
+        let (buffer, consumer) = ring(BUFFER_SIZE);
         Self {
             on: false,
             sequencer: Sequencer::new(),
             panning: 0,
             volume_left: 0,
             volume_right: 0,
+            vin: 0,
             timer: Timer::new(APU_FREQUENCY),
             ch1: Square::new(create_blipbuf(SAMPLE_RATE), true),
             ch2: Square::new(create_blipbuf(SAMPLE_RATE), false),
             ch3: Wave::new(create_blipbuf(SAMPLE_RATE)),
             ch4: Noise::new(create_blipbuf(SAMPLE_RATE)),
-            buffer: Arc::new(Mutex::new(Vec::new())),
+            sample_rate: SAMPLE_RATE,
+            buffer,
+            consumer: Some(consumer),
+            recorder: None,
+            mixer: Mixer::default(),
+            filter_l: Filter::new(),
+            filter_r: Filter::new(),
         }
     }
 
+    // takes over the output side of another APU, after this one was loaded from a save state.
+    pub fn keep_output(&mut self, other: &mut APU) {
+        std::mem::swap(&mut self.ch1.blip, &mut other.ch1.blip);
+        std::mem::swap(&mut self.ch2.blip, &mut other.ch2.blip);
+        std::mem::swap(&mut self.ch3.blip, &mut other.ch3.blip);
+        std::mem::swap(&mut self.ch4.blip, &mut other.ch4.blip);
+        self.ch1.record = other.ch1.record.take();
+        self.ch2.record = other.ch2.record.take();
+        self.ch3.record = other.ch3.record.take();
+        self.ch4.record = other.ch4.record.take();
+        self.ch1.from = other.ch1.from;
+        self.ch2.from = other.ch2.from;
+        self.ch3.from = other.ch3.from;
+        self.ch4.from = other.ch4.from;
+        self.sample_rate = other.sample_rate;
+        std::mem::swap(&mut self.buffer, &mut other.buffer);
+        self.consumer = other.consumer.take();
+        self.recorder = other.recorder.take();
+        self.mixer = other.mixer;
+        self.filter_l = other.filter_l;
+        self.filter_r = other.filter_r;
+    }
+
+    // replaces the blip buffers for a new sample rate, keeping the channel state.
     pub fn sample(&mut self, sample: u32) {
-        self.ch1 = Square::new(create_blipbuf(sample), true);
-        self.ch2 = Square::new(create_blipbuf(sample), false);
-        self.ch3 = Wave::new(create_blipbuf(sample));
-        self.ch4 = Noise::new(create_blipbuf(sample));
+        self.ch1.blip = create_blipbuf(sample);
+        self.ch2.blip = create_blipbuf(sample);
+        self.ch3.blip = create_blipbuf(sample);
+        self.ch4.blip = create_blipbuf(sample);
+        self.ch1.from = 0;
+        self.ch2.from = 0;
+        self.ch3.from = 0;
+        self.ch4.from = 0;
+        self.sample_rate = sample;
+    }
+
+    pub fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    // number of samples waiting to be played.
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    /* hands out the reading end of the audio buffer. there can only be one consumer, so once it is
+    taken a new buffer is made for the next player and the old one stops receiving samples. */
+    pub fn consumer(&mut self) -> Option<Consumer<Sample>> {
+        if self.consumer.is_none() {
+            let (buffer, consumer) = ring(BUFFER_SIZE);
+            self.buffer = buffer;
+            return Some(consumer);
+        }
+        self.consumer.take()
+    }
+
+    pub fn buffer_stats(&self) -> RingStats {
+        self.buffer.stats()
+    }
+
+    // an idle APU, paused or powered off, running the buffer empty is not an underrun.
+    pub fn set_idle(&self, idle: bool) {
+        self.buffer.set_idle(idle || !self.on());
+    }
+
+    /* dynamic rate control - scales the output rate of the blip buffers by a small ratio,
+    so that the amount of generated audio follows what the player actually consumes. */
+    pub fn adjust_rate(&mut self, ratio: f64) {
+        let rate = self.sample_rate as f64 * ratio;
+        self.ch1.blip.set_rates(CLOCK_FREQUENCY as f64, rate);
+        self.ch2.blip.set_rates(CLOCK_FREQUENCY as f64, rate);
+        self.ch3.blip.set_rates(CLOCK_FREQUENCY as f64, rate);
+        self.ch4.blip.set_rates(CLOCK_FREQUENCY as f64, rate);
+    }
+
+    pub fn set_mixer(&mut self, mixer: Mixer) {
+        self.mixer = mixer;
+    }
+
+    // starts writing the output to a WAV file, with each channel to its own stem file if stems is set.
+    pub fn start_recording(&mut self, path: &Path, stems: bool) -> io::Result<()> {
+        self.stop_recording()?;
+        self.recorder = Some(Recorder::new(path, stems, self.sample_rate)?);
+        self.ch1.record = Some(create_blipbuf(self.sample_rate));
+        self.ch2.record = Some(create_blipbuf(self.sample_rate));
+        self.ch3.record = Some(create_blipbuf(self.sample_rate));
+        self.ch4.record = Some(create_blipbuf(self.sample_rate));
+        Ok(())
+    }
+
+    pub fn stop_recording(&mut self) -> io::Result<()> {
+        self.ch1.record = None;
+        self.ch2.record = None;
+        self.ch3.record = None;
+        self.ch4.record = None;
+        match self.recorder.take() {
+            Some(mut recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+
+    // whether NR52 has the APU powered, it makes no samples while it is off.
+    pub fn on(&self) -> bool {
+        self.on
+    }
+
+    pub fn recording(&self) -> bool {
+        self.recorder.is_some()
     }
 
     pub fn read(&self, address: u16) -> u8 {
-        match address {
+        let value = match address {
             //0xff24 => { self.v }
             0xff10..=0xff14 => self.ch1.read(address),
             0xff16..=0xff19 => self.ch2.read(address),
             0xff1a..=0xff1e => self.ch3.read(address),
             0xff1f..=0xff23 => self.ch4.read(address),
-            0xff24 => ((self.volume_right & 0x7) << 4) | (self.volume_left & 0x7),
+            0xff24 => self.vin | ((self.volume_left & 0x7) << 4) | (self.volume_right & 0x7),
             0xff25 => self.panning,
             0xff26 => {
                 (self.on as u8) << 7
-                    | 0x70
                     | (self.ch4.on() as u8) << 3
                     | (self.ch3.on() as u8) << 2
                     | (self.ch2.on() as u8) << 1
                     | (self.ch1.on() as u8)
             }
-            0xff30..=0xff3f => self.ch3.read(address),
-            _ => 0xFF,
-        }
+            0xff30..=0xff3f => return self.ch3.read(address),
+            _ => return 0xFF,
+        };
+
+        value | READ_MASK[(address - 0xff10) as usize]
     }
 
     pub fn write(&mut self, value: u8, address: u16) {
+        // while off, only NR52, wave RAM and (on the DMG) the length counters can be written.
+        if !self.on {
+            match address {
+                0xff11 => self.ch1.write_length(value),
+                0xff16 => self.ch2.write_length(value),
+                0xff1b => self.ch3.write_length(value),
+                0xff20 => self.ch4.write_length(value),
+                0xff26 | 0xff30..=0xff3f => (),
+                _ => return,
+            }
+            if address < 0xff26 {
+                return;
+            }
+        }
+
         match address {
             0xff10..=0xff14 => self.ch1.write(value, address),
             0xff16..=0xff19 => self.ch2.write(value, address),
             0xff1a..=0xff1e => self.ch3.write(value, address),
             0xff1f..=0xff23 => self.ch4.write(value, address),
             0xff24 => {
+                self.vin = value & 0x88;
                 self.volume_left = (value >> 4) & 0x7;
                 self.volume_right = value & 0x7;
             }
@@ -93,13 +265,51 @@ impl APU {
             }
 
             0xff26 => {
-                self.on = ((value >> 7) & 0b1) != 0;
+                let on = ((value >> 7) & 0b1) != 0;
+                if self.on && !on {
+                    self.power_off();
+                } else if !self.on && on {
+                    self.power_on();
+                }
+                self.on = on;
             }
             0xff30..=0xff3f => self.ch3.write(value, address),
             _ => (), //panic!("Invalid write for APU"),
         }
     }
 
+    // turning the APU off clears every register from NR10 to NR51.
+    fn power_off(&mut self) {
+        self.ch1.power_off();
+        self.ch2.power_off();
+        self.ch3.power_off();
+        self.ch4.power_off();
+        self.volume_left = 0;
+        self.volume_right = 0;
+        self.vin = 0;
+        self.panning = 0;
+    }
+
+    // turning it on resets the frame sequencer, so that the next step is 0.
+    fn power_on(&mut self) {
+        self.sequencer = Sequencer::new();
+        self.timer.n = 0;
+        self.ch1.from = 0;
+        self.ch2.from = 0;
+        self.ch3.from = 0;
+        self.ch4.from = 0;
+        self.set_frame();
+    }
+
+    // tells the length counters where the frame sequencer is.
+    fn set_frame(&mut self) {
+        let frame = self.sequencer.step;
+        self.ch1.length.frame = frame;
+        self.ch2.length.frame = frame;
+        self.ch3.length.frame = frame;
+        self.ch4.length.frame = frame;
+    }
+
     pub fn step(&mut self, m_cycles: u8) {
         if !self.on {
             return;
@@ -107,30 +317,31 @@ impl APU {
 
         let cycles = m_cycles as u32 * 4;
 
-        for _ in 0..self.timer.step(cycles) {
-            self.ch1.step(self.timer.period);
-            self.ch2.step(self.timer.period);
-            self.ch3.step(self.timer.period);
-            self.ch4.step(self.timer.period);
+        // the channels are stepped every instruction so their state is visible to the CPU in time.
+        self.ch1.step(cycles);
+        self.ch2.step(cycles);
+        self.ch3.step(cycles);
+        self.ch4.step(cycles);
 
+        for _ in 0..self.timer.step(cycles) {
             let step = self.sequencer.step();
 
             match step {
                 0 | 4 => {
                     // length counter step
-                    self.ch1.length.step();
-                    self.ch2.length.step();
-                    self.ch3.length.step();
-                    self.ch4.length.step();
+                    self.ch1.length_step();
+                    self.ch2.length_step();
+                    self.ch3.length_step();
+                    self.ch4.length_step();
                 }
 
                 2 | 6 => {
                     // sweep and length counter step
                     self.ch1.sweep_step();
-                    self.ch1.length.step();
-                    self.ch2.length.step();
-                    self.ch3.length.step();
-                    self.ch4.length.step();
+                    self.ch1.length_step();
+                    self.ch2.length_step();
+                    self.ch3.length_step();
+                    self.ch4.length_step();
                 }
 
                 7 => {
@@ -142,10 +353,23 @@ impl APU {
                 _ => (),
             }
 
+            self.set_frame();
+
             self.ch1.blip.end_frame(self.timer.period);
             self.ch2.blip.end_frame(self.timer.period);
             self.ch3.blip.end_frame(self.timer.period);
             self.ch4.blip.end_frame(self.timer.period);
+            for record in [
+                &mut self.ch1.record,
+                &mut self.ch2.record,
+                &mut self.ch3.record,
+                &mut self.ch4.record,
+            ]
+            .into_iter()
+            .flatten()
+            {
+                record.end_frame(self.timer.period);
+            }
 
             self.ch1.from = self.ch1.from.wrapping_sub(self.timer.period);
             self.ch2.from = self.ch2.from.wrapping_sub(self.timer.period);
@@ -158,15 +382,9 @@ impl APU {
 
     fn play(&mut self, l: &[f32], r: &[f32]) {
         assert_eq!(l.len(), r.len());
-        // pushes generated audio into the audio_buffer.
-        let mut buffer = self.buffer.lock().unwrap();
+        // pushes generated audio into the audio_buffer. samples are dropped (and counted) once it is full.
         for (l, r) in l.iter().zip(r) {
-            // Do not fill the buffer with more than 1 second of data
-            // This speeds up the resync after the turning on and off the speed limiter
-            /*if buffer.len() > SAMPLE_RATE as usize {
-                return;
-            }*/
-            buffer.push((*l, *r));
+            self.buffer.push((*l, *r));
         }
     }
 
@@ -180,79 +398,137 @@ impl APU {
         assert_eq!(sc2, sc3);
         assert_eq!(sc3, sc4);
 
-        let sample_count = sc1 as usize;
+        let buf_l = &mut [0f32; 2048];
+        let buf_r = &mut [0f32; 2048];
 
         let mut sum = 0;
+        while sum < sc1 as usize {
+            let count = self.mix_block(false, buf_l, buf_r);
+            self.play(&buf_l[..count], &buf_r[..count]);
+            sum += count;
+        }
 
-        let left_vol = (self.volume_left as f32 / 7.0) * (1.0 / 15.0) * 0.25;
-        let right_vol = (self.volume_right as f32 / 7.0) * (1.0 / 15.0) * 0.25;
-
-        while sum < sample_count {
-            let buf_l = &mut [0f32; 2048];
-            let buf_r = &mut [0f32; 2048];
-            let buf = &mut [0i16; 2048];
-
-            let count1 = self.ch1.blip.read_samples(buf, false);
-            for (i, v) in buf[..count1].iter().enumerate() {
-                if self.panning & 0x10 == 0x10 {
-                    buf_l[i] += *v as f32 * left_vol;
-                }
-                if self.panning & 0x01 == 0x01 {
-                    buf_r[i] += *v as f32 * right_vol;
+        // the recording reads its own buffers, which are at the fixed rate of the file.
+        while self
+            .ch1
+            .record
+            .as_ref()
+            .is_some_and(|b| b.samples_avail() > 0)
+        {
+            let count = self.mix_block(true, buf_l, buf_r);
+            if let Some(recorder) = self.recorder.as_mut() {
+                // a failed write stops the recording instead of the emulator.
+                if let Err(e) = recorder.mix(&buf_l[..count], &buf_r[..count]) {
+                    println!("Recording stopped: {}", e);
+                    let _ = self.stop_recording();
                 }
             }
+        }
+    }
 
-            let count2 = self.ch2.blip.read_samples(buf, false);
-            for (i, v) in buf[..count2].iter().enumerate() {
-                if self.panning & 0x20 == 0x20 {
-                    buf_l[i] += *v as f32 * left_vol;
-                }
-                if self.panning & 0x02 == 0x02 {
-                    buf_r[i] += *v as f32 * right_vol;
-                }
-            }
+    /* reads a block of samples from the blip buffers of playback, or of the recording, and mixes
+    them into buf_l and buf_r. the recording also gets each channel as a stem, before panning. */
+    fn mix_block(
+        &mut self,
+        record: bool,
+        buf_l: &mut [f32; 2048],
+        buf_r: &mut [f32; 2048],
+    ) -> usize {
+        let left_vol = (self.volume_left as f32 / 7.0) * (1.0 / 15.0) * 0.25;
+        let right_vol = (self.volume_right as f32 / 7.0) * (1.0 / 15.0) * 0.25;
+
+        // gain of each channel after mute and solo.
+        let gain = [
+            self.mixer.channel(0),
+            self.mixer.channel(1),
+            self.mixer.channel(2),
+            self.mixer.channel(3),
+        ];
+
+        let blips = if record {
+            [
+                &mut self.ch1.record,
+                &mut self.ch2.record,
+                &mut self.ch3.record,
+                &mut self.ch4.record,
+            ]
+            .map(|b| b.as_mut().unwrap())
+        } else {
+            [
+                &mut self.ch1.blip,
+                &mut self.ch2.blip,
+                &mut self.ch3.blip,
+                &mut self.ch4.blip,
+            ]
+        };
+
+        buf_l.fill(0.0);
+        buf_r.fill(0.0);
+        let buf = &mut [0i16; 2048];
+        let mut counts = [0; 4];
+        // result of writing the stems, if recording.
+        let mut recorded = Ok(());
+
+        for (i, blip) in blips.into_iter().enumerate() {
+            let count = blip.read_samples(buf, false);
+            counts[i] = count;
 
             // channel3 is the WaveChannel, that outputs samples with a 4x
             // increase in amplitude in order to avoid a loss of precision.
-            let count3 = self.ch3.blip.read_samples(buf, false);
-            for (i, v) in buf[..count3].iter().enumerate() {
-                if self.panning & 0x40 == 0x40 {
-                    buf_l[i] += *v as f32 * left_vol / 4.0;
-                }
-                if self.panning & 0x04 == 0x04 {
-                    buf_r[i] += *v as f32 * right_vol / 4.0;
-                }
+            let scale = if i == 2 { 4 } else { 1 };
+            if let (true, Some(recorder)) = (record, self.recorder.as_mut()) {
+                let stem: Vec<i16> = buf[..count].iter().map(|v| v / scale).collect();
+                recorded = recorded.and(recorder.stem(i, &stem));
             }
 
-            let count4 = self.ch4.blip.read_samples(buf, false);
-            for (i, v) in buf[..count4].iter().enumerate() {
-                if self.panning & 0x80 == 0x80 {
-                    buf_l[i] += *v as f32 * left_vol;
+            // bits of NR51 that send the channel to the left and right.
+            for (j, v) in buf[..count].iter().enumerate() {
+                let v = *v as f32 * gain[i] / scale as f32;
+                if self.panning & (0x10 << i) != 0 {
+                    buf_l[j] += v * left_vol;
                 }
-                if self.panning & 0x08 == 0x08 {
-                    buf_r[i] += *v as f32 * right_vol;
+                if self.panning & (0x01 << i) != 0 {
+                    buf_r[j] += v * right_vol;
                 }
             }
+        }
 
-            debug_assert!(count1 == count2);
-            debug_assert!(count1 == count3);
-            debug_assert!(count1 == count4);
-
-            self.play(&buf_l[..count1], &buf_r[..count1]);
-
-            sum += count1;
+        debug_assert!(counts.iter().all(|&c| c == counts[0]));
+        let count = counts[0];
+
+        if record {
+            let recorder = self.recorder.as_mut().unwrap();
+            let rate = recorder.sample_rate;
+            recorder
+                .filter_l
+                .apply(&mut buf_l[..count], &self.mixer, rate);
+            recorder
+                .filter_r
+                .apply(&mut buf_r[..count], &self.mixer, rate);
+            if let Err(e) = recorded {
+                println!("Recording stopped: {}", e);
+                let _ = self.stop_recording();
+            }
+        } else {
+            self.filter_l
+                .apply(&mut buf_l[..count], &self.mixer, self.sample_rate);
+            self.filter_r
+                .apply(&mut buf_r[..count], &self.mixer, self.sample_rate);
         }
+        count
     }
 }
 
 // frame sequencer
+#[derive(Serialize, Deserialize)]
 struct Sequencer {
     step: u8,
 }
 
 impl Sequencer {
+    // starts at 7 so that the first step after power on is 0.
     pub fn new() -> Self {
-        Self { step: 0 }
+        Self { step: 7 }
     }
 
     pub fn step(&mut self) -> u8 {
@@ -264,6 +540,15 @@ impl Sequencer {
     }
 }
 
+// placeholders for the skipped output fields of a deserialized APU.
+fn blip() -> BlipBuf {
+    create_blipbuf(SAMPLE_RATE)
+}
+
+fn unplugged() -> Producer<Sample> {
+    ring(1).0
+}
+
 pub fn create_blipbuf(sample: u32) -> BlipBuf {
     let mut blipbuf = BlipBuf::new(sample);
     blipbuf.set_rates(CLOCK_FREQUENCY as f64, sample as f64);
@@ -275,3 +560,86 @@ pub trait AudioPlayer: Send {
     fn samples_rate(&self) -> u32;
     fn underflowed(&self) -> bool;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sha1::{Digest, Sha1};
+
+    // a chord on all four channels, with sweep, envelopes, a wave ramp and noise.
+    const WRITES: [(u16, u8); 17] = [
+        (0xff26, 0x80),
+        (0xff24, 0x77),
+        (0xff25, 0xff),
+        (0xff10, 0x16),
+        (0xff11, 0x80),
+        (0xff12, 0xf3),
+        (0xff14, 0x87),
+        (0xff16, 0x40),
+        (0xff17, 0xf0),
+        (0xff18, 0x80),
+        (0xff19, 0x86),
+        (0xff1a, 0x80),
+        (0xff1c, 0x20),
+        (0xff1e, 0x87),
+        (0xff21, 0xf1),
+        (0xff22, 0x45),
+        (0xff23, 0x80),
+    ];
+
+    /* records a second of the chord and hashes the stems. the mix goes through floating point
+    filters, so only the 16 bit stems are compared. the files are named after the test, as the
+    tests run at the same time. */
+    fn stems(test: &str, adjust: f64) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!(
+            "kirboy-apu-{}-{}-{}.wav",
+            std::process::id(),
+            test,
+            adjust
+        ));
+        let mut apu = APU::new();
+        for (i, address) in (0xff30..=0xff3f).enumerate() {
+            apu.write((i as u8) << 4 | i as u8, address);
+        }
+        for (address, value) in WRITES {
+            apu.write(value, address);
+        }
+        apu.adjust_rate(adjust);
+        apu.start_recording(&path, true).unwrap();
+        for _ in 0..CLOCK_FREQUENCY / 4 / 4 {
+            apu.step(4);
+        }
+        apu.stop_recording().unwrap();
+
+        let _ = std::fs::remove_file(&path);
+        let name = path.file_stem().unwrap().to_string_lossy().to_string();
+        ["square1", "square2", "wave", "noise"]
+            .iter()
+            .map(|stem| {
+                let stem = path.with_file_name(format!("{}-{}.wav", name, stem));
+                let hash = format!("{:x}", Sha1::digest(std::fs::read(&stem).unwrap()));
+                let _ = std::fs::remove_file(stem);
+                hash
+            })
+            .collect()
+    }
+
+    #[test]
+    fn recording_hashes() {
+        assert_eq!(
+            stems("hashes", 1.0),
+            [
+                "f9bc942b22b2014e7d0a0bc9dc5c7342b1fbf91f",
+                "8e6c7525326c4fe2e25b26f0d271b9e9490aaf5c",
+                "a6a228e65676c9cf5fa259985b03bf65c20eb972",
+                "f5a23e655fb7fe6d3ef2e7aad427288c83cf9dbd",
+            ]
+        );
+    }
+
+    // the rate control of playback does not reach the recording.
+    #[test]
+    fn recording_ignores_rate_control() {
+        assert_eq!(stems("rate", 1.0), stems("rate", 1.005));
+    }
+}
@@ -1,10 +1,12 @@
 use blip_buf::BlipBuf;
+use serde::{Deserialize, Serialize};
 
 use super::{
     channel::{Channel, Envelope, Length},
     timer::Timer,
 };
 
+#[derive(Serialize, Deserialize)]
 pub struct Noise {
     on: bool,
     dac: bool,
@@ -12,10 +14,18 @@ pub struct Noise {
     pub envelope: Envelope,
     divisor_code: u8,
     shift: u8,
+    // NR43 as written, for reading it back.
+    polynomial: u8,
     lfsr: LFSR,
     timer: Timer,
+    // position in the blip buffer, kept by the running APU when a state is loaded.
+    #[serde(skip)]
     pub from: u32,
+    #[serde(skip, default = "super::blip")]
     pub blip: BlipBuf,
+    // a second buffer at the fixed rate of the recording, untouched by the playback rate control.
+    #[serde(skip)]
+    pub record: Option<BlipBuf>,
     ampl: i32,
 }
 
@@ -28,9 +38,11 @@ impl Noise {
             envelope: Envelope::new(),
             divisor_code: 7,
             shift: 0,
+            polynomial: 0,
             lfsr: LFSR::new(),
             timer: Timer::new(2048),
             from: 0,
+            record: None,
             blip,
             ampl: 0,
         }
@@ -55,7 +67,7 @@ impl Channel for Noise {
         match address {
             0xff20 => 0xff,
             0xff21 => self.envelope.read(),
-            //0xff22 =>
+            0xff22 => self.polynomial,
             0xff23 => 0x80 | if self.length.on { 0x40 } else { 0 } | 0x3f,
             _ => 0xff, //panic!("Invalid read for Noise"),
         }
@@ -71,14 +83,16 @@ impl Channel for Noise {
 
             //nrx2
             0xff21 => {
+                self.envelope.write(value, self.on);
+
                 self.dac = value & 0xf8 != 0;
                 self.on &= self.dac;
-
-                self.envelope.write(value)
             }
 
             //nrx3
             0xff22 => {
+                self.polynomial = value;
+
                 self.divisor_code = value & 0x7;
 
                 self.lfsr.set(value);
@@ -89,12 +103,17 @@ impl Channel for Noise {
             }
 
             0xff23 => {
-                self.length.on = value & 0x40 == 0x40;
+                let trigger = value & 0x80 == 0x80;
+
+                if self.length.enable(value & 0x40 == 0x40) && !trigger {
+                    self.on = false;
+                }
 
-                if value & 0x80 == 0x80 {
+                if trigger {
                     self.length.trigger();
                     self.envelope.trigger();
                     self.lfsr.trigger();
+                    self.timer.n = 0;
 
                     if self.dac {
                         self.on = true
@@ -112,7 +131,6 @@ impl Channel for Noise {
 
     fn step(&mut self, t_cycles: u32) {
         for _ in 0..self.timer.step(t_cycles) {
-            self.on &= self.length.active();
             let ampl = if !self.on {
                 0x00
             } else if self.lfsr.step() {
@@ -128,11 +146,40 @@ impl Channel for Noise {
             let d = ampl - self.ampl;
             self.ampl = ampl;
             self.blip.add_delta(self.from, d);
+            if let Some(record) = self.record.as_mut() {
+                record.add_delta(self.from, d);
+            }
         }
     }
+
+    fn length_step(&mut self) {
+        if self.length.step() {
+            self.on = false;
+        }
+    }
+
+    fn power_off(&mut self) {
+        self.on = false;
+        self.dac = false;
+        self.envelope = Envelope::new();
+        self.divisor_code = 0;
+        self.shift = 0;
+        self.polynomial = 0;
+        self.lfsr.set(0);
+        self.timer.period = self.period();
+        self.length.on = false;
+    }
+}
+
+impl Noise {
+    // only the length can be written while the APU is off.
+    pub fn write_length(&mut self, value: u8) {
+        self.length.set(value as u16 & 0x3f);
+    }
 }
 
 // LFSR Register - For Psuedorandom Noise Generation
+#[derive(Serialize, Deserialize)]
 struct LFSR {
     lfsr: u16,
     shift: u8,
@@ -0,0 +1,73 @@
+use std::{io, path::Path};
+
+use super::mixer::Filter;
+use crate::wav::{WavFormat, WavWriter};
+
+// names used for the per-channel stem files.
+const STEMS: [&str; 4] = ["square1", "square2", "wave", "noise"];
+
+/* records the mixed output of the APU as a stereo float WAV file,
+and optionally each channel before panning and volume as a separate 16 bit mono stem.
+the recording has its own rate and filters, so the rate control of playback does not reach it. */
+pub struct Recorder {
+    mix: WavWriter,
+    stems: Option<[WavWriter; 4]>,
+    pub sample_rate: u32,
+    pub filter_l: Filter,
+    pub filter_r: Filter,
+}
+
+impl Recorder {
+    pub fn new(path: &Path, stems: bool, sample_rate: u32) -> io::Result<Self> {
+        let mix = WavWriter::create(path, WavFormat::Float32, 2, sample_rate)?;
+
+        let stems = if stems {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            let stem = |i: usize| {
+                WavWriter::create(
+                    &path.with_file_name(format!("{}-{}.wav", name, STEMS[i])),
+                    WavFormat::Pcm16,
+                    1,
+                    sample_rate,
+                )
+            };
+            Some([stem(0)?, stem(1)?, stem(2)?, stem(3)?])
+        } else {
+            None
+        };
+
+        Ok(Self {
+            mix,
+            stems,
+            sample_rate,
+            filter_l: Filter::new(),
+            filter_r: Filter::new(),
+        })
+    }
+
+    pub fn mix(&mut self, l: &[f32], r: &[f32]) -> io::Result<()> {
+        let mut interleaved = Vec::with_capacity(l.len() * 2);
+        for (l, r) in l.iter().zip(r) {
+            interleaved.push(*l);
+            interleaved.push(*r);
+        }
+        self.mix.write_f32(&interleaved)
+    }
+
+    pub fn stem(&mut self, channel: usize, samples: &[i16]) -> io::Result<()> {
+        match self.stems.as_mut() {
+            Some(stems) => stems[channel].write_i16(samples),
+            None => Ok(()),
+        }
+    }
+
+    pub fn finish(&mut self) -> io::Result<()> {
+        self.mix.finish()?;
+        if let Some(stems) = self.stems.as_mut() {
+            for stem in stems.iter_mut() {
+                stem.finish()?;
+            }
+        }
+        Ok(())
+    }
+}
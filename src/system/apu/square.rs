@@ -1,10 +1,12 @@
 use blip_buf::BlipBuf;
+use serde::{Deserialize, Serialize};
 
 use super::{
     channel::{Channel, Envelope, Length},
     timer::Timer,
 };
 
+#[derive(Serialize, Deserialize)]
 pub struct Square {
     on: bool,
     dac: bool,
@@ -17,7 +19,13 @@ pub struct Square {
     pub length: Length,
     pub envelope: Envelope,
     has_sweep: bool,
+    #[serde(skip, default = "super::blip")]
     pub blip: BlipBuf,
+    // a second buffer at the fixed rate of the recording, untouched by the playback rate control.
+    #[serde(skip)]
+    pub record: Option<BlipBuf>,
+    // position in the blip buffer, kept by the running APU when a state is loaded.
+    #[serde(skip)]
     pub from: u32,
     ampl: i32,
 }
@@ -37,6 +45,7 @@ impl Square {
             has_sweep,
             blip,
             from: 0,
+            record: None,
             ampl: 0,
         }
     }
@@ -59,6 +68,8 @@ impl Square {
         let d = self.sweep.frequency >> self.sweep.shift;
 
         let new_frequency = if self.sweep.direction {
+            // remembered for the negate mode quirk.
+            self.sweep.negated = true;
             self.sweep.frequency - d
         } else {
             self.sweep.frequency + d
@@ -81,11 +92,11 @@ impl Square {
                 if self.sweep.on {
                     let new_frequency = self.sweep_calc_frequency();
 
-                    if new_frequency <= 2047 {
-                        if self.sweep.shift != 0 {
-                            self.frequency = new_frequency;
-                            self.sweep.frequency = new_frequency;
-                        }
+                    // the new frequency is written back and checked for overflow a second time.
+                    if new_frequency <= 2047 && self.sweep.shift != 0 {
+                        self.frequency = new_frequency;
+                        self.sweep.frequency = new_frequency;
+                        self.timer.period = self.period();
                         self.sweep_calc_frequency();
                     }
                 }
@@ -97,6 +108,7 @@ impl Square {
 
     fn sweep_trigger(&mut self) {
         self.sweep.frequency = self.frequency;
+        self.sweep.negated = false;
         self.sweep.clock = if self.sweep.period > 0 {
             self.sweep.period
         } else {
@@ -122,7 +134,7 @@ impl Channel for Square {
 
             0xFF14 | 0xFF19 => 0x80 | if self.length.on { 0x40 } else { 0 } | 0x3F,
 
-            _ => panic!("Invalid read for Square"),
+            _ => 0xFF,
         }
     }
 
@@ -130,7 +142,12 @@ impl Channel for Square {
         match address {
             // nrx0
             0xff10 => {
+                // clearing negate mode after it was used in a calculation disables the channel.
+                let negated = self.sweep.direction && self.sweep.negated;
                 self.sweep.write(value);
+                if negated && !self.sweep.direction {
+                    self.on = false;
+                }
             }
             // nrx1
             0xff11 | 0xff16 => {
@@ -142,8 +159,9 @@ impl Channel for Square {
 
             // nrx2
             0xff12 | 0xff17 => {
+                self.envelope.write(value, self.on);
                 self.dac = value & 0xf8 != 0;
-                self.envelope.write(value);
+                self.on &= self.dac;
             }
 
             // nrx3
@@ -157,18 +175,23 @@ impl Channel for Square {
                 self.frequency = (self.frequency & 0xff) | ((value as u16 & 0x07) << 8);
                 self.timer.period = self.period();
 
-                self.length.on = value & 0x40 == 0x40;
+                let trigger = value & 0x80 == 0x80;
 
-                self.on &= self.length.active();
+                if self.length.enable(value & 0x40 == 0x40) && !trigger {
+                    self.on = false;
+                }
 
                 // if set
-                if value & 0x80 == 0x80 {
+                if trigger {
                     if self.dac {
                         self.on = true;
                     }
 
                     self.length.trigger();
 
+                    // the frequency timer is reloaded with the period.
+                    self.timer.n = 0;
+
                     if self.has_sweep {
                         self.sweep_trigger();
                     }
@@ -180,16 +203,16 @@ impl Channel for Square {
 
                 //self.sweep_trigger();
             }
-            _ => panic!("Invalid write for Square"),
+            _ => (),
         }
     }
+
     fn on(&self) -> bool {
         self.on
     }
 
     fn step(&mut self, t_cycles: u32) {
         for _ in 0..self.timer.step(t_cycles) {
-            self.on &= self.length.active();
             let ampl = if !self.on {
                 0x00
             } else if self.duty_phase() {
@@ -203,12 +226,41 @@ impl Channel for Square {
             let d = ampl - self.ampl;
             self.ampl = ampl;
             self.blip.add_delta(self.from, d);
+            if let Some(record) = self.record.as_mut() {
+                record.add_delta(self.from, d);
+            }
 
             self.duty_step = (self.duty_step + 1) % 8;
         }
     }
+
+    fn length_step(&mut self) {
+        if self.length.step() {
+            self.on = false;
+        }
+    }
+
+    fn power_off(&mut self) {
+        self.on = false;
+        self.dac = false;
+        self.duty = 0;
+        self.frequency = 0;
+        self.timer.period = self.period();
+        self.duty_step = 0;
+        self.sweep = Sweep::new();
+        self.envelope = Envelope::new();
+        self.length.on = false;
+    }
+}
+
+impl Square {
+    // only the length can be written while the APU is off.
+    pub fn write_length(&mut self, value: u8) {
+        self.length.set(value as u16 & 0x3F);
+    }
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Sweep {
     pub period: u8,
     // 0 = addition, 1 = subtraction
@@ -217,6 +269,8 @@ pub struct Sweep {
     pub on: bool,
     pub clock: u8,
     pub frequency: u16,
+    // set once a calculation used negate mode since the last trigger.
+    pub negated: bool,
 }
 
 // Frequency Sweep Module
@@ -229,6 +283,7 @@ impl Sweep {
             on: false,
             clock: 0,
             frequency: 0,
+            negated: false,
         }
     }
 
@@ -1,10 +1,17 @@
 use blip_buf::BlipBuf;
+use serde::{Deserialize, Serialize};
 
 use super::{
     channel::{Channel, Length},
     timer::Timer,
 };
 
+/* t-cycles after a sample fetch in which the CPU can still reach wave RAM while the channel plays.
+an approximation, the APU is stepped after each instruction, so the access is not timed to the
+cycle. see the dmg_sound test in blargg.rs. */
+const WAVE_ACCESS_WINDOW: u32 = 4;
+
+#[derive(Serialize, Deserialize)]
 pub struct Wave {
     pub length: Length,
     dac: bool,
@@ -15,8 +22,16 @@ pub struct Wave {
     frequency: u16,
     ampl: i32,
     wave_index: usize,
+    // last sample read from wave RAM.
+    sample: u8,
+    // position in the blip buffer, kept by the running APU when a state is loaded.
+    #[serde(skip)]
     pub from: u32,
+    #[serde(skip, default = "super::blip")]
     pub blip: BlipBuf,
+    // a second buffer at the fixed rate of the recording, untouched by the playback rate control.
+    #[serde(skip)]
+    pub record: Option<BlipBuf>,
 }
 
 impl Wave {
@@ -31,7 +46,9 @@ impl Wave {
             frequency: 0,
             ampl: 0,
             wave_index: 0,
+            sample: 0,
             from: 0,
+            record: None,
             blip,
         }
     }
@@ -39,6 +56,55 @@ impl Wave {
     fn period(&self) -> u32 {
         (2048 - self.frequency as u32) * 2
     }
+
+    // true right after the channel fetched a byte from wave RAM.
+    fn fetching(&self) -> bool {
+        self.timer.n < WAVE_ACCESS_WINDOW
+    }
+
+    /* while the channel plays, the CPU can only access the byte the channel is reading,
+    and on the DMG only in the same cycle the channel reads it. otherwise reads give 0xff. */
+    fn read_wave_ram(&self, address: u16) -> u8 {
+        if !self.on {
+            self.wave_ram[address as usize & 0xF]
+        } else if self.fetching() {
+            self.wave_ram[self.wave_index >> 1]
+        } else {
+            0xff
+        }
+    }
+
+    fn write_wave_ram(&mut self, value: u8, address: u16) {
+        if !self.on {
+            self.wave_ram[address as usize & 0xF] = value;
+        } else if self.fetching() {
+            self.wave_ram[self.wave_index >> 1] = value;
+        }
+    }
+
+    /* retriggering on the DMG while the channel is about to read corrupts wave RAM.
+    if the next byte is in the first 4, only byte 0 is overwritten by it, otherwise the first 4 bytes
+    are overwritten with the aligned 4 byte block containing it. */
+    fn corrupt(&mut self) {
+        if !self.on || self.timer.period - self.timer.n > 2 {
+            return;
+        }
+
+        let position = ((self.wave_index + 1) % 32) >> 1;
+        if position < 4 {
+            self.wave_ram[0] = self.wave_ram[position];
+        } else {
+            let block = position & !0x3;
+            for i in 0..4 {
+                self.wave_ram[i] = self.wave_ram[block + i];
+            }
+        }
+    }
+
+    // only the length can be written while the APU is off.
+    pub fn write_length(&mut self, value: u8) {
+        self.length.set(value as u16);
+    }
 }
 
 impl Channel for Wave {
@@ -54,9 +120,9 @@ impl Channel for Wave {
 
             0xff1e => 0x80 | if self.length.on { 0x40 } else { 0 } | 0x3F,
 
-            0xff30..=0xff3f => self.wave_ram[address as usize & 0xF],
+            0xff30..=0xff3f => self.read_wave_ram(address),
 
-            _ => panic!("Invalid read for Wave"),
+            _ => 0xFF,
         }
     }
 
@@ -89,26 +155,33 @@ impl Channel for Wave {
             0xff1e => {
                 self.frequency = (self.frequency & 0xff) | ((value as u16 & 0x07) << 8);
 
-                self.length.on = value & 0x40 == 0x40;
+                self.timer.period = self.period();
 
-                self.on &= self.length.active();
+                let trigger = value & 0x80 == 0x80;
 
-                self.timer.period = self.period();
+                if self.length.enable(value & 0x40 == 0x40) && !trigger {
+                    self.on = false;
+                }
 
                 // if set
-                if value & 0x80 == 0x80 {
+                if trigger {
+                    self.corrupt();
+
                     if self.dac {
                         self.on = true;
                     }
 
                     self.length.trigger();
+
+                    // playback restarts from the first sample, with the timer reloaded.
+                    self.wave_index = 0;
+                    self.timer.n = 0;
                 }
             }
 
-            0xff30..=0xff3f => {
-                self.wave_ram[address as usize & 0xF] = value;
-            }
-            _ => panic!("Invalid write for Wave"),
+            0xff30..=0xff3f => self.write_wave_ram(value, address),
+
+            _ => (),
         }
     }
 
@@ -122,15 +195,17 @@ impl Channel for Wave {
         };
 
         for _ in 0..self.timer.step(t_cycles) {
-            self.on &= self.length.active();
-            let sample = if self.wave_index & 0x1 == 0 {
-                self.wave_ram[self.wave_index >> 1] & 0xf
-            } else {
+            // the position advances before the sample is read, so the first sample after a trigger is 1.
+            self.wave_index = (self.wave_index + 1) % 32;
+
+            self.sample = if self.wave_index & 0x1 == 0 {
                 self.wave_ram[self.wave_index >> 1] >> 4
+            } else {
+                self.wave_ram[self.wave_index >> 1] & 0xf
             };
 
             let ampl = if self.on && self.dac {
-                ((sample << 2) >> volume) as i32
+                ((self.sample << 2) >> volume) as i32
             } else {
                 0x00
             };
@@ -144,8 +219,25 @@ impl Channel for Wave {
             let d = ampl - self.ampl;
             self.ampl = ampl;
             self.blip.add_delta(self.from, d);
+            if let Some(record) = self.record.as_mut() {
+                record.add_delta(self.from, d);
+            }
+        }
+    }
 
-            self.wave_index = (self.wave_index + 1) % 32;
+    fn length_step(&mut self) {
+        if self.length.step() {
+            self.on = false;
         }
     }
+
+    fn power_off(&mut self) {
+        self.on = false;
+        self.dac = false;
+        self.volume = 0;
+        self.frequency = 0;
+        self.timer.period = self.period();
+        self.sample = 0;
+        self.length.on = false;
+    }
 }
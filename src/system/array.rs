@@ -0,0 +1,23 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+/* serde only derives arrays of up to 32 elements. used with #[serde(with = "array")]
+for the memory arrays, which are stored as sequences. */
+pub fn serialize<S, T, const N: usize>(array: &[T; N], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+    T: Serialize,
+{
+    serializer.collect_seq(array.iter())
+}
+
+pub fn deserialize<'de, D, T, const N: usize>(deserializer: D) -> Result<[T; N], D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    let values = Vec::<T>::deserialize(deserializer)?;
+    let length = values.len();
+    values
+        .try_into()
+        .map_err(|_| D::Error::invalid_length(length, &format!("an array of {}", N).as_str()))
+}
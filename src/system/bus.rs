@@ -0,0 +1,170 @@
+use crate::system::mmu::MMU;
+use std::cell::{Cell, RefCell};
+
+/* the memory map the CPU runs against. the MMU is the one of the Game Boy, test harnesses and
+players bring their own. words are little endian, like on the Game Boy. */
+pub trait Bus {
+    fn read_byte(&self, address: u16) -> u8;
+
+    fn write_byte(&mut self, value: u8, address: u16);
+
+    fn read_word(&self, address: u16) -> u16 {
+        (self.read_byte(address) as u16) | ((self.read_byte(address.wrapping_add(1)) as u16) << 8)
+    }
+
+    fn write_word(&mut self, value: u16, address: u16) {
+        self.write_byte((value & 0x00ff) as u8, address);
+        self.write_byte((value >> 8) as u8, address.wrapping_add(1));
+    }
+
+    // runs everything else on the bus for the m-cycles the CPU just took.
+    fn step(&mut self, _m_cycles: u8) {}
+
+    // the enabled and the requested interrupts, IE and IF.
+    fn interrupts(&self) -> (u8, u8) {
+        (self.read_byte(0xffff), self.read_byte(0xff0f) & 0x1f)
+    }
+
+    // clears a requested interrupt once the CPU jumps to its handler.
+    fn acknowledge(&mut self, interrupt: u8) {
+        let requested = self.read_byte(0xff0f);
+        self.write_byte(requested & !(1 << interrupt), 0xff0f);
+    }
+}
+
+impl Bus for MMU {
+    fn read_byte(&self, address: u16) -> u8 {
+        MMU::read_byte(self, address)
+    }
+
+    fn write_byte(&mut self, value: u8, address: u16) {
+        MMU::write_byte(self, value, address)
+    }
+
+    fn read_word(&self, address: u16) -> u16 {
+        MMU::read_word(self, address)
+    }
+
+    fn write_word(&mut self, value: u16, address: u16) {
+        MMU::write_word(self, value, address)
+    }
+
+    fn step(&mut self, m_cycles: u8) {
+        MMU::step(self, m_cycles)
+    }
+
+    fn interrupts(&self) -> (u8, u8) {
+        (self.inte, self.intf)
+    }
+
+    fn acknowledge(&mut self, interrupt: u8) {
+        self.intf &= !(1 << interrupt);
+    }
+}
+
+// 64K of RAM and nothing else, for test harnesses.
+pub struct Flat {
+    pub memory: Vec<u8>,
+}
+
+impl Flat {
+    pub fn new() -> Self {
+        Self {
+            memory: vec![0; 0x10000],
+        }
+    }
+}
+
+impl Bus for Flat {
+    fn read_byte(&self, address: u16) -> u8 {
+        self.memory[address as usize]
+    }
+
+    fn write_byte(&mut self, value: u8, address: u16) {
+        self.memory[address as usize] = value;
+    }
+}
+
+// a read or a write of the CPU.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Access {
+    pub address: u16,
+    pub value: u8,
+    pub write: bool,
+}
+
+/* wraps a bus, counting the reads and writes of every address, and keeping every access in
+order while the log is on. for profiling, fuzzing and checking the bus activity of the CPU.
+reads take &self, so what they change is in cells. */
+pub struct Instrumented<B> {
+    pub bus: B,
+    reads: Vec<Cell<u64>>,
+    writes: Vec<u64>,
+    log: Option<RefCell<Vec<Access>>>,
+}
+
+impl<B: Bus> Instrumented<B> {
+    pub fn new(bus: B, log: bool) -> Self {
+        Self {
+            bus,
+            reads: vec![Cell::new(0); 0x10000],
+            writes: vec![0; 0x10000],
+            log: log.then(|| RefCell::new(Vec::new())),
+        }
+    }
+
+    pub fn reads(&self, address: u16) -> u64 {
+        self.reads[address as usize].get()
+    }
+
+    pub fn writes(&self, address: u16) -> u64 {
+        self.writes[address as usize]
+    }
+
+    // the accesses since the last call, empty if the log is off.
+    pub fn take_log(&mut self) -> Vec<Access> {
+        self.log
+            .as_mut()
+            .map(|log| std::mem::take(log.get_mut()))
+            .unwrap_or_default()
+    }
+
+    fn record(&self, address: u16, value: u8, write: bool) {
+        if let Some(log) = self.log.as_ref() {
+            log.borrow_mut().push(Access {
+                address,
+                value,
+                write,
+            });
+        }
+    }
+}
+
+impl<B: Bus> Bus for Instrumented<B> {
+    fn read_byte(&self, address: u16) -> u8 {
+        let value = self.bus.read_byte(address);
+        let reads = &self.reads[address as usize];
+        reads.set(reads.get() + 1);
+        self.record(address, value, false);
+        value
+    }
+
+    fn write_byte(&mut self, value: u8, address: u16) {
+        self.bus.write_byte(value, address);
+        self.writes[address as usize] += 1;
+        self.record(address, value, true);
+    }
+
+    fn step(&mut self, m_cycles: u8) {
+        self.bus.step(m_cycles);
+    }
+
+    // the CPU checking for interrupts is not an access of an instruction, so it is not counted.
+    fn interrupts(&self) -> (u8, u8) {
+        self.bus.interrupts()
+    }
+
+    fn acknowledge(&mut self, interrupt: u8) {
+        self.bus.acknowledge(interrupt);
+    }
+}
@@ -19,6 +19,14 @@ impl CPUState {
     }
 }
 
+// the mnemonic of the instruction starting with opcode, next is the byte after it for CB instructions.
+pub fn name(opcode: u8, next: u8) -> String {
+    match opcode {
+        0xcb => display_ins_cb(next),
+        _ => display_ins(opcode),
+    }
+}
+
 fn display_ins(opcode: u8) -> String {
     match opcode {
         0x00 => "NOP",
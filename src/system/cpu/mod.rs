@@ -1,6 +1,7 @@
 use registers::{DoubleRegister, Register, Registers};
+use serde::{Deserialize, Serialize};
 
-use crate::system::{mbc::MBC, mmu::MMU};
+use crate::system::{bus::Bus, mbc::MBC, mmu::MMU};
 
 // cpu
 
@@ -27,15 +28,19 @@ pub enum CPUResult {
     Interrupt,
 }
 
+#[derive(Serialize, Deserialize)]
 enum Interrupt {
     OFF,
     EXECUTE,
     QUEUED,
 }
 
-pub struct CPU {
+/* the SM83 core. mmu is the bus it runs against, the MMU of the Game Boy with all of its
+components unless another one is given, like a plain memory map for test harnesses. */
+#[derive(Serialize, Deserialize)]
+pub struct CPU<B = MMU> {
     pub registers: Registers, // Register Class
-    pub mmu: MMU,             // MMU Class for all the components.
+    pub mmu: B,               // MMU Class for all the components.
     pub ime: bool,            // The IME Flag.
     pub halted: bool,         // Sees if the CPU is halted.
     di: Interrupt,            // disable interrupt
@@ -46,9 +51,71 @@ pub struct CPU {
 
 impl CPU {
     pub fn new(cartridge: Box<dyn MBC>) -> Self {
+        Self::with_bus(MMU::init(cartridge))
+    }
+
+    // serializes the whole machine. the cartridge is stored next to it, as it is a trait object.
+    pub fn save_state(&self) -> Vec<u8> {
+        bincode::serialize(&(self, self.mmu.cartridge.save_state())).unwrap()
+    }
+
+    // loads a state made by save_state, keeping the cartridge ROM, the audio output and the frontend settings.
+    pub fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let (mut state, cartridge): (CPU, Vec<u8>) = bincode::deserialize(data)?;
+        self.mmu.cartridge.load_state(&cartridge)?;
+
+        std::mem::swap(&mut state.mmu.cartridge, &mut self.mmu.cartridge);
+        state.mmu.apu.keep_output(&mut self.mmu.apu);
+        state.mmu.joypad.opposing = self.mmu.joypad.opposing;
+        *self = state;
+        Ok(())
+    }
+
+    // FNV-1a hash of the registers and everything mapped above the ROM, to check that two runs are in sync.
+    pub fn hash(&self) -> u64 {
+        let mut hash: u64 = 0xcbf29ce484222325;
+        let mut add = |byte: u8| {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x100000001b3);
+        };
+
+        for r in [
+            Register::A,
+            Register::F,
+            Register::B,
+            Register::C,
+            Register::D,
+            Register::E,
+            Register::H,
+            Register::L,
+        ] {
+            add(self.registers.get(r));
+        }
+        for byte in self
+            .pc
+            .to_le_bytes()
+            .iter()
+            .chain(self.sp.to_le_bytes().iter())
+        {
+            add(*byte);
+        }
+        add(self.ime as u8);
+        add(self.halted as u8);
+
+        for address in 0x8000..=0xffff {
+            add(self.mmu.read_byte(address));
+        }
+
+        hash
+    }
+}
+
+impl<B: Bus> CPU<B> {
+    // the state the boot ROM leaves the CPU in, running against the bus.
+    pub fn with_bus(mmu: B) -> Self {
         Self {
             registers: Registers::init(),
-            mmu: MMU::init(cartridge),
+            mmu,
             ime: false,
             halted: false,
             di: Interrupt::OFF,
@@ -58,6 +125,22 @@ impl CPU {
         }
     }
 
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn sp(&self) -> u16 {
+        self.sp
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn set_sp(&mut self, sp: u16) {
+        self.sp = sp;
+    }
+
     fn fetch(&mut self) -> u8 {
         let byte = self.mmu.read_byte(self.pc);
         self.pc = self.pc.wrapping_add(1);
@@ -71,7 +154,8 @@ impl CPU {
     }
 
     fn handle_interrupt(&mut self) -> bool {
-        let interrupts = self.mmu.inte & self.mmu.intf;
+        let (enabled, requested) = self.mmu.interrupts();
+        let interrupts = enabled & requested;
 
         if !(self.ime || self.halted) || interrupts == 0 {
             return false;
@@ -87,7 +171,7 @@ impl CPU {
 
         // disables handled interrupt, by looking at the trailing zeros, as flags are ordered by priority in the representation.
         let interrupt = interrupts.trailing_zeros();
-        self.mmu.intf = self.mmu.intf & !(1 << interrupt);
+        self.mmu.acknowledge(interrupt as u8);
 
         self.push(self.pc);
         self.pc = 0x40 | ((interrupt as u16) << 3);
@@ -1012,7 +1096,7 @@ impl CPU {
     }
 
     // returns the ALU logical operation depending on the index.
-    fn get_alu(&self, i: u8) -> fn(&mut CPU, u8) {
+    fn get_alu(&self, i: u8) -> fn(&mut Self, u8) {
         match i {
             0 => CPU::add,
             1 => CPU::adc,
@@ -1026,7 +1110,7 @@ impl CPU {
         }
     }
 
-    fn get_rot(&self, i: u8) -> (fn(&mut CPU, u8) -> u8) {
+    fn get_rot(&self, i: u8) -> (fn(&mut Self, u8) -> u8) {
         match i {
             0 => CPU::rlc,
             1 => CPU::rrc,
@@ -1,8 +1,10 @@
 use std::{collections::HashMap, hash::Hash};
 
+use serde::{Deserialize, Serialize};
+
 // Flag Register.
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
 pub struct FlagRegister {
     pub zero: bool,
     pub subtract: bool,
@@ -31,7 +33,7 @@ impl FlagRegister {
     }
 }
 
-#[derive(Hash, Eq, PartialEq, Debug)]
+#[derive(Serialize, Deserialize, Hash, Eq, PartialEq, Debug)]
 pub enum Register {
     A,
     B,
@@ -61,7 +63,7 @@ impl DoubleRegister {
     }
 }
 
-#[derive(Debug)]
+#[derive(Serialize, Deserialize, Debug)]
 pub struct Registers {
     data: HashMap<Register, u8>,
     pub f: FlagRegister,
@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
 pub struct Joypad {
     a: bool,
     b: bool,
@@ -7,12 +10,43 @@ pub struct Joypad {
     down: bool,
     left: bool,
     right: bool,
-    direction: bool,
-    action: bool,
+    // select lines as written to P1, bit 4 for the d-pad and bit 5 for the buttons. 0 selects.
+    p1: u8,
     pub interrupt: bool,
+    // a setting of the frontend, not part of save states.
+    #[serde(skip)]
+    pub opposing: Opposing,
+    // the later pressed of left/right and up/down.
+    last_horizontal: Input,
+    last_vertical: Input,
+    // SGB packets are decoded from the writes to P1 while this is on, see MMU::set_sgb.
+    pub sgb: bool,
+    // the packet being received, bit by bit, and the packets received since the last write.
+    packet: [u8; 16],
+    bit: u8,
+    receiving: bool,
+    pub packets: Vec<[u8; 16]>,
+    // joypads enabled with MLT_REQ, the one being read and the held buttons of joypads 2 - 4.
+    players: u8,
+    player: u8,
+    others: [u8; 3],
+}
+
+// what happens when opposing directions are held together, which a real d-pad can't do.
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Opposing {
+    // passes both to the game. some games crash.
+    Allow,
+    // only the direction pressed last counts.
+    #[default]
+    Last,
+    // neither direction counts.
+    Neutral,
 }
 
-#[derive(Copy, Clone)]
+#[derive(Serialize, Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
 pub enum Input {
     Left,
     Right,
@@ -24,6 +58,34 @@ pub enum Input {
     Select,
 }
 
+// every input, in the order of their bits in a joypad state byte.
+pub const INPUTS: [Input; 8] = [
+    Input::A,
+    Input::B,
+    Input::Select,
+    Input::Start,
+    Input::Right,
+    Input::Left,
+    Input::Up,
+    Input::Down,
+];
+
+impl Input {
+    // bit of the input in a joypad state byte.
+    pub fn bit(&self) -> u8 {
+        match self {
+            Input::A => 0x01,
+            Input::B => 0x02,
+            Input::Select => 0x04,
+            Input::Start => 0x08,
+            Input::Right => 0x10,
+            Input::Left => 0x20,
+            Input::Up => 0x40,
+            Input::Down => 0x80,
+        }
+    }
+}
+
 impl Joypad {
     pub fn new() -> Self {
         Self {
@@ -35,46 +97,188 @@ impl Joypad {
             down: false,
             left: false,
             right: false,
-            direction: false,
-            action: false,
+            // both groups selected, P1 reads 0xcf after the boot ROM.
+            p1: 0x00,
             interrupt: false,
+            opposing: Opposing::default(),
+            last_horizontal: Input::Right,
+            last_vertical: Input::Down,
+            sgb: false,
+            packet: [0; 16],
+            bit: 0,
+            receiving: false,
+            packets: Vec::new(),
+            players: 1,
+            player: 0,
+            others: [0; 3],
         }
     }
 
     pub fn write(&mut self, value: u8) {
-        self.direction = ((value >> 4) & 0x1) == 0;
-        self.action = ((value >> 5) & 0x1) == 0;
+        let old = self.lines();
+        let old_p1 = self.p1;
+        self.p1 = value & 0x30;
+        if self.sgb {
+            self.sgb_pulse(old_p1);
+        }
+        self.update(old);
     }
 
-    pub fn read(&self) -> u8 {
-        if self.direction {
-            0xc0 | ((!self.down as u8) << 3)
-                | ((!self.up as u8) << 2)
-                | ((!self.left as u8) << 1)
-                | (!self.right as u8)
-        } else if self.action {
-            0xc0 | ((!self.start as u8) << 3)
-                | ((!self.select as u8) << 2)
-                | ((!self.b as u8) << 1)
-                | (!self.a as u8)
+    /* the SGB protocol. both lines low resets and starts a packet, then every bit is a pulse
+    from both lines high: P14 low is a 0, P15 low is a 1. 128 bits are followed by a 0 stop bit.
+    without a packet, going back to both lines high selects the next joypad for multiplayer. */
+    fn sgb_pulse(&mut self, old: u8) {
+        match self.p1 {
+            0x00 => {
+                self.receiving = true;
+                self.packet = [0; 16];
+                self.bit = 0;
+            }
+            0x10 | 0x20 if self.receiving && old == 0x30 => {
+                let one = self.p1 == 0x10;
+                if self.bit < 128 {
+                    self.packet[(self.bit / 8) as usize] |= (one as u8) << (self.bit % 8);
+                    self.bit += 1;
+                } else {
+                    self.receiving = false;
+                    if !one {
+                        self.packets.push(self.packet);
+                    }
+                }
+            }
+            0x30 if !self.receiving && old != 0x30 && self.players > 1 => {
+                self.player = (self.player + 1) % self.players;
+            }
+            _ => (),
+        }
+    }
+
+    // the number of joypads read by the game, set by MLT_REQ. 1 turns multiplayer off.
+    pub fn set_players(&mut self, players: u8) {
+        self.players = players;
+        self.player = 0;
+    }
+
+    pub fn players(&self) -> u8 {
+        self.players
+    }
+
+    // presses or releases a button on joypad 2 - 4, numbered from 1.
+    pub fn set_player_input(&mut self, player: usize, input: Input, pressed: bool) {
+        if player == 0 || player > self.others.len() {
+            return;
+        }
+
+        let old = self.lines();
+        if pressed {
+            self.others[player - 1] |= input.bit();
         } else {
-            0xc0
+            self.others[player - 1] &= !input.bit();
+        }
+        self.update(old);
+    }
+
+    /* the unused bits read as 1, the select bits read back as written.
+    with multiplayer and no group selected, the low bits tell which joypad is read. */
+    pub fn read(&self) -> u8 {
+        if self.players > 1 && self.p1 == 0x30 {
+            return 0xc0 | self.p1 | (0xf - self.player);
+        }
+        0xc0 | self.p1 | self.lines()
+    }
+
+    // resolves a pair of opposing directions. true if the direction counts as held.
+    fn direction(&self, held: bool, other: bool, input: Input, last: Input) -> bool {
+        if !held || !other {
+            return held;
+        }
+
+        match self.opposing {
+            Opposing::Allow => true,
+            Opposing::Last => input == last,
+            Opposing::Neutral => false,
+        }
+    }
+
+    /* the 4 input lines. a held button pulls its line low while its group is selected.
+    with both groups selected, a line is low if either button on it is held. */
+    fn lines(&self) -> u8 {
+        if self.player > 0 {
+            // the other joypads, without opposing direction handling.
+            let state = self.others[(self.player - 1) as usize];
+            let mut low = 0;
+            if self.p1 & 0x10 == 0 {
+                low |= state >> 4;
+            }
+            if self.p1 & 0x20 == 0 {
+                low |= state & 0xf;
+            }
+            return !low & 0xf;
+        }
+
+        let mut low = 0;
+
+        if self.p1 & 0x10 == 0 {
+            let right = self.direction(self.right, self.left, Input::Right, self.last_horizontal);
+            let left = self.direction(self.left, self.right, Input::Left, self.last_horizontal);
+            let up = self.direction(self.up, self.down, Input::Up, self.last_vertical);
+            let down = self.direction(self.down, self.up, Input::Down, self.last_vertical);
+            low |= (down as u8) << 3 | (up as u8) << 2 | (left as u8) << 1 | right as u8;
+        }
+
+        if self.p1 & 0x20 == 0 {
+            low |= (self.start as u8) << 3
+                | (self.select as u8) << 2
+                | (self.b as u8) << 1
+                | self.a as u8;
+        }
+
+        !low & 0xf
+    }
+
+    // the joypad interrupt is requested when a line goes from high to low.
+    fn update(&mut self, old: u8) {
+        if old & !self.lines() & 0xf != 0 {
+            self.interrupt = true;
+        }
+    }
+
+    // presses and releases buttons so that the held buttons match the state byte.
+    pub fn set_state(&mut self, state: u8) {
+        for input in INPUTS {
+            if state & input.bit() != 0 {
+                self.key_down(input);
+            } else {
+                self.key_up(input);
+            }
         }
     }
 
     pub fn key_down(&mut self, key: Input) {
-        let old = self.read();
+        let old = self.lines();
         match key {
             Input::Right => {
+                if !self.right {
+                    self.last_horizontal = key;
+                }
                 self.right = true;
             }
             Input::Left => {
+                if !self.left {
+                    self.last_horizontal = key;
+                }
                 self.left = true;
             }
             Input::Up => {
+                if !self.up {
+                    self.last_vertical = key;
+                }
                 self.up = true;
             }
             Input::Down => {
+                if !self.down {
+                    self.last_vertical = key;
+                }
                 self.down = true;
             }
             Input::A => {
@@ -90,14 +294,11 @@ impl Joypad {
                 self.start = true;
             }
         }
-        let new = self.read();
-        if old != new {
-            self.interrupt = true;
-        }
+        self.update(old);
     }
 
     pub fn key_up(&mut self, key: Input) {
-        let old = self.read();
+        let old = self.lines();
         match key {
             Input::Right => {
                 self.right = false;
@@ -124,9 +325,6 @@ impl Joypad {
                 self.start = false;
             }
         }
-        let new = self.read();
-        if old != new {
-            self.interrupt = true;
-        }
+        self.update(old);
     }
 }
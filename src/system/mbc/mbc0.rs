@@ -1,8 +1,13 @@
+use serde::{Deserialize, Serialize};
+
 use crate::system::mbc::MBC;
 
 use super::MBCError;
 
+#[derive(Serialize, Deserialize)]
 pub struct MBC0 {
+    // not part of save states.
+    #[serde(skip)]
     rom: Vec<u8>,
 }
 
@@ -13,6 +18,17 @@ impl MBC0 {
 }
 
 impl MBC for MBC0 {
+    fn save_state(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let mut state: MBC0 = bincode::deserialize(data)?;
+        state.rom = std::mem::take(&mut self.rom);
+        *self = state;
+        Ok(())
+    }
+
     fn read_ram(&self, _address: u16) -> u8 {
         0
     }
@@ -33,4 +49,20 @@ impl MBC for MBC0 {
     fn save_ram(&self) -> Option<Vec<u8>> {
         None
     }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &[]
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut []
+    }
 }
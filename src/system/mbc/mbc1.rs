@@ -1,8 +1,13 @@
+use serde::{Deserialize, Serialize};
+
 use crate::system::mbc::{ram_banks, rom_banks, MBC};
 
 use super::MBCError;
 
+#[derive(Serialize, Deserialize)]
 pub struct MBC1 {
+    // not part of save states.
+    #[serde(skip)]
     rom: Vec<u8>,
     ram: Vec<u8>,
     ram_on: bool,
@@ -64,6 +69,17 @@ impl MBC1 {
 }
 
 impl MBC for MBC1 {
+    fn save_state(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let mut state: MBC1 = bincode::deserialize(data)?;
+        state.rom = std::mem::take(&mut self.rom);
+        *self = state;
+        Ok(())
+    }
+
     fn read_ram(&self, address: u16) -> u8 {
         if !self.ram_on {
             return 0xff;
@@ -174,4 +190,24 @@ impl MBC for MBC1 {
             None
         }
     }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn banks(&self) -> (usize, usize) {
+        (self.high_bank(), if self.mode { self.ram_bank } else { 0 })
+    }
 }
@@ -1,8 +1,13 @@
+use serde::{Deserialize, Serialize};
+
 use crate::system::mbc::MBC;
 
 use super::MBCError;
 
+#[derive(Serialize, Deserialize)]
 pub struct MBC2 {
+    // not part of save states.
+    #[serde(skip)]
     rom: Vec<u8>,
     ram: Vec<u8>,
     ram_on: bool,
@@ -29,6 +34,17 @@ impl MBC2 {
 }
 
 impl MBC for MBC2 {
+    fn save_state(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let mut state: MBC2 = bincode::deserialize(data)?;
+        state.rom = std::mem::take(&mut self.rom);
+        *self = state;
+        Ok(())
+    }
+
     fn read_ram(&self, address: u16) -> u8 {
         if !self.ram_on {
             return 0xff;
@@ -93,4 +109,24 @@ impl MBC for MBC2 {
             None
         }
     }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn banks(&self) -> (usize, usize) {
+        (self.rom_bank, 0)
+    }
 }
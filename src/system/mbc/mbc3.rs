@@ -1,10 +1,16 @@
 use std::time;
 
+use serde::{Deserialize, Serialize};
+
+use crate::emulator::CLOCK_FREQUENCY;
 use crate::system::mbc::{ram_banks, rom_banks, MBC};
 
 use super::MBCError;
 
+#[derive(Serialize, Deserialize)]
 pub struct MBC3 {
+    // not part of save states.
+    #[serde(skip)]
     rom: Vec<u8>,
     ram: Vec<u8>,
     ram_on: bool,
@@ -52,6 +58,17 @@ impl MBC3 {
 }
 
 impl MBC for MBC3 {
+    fn save_state(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let mut state: MBC3 = bincode::deserialize(data)?;
+        state.rom = std::mem::take(&mut self.rom);
+        *self = state;
+        Ok(())
+    }
+
     fn read_ram(&self, address: u16) -> u8 {
         if !self.ram_on {
             return 0xff;
@@ -150,6 +167,18 @@ impl MBC for MBC3 {
         Ok(())
     }
 
+    fn step(&mut self, m_cycles: u8) {
+        self.rtc.step(m_cycles);
+    }
+
+    fn set_deterministic(&mut self) {
+        self.rtc.set_deterministic();
+    }
+
+    fn set_real_time(&mut self) {
+        self.rtc.set_real_time();
+    }
+
     fn save_ram(&self) -> Option<Vec<u8>> {
         if self.battery {
             Some({
@@ -161,14 +190,43 @@ impl MBC for MBC3 {
             None
         }
     }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn banks(&self) -> (usize, usize) {
+        (self.rom_bank, self.ram_bank)
+    }
 }
 
+// unix time the emulated clock starts at when deterministic (2000-01-01).
+const EMULATED_EPOCH: u64 = 946_684_800;
+
+#[derive(Serialize, Deserialize)]
 struct RTC {
     enabled: bool,
     ram: [u8; 5],
     latch: [u8; 5],
     address: usize,
     start: u64,
+    // t-cycles run since the emulated epoch. None uses the system clock.
+    emulated: Option<u64>,
+    // the start on the system clock, kept while the clock is emulated.
+    #[serde(skip)]
+    real_start: Option<u64>,
 }
 
 impl RTC {
@@ -179,6 +237,57 @@ impl RTC {
             latch: [0; 5],
             address: 0,
             start: 0,
+            emulated: None,
+            real_start: None,
+        }
+    }
+
+    // starts the clock from zero, counting emulated cycles instead of real time.
+    pub fn set_deterministic(&mut self) {
+        if self.emulated.is_none() {
+            self.real_start = Some(self.start);
+        }
+        self.emulated = Some(0);
+        self.ram = [0; 5];
+        self.latch = [0; 5];
+        self.start = EMULATED_EPOCH;
+    }
+
+    /* goes back to the system clock, at the time it had before it was emulated. without that time,
+    like after loading a state, it goes on from the emulated time. */
+    pub fn set_real_time(&mut self) {
+        if self.emulated.is_none() {
+            return;
+        }
+        self.calculate();
+        self.emulated = None;
+        match self.real_start.take() {
+            Some(start) => {
+                self.start = start;
+                self.ram = [0; 5];
+                self.calculate();
+            }
+            None => self.start = self.check(),
+        }
+        self.latch.clone_from_slice(&self.ram);
+    }
+
+    pub fn step(&mut self, m_cycles: u8) {
+        if let Some(cycles) = self.emulated.as_mut() {
+            *cycles += m_cycles as u64 * 4;
+        }
+    }
+
+    // current time in seconds since the unix epoch.
+    fn now(&self) -> u64 {
+        match self.emulated {
+            Some(cycles) => EMULATED_EPOCH + cycles / CLOCK_FREQUENCY as u64,
+            None => match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
+                Ok(t) => t.as_secs(),
+                Err(_) => {
+                    panic!("System clock is set to a time before the unix epoch (1970-01-01)")
+                }
+            },
         }
     }
 
@@ -234,12 +343,7 @@ impl RTC {
             return;
         }
 
-        let time_start = time::UNIX_EPOCH + time::Duration::from_secs(self.start);
-
-        let difference = time::SystemTime::now()
-            .duration_since(time_start)
-            .unwrap()
-            .as_secs();
+        let difference = self.now().saturating_sub(self.start);
 
         self.ram[0] = (difference % 60) as u8;
         self.ram[1] = ((difference / 60) % 60) as u8;
@@ -256,12 +360,7 @@ impl RTC {
     }
 
     pub fn check(&self) -> u64 {
-        let mut difference = match time::SystemTime::now().duration_since(time::UNIX_EPOCH) {
-            Ok(t) => t.as_secs(),
-            Err(_) => {
-                panic!("System clock is set to a time before the unix epoch (1970-01-01)")
-            }
-        };
+        let mut difference = self.now();
         difference -= self.ram[0] as u64;
         difference -= (self.ram[1] as u64) * 60;
         difference -= (self.ram[2] as u64) * 3600;
@@ -1,8 +1,13 @@
+use serde::{Deserialize, Serialize};
+
 use crate::system::mbc::{ram_banks, rom_banks, MBC};
 
 use super::MBCError;
 
+#[derive(Serialize, Deserialize)]
 pub struct MBC5 {
+    // not part of save states.
+    #[serde(skip)]
     rom: Vec<u8>,
     ram: Vec<u8>,
     rom_bank: usize,
@@ -39,6 +44,17 @@ impl MBC5 {
 }
 
 impl MBC for MBC5 {
+    fn save_state(&self) -> Vec<u8> {
+        bincode::serialize(self).unwrap()
+    }
+
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()> {
+        let mut state: MBC5 = bincode::deserialize(data)?;
+        state.rom = std::mem::take(&mut self.rom);
+        *self = state;
+        Ok(())
+    }
+
     fn write_rom(&mut self, value: u8, address: u16) {
         match address {
             0x0000..=0x1fff => {
@@ -109,4 +125,24 @@ impl MBC for MBC5 {
             None
         }
     }
+
+    fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    fn rom_mut(&mut self) -> &mut [u8] {
+        &mut self.rom
+    }
+
+    fn ram(&self) -> &[u8] {
+        &self.ram
+    }
+
+    fn ram_mut(&mut self) -> &mut [u8] {
+        &mut self.ram
+    }
+
+    fn banks(&self) -> (usize, usize) {
+        (self.rom_bank, self.ram_bank)
+    }
 }
@@ -28,6 +28,39 @@ pub trait MBC: Send {
     // retrieves the RAM buffer, if battery is true. Else, it just returns None.
     fn save_ram(&self) -> Option<Vec<u8>>;
 
+    // advances anything on the cartridge that runs on its own, like the RTC.
+    fn step(&mut self, _m_cycles: u8) {}
+
+    // the cartridge state without the ROM, for save states.
+    fn save_state(&self) -> Vec<u8>;
+
+    fn load_state(&mut self, data: &[u8]) -> bincode::Result<()>;
+
+    // the whole ROM and RAM, whatever banks are mapped, for the memory viewer.
+    fn rom(&self) -> &[u8];
+
+    fn rom_mut(&mut self) -> &mut [u8];
+
+    fn ram(&self) -> &[u8];
+
+    fn ram_mut(&mut self) -> &mut [u8];
+
+    // the ROM bank mapped at 0x4000 and the RAM bank mapped at 0xa000.
+    fn banks(&self) -> (usize, usize) {
+        (1, 0)
+    }
+
+    // drives the cartridge from emulated time instead of the wall clock, so runs can be replayed.
+    fn set_deterministic(&mut self) {}
+
+    // goes back to the wall clock after set_deterministic.
+    fn set_real_time(&mut self) {}
+
+    // true if the header asks for SGB functions, which also needs the new licensee code.
+    fn sgb(&self) -> bool {
+        self.read_rom(0x146) == 0x03 && self.read_rom(0x14b) == 0x33
+    }
+
     // retrieves the title from the cartridge itself.
     fn title(&self) -> String {
         let mut title = String::with_capacity(TITLE_LENGTH);
@@ -44,6 +77,11 @@ pub trait MBC: Send {
     }
 }
 
+// a cartridge without ROM, standing in until the real one is put back.
+pub fn empty() -> Box<dyn MBC> {
+    Box::new(mbc0::MBC0::new(Vec::new()))
+}
+
 pub fn new(data: Vec<u8>) -> Result<Box<dyn MBC>, MBCError> {
     let mbc_type = data[0x147];
 
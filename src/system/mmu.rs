@@ -1,14 +1,44 @@
-use crate::system::{apu::APU, joypad::Joypad, mbc::MBC, ppu::PPU, timer::Timer};
-
+use serde::{Deserialize, Serialize};
+
+use crate::system::{
+    apu::APU,
+    array,
+    joypad::Joypad,
+    mbc::{self, MBC},
+    ppu::PPU,
+    sgb::Sgb,
+    timer::Timer,
+};
+
+#[derive(Serialize, Deserialize)]
 pub struct MMU {
     pub ppu: PPU,
     pub joypad: Joypad,
     pub inte: u8,
     pub intf: u8,
+    #[serde(with = "array")]
     ram: [u8; 0x10000],
     pub timer: Timer,
+    // a trait object, saved separately through MBC::save_state.
+    #[serde(skip, default = "mbc::empty")]
     pub cartridge: Box<dyn MBC>,
     pub apu: APU,
+    // only there for SGB cartridges, while SGB mode is on.
+    pub sgb: Option<Sgb>,
+    // Game Genie cheats, set again by the emulator after a save state is loaded.
+    #[serde(skip)]
+    pub patches: Vec<Patch>,
+    // LY always reads 0x90, for comparing against the logs of gameboy-doctor.
+    #[serde(skip)]
+    pub stub_ly: bool,
+}
+
+// replaces the byte read from a ROM address, only when it was compare if there is one.
+#[derive(Copy, Clone, Debug)]
+pub struct Patch {
+    pub address: u16,
+    pub value: u8,
+    pub compare: Option<u8>,
 }
 
 impl MMU {
@@ -22,12 +52,17 @@ impl MMU {
             ram: [0; 0x10000],
             cartridge,
             apu: APU::new(),
+            sgb: None,
+            patches: Vec::new(),
+            stub_ly: false,
         }
     }
 
     // initializes gameboy state without needing boot rom.
     pub fn init(cartridge: Box<dyn MBC>) -> Self {
         let mut mmu = MMU::new(cartridge);
+        // the APU has to be on before its registers can be written.
+        mmu.write_byte(0xf1, 0xff26);
         mmu.write_byte(0x80, 0xff10);
         mmu.write_byte(0xbf, 0xff11);
         mmu.write_byte(0xf3, 0xff12);
@@ -42,7 +77,6 @@ impl MMU {
         mmu.write_byte(0xbf, 0xff23);
         mmu.write_byte(0x77, 0xff24);
         mmu.write_byte(0xf3, 0xff25);
-        mmu.write_byte(0xf1, 0xff26);
         mmu.write_byte(0x91, 0xff40);
         mmu.write_byte(0xfc, 0xff47);
         mmu.write_byte(0xff, 0xff48);
@@ -68,12 +102,14 @@ impl MMU {
         self.ppu.interrupt_stat = false;
 
         self.apu.step(m_cycles);
+
+        self.cartridge.step(m_cycles);
     }
 
     // reads the address by mapping it to the correct component.
     pub fn read_byte(&self, address: u16) -> u8 {
         match address {
-            0x0000..=0x7fff => self.cartridge.read_rom(address),
+            0x0000..=0x7fff => self.read_rom(address),
             0x8000..=0x9fff => self.ppu.read(address),
             0xa000..=0xbfff => self.cartridge.read_ram(address),
             0xfe00..=0xfe9f => self.ppu.read(address),
@@ -86,6 +122,7 @@ impl MMU {
             // oam dma transfer - returns nothing.
             0xff46 => 0,
 
+            0xff44 if self.stub_ly => 0x90,
             0xff40..=0xff4b => self.ppu.read(address),
 
             0xff0f => 0xe0 | self.intf,
@@ -100,6 +137,17 @@ impl MMU {
         }
     }
 
+    // the compare value lets a patch only hit the bank it was made for.
+    fn read_rom(&self, address: u16) -> u8 {
+        let value = self.cartridge.read_rom(address);
+        for patch in self.patches.iter() {
+            if patch.address == address && patch.compare.is_none_or(|c| c == value) {
+                return patch.value;
+            }
+        }
+        value
+    }
+
     // writes value to given address.
     pub fn write_byte(&mut self, value: u8, address: u16) {
         match address {
@@ -107,7 +155,10 @@ impl MMU {
             0x8000..=0x9fff => self.ppu.write(value, address),
             0xa000..=0xbfff => self.cartridge.write_ram(value, address),
             0xfe00..=0xfe9f => self.ppu.write(value, address),
-            0xff00 => self.joypad.write(value),
+            0xff00 => {
+                self.joypad.write(value);
+                self.sgb_packets();
+            }
 
             0xff10..=0xff3f => self.apu.write(value, address),
 
@@ -148,6 +199,30 @@ impl MMU {
         self.write_byte((value >> 8) as u8, address.wrapping_add(1));
     }
 
+    // runs the SGB commands of the packets that the joypad received.
+    fn sgb_packets(&mut self) {
+        let packets = std::mem::take(&mut self.joypad.packets);
+        if let Some(sgb) = self.sgb.as_mut() {
+            for packet in packets {
+                sgb.packet(packet, &self.ppu, &mut self.joypad);
+            }
+        }
+    }
+
+    // turns SGB mode on or off. it only turns on for cartridges that support it.
+    pub fn set_sgb(&mut self, on: bool) {
+        if !on {
+            self.sgb = None;
+        } else if self.sgb.is_none() && self.cartridge.sgb() {
+            self.sgb = Some(Sgb::new());
+        }
+
+        self.joypad.sgb = self.sgb.is_some();
+        if self.sgb.is_none() {
+            self.joypad.set_players(1);
+        }
+    }
+
     /* when called, starts reading from addresses that contain the Object Tile Data,
     before transfering it to the OAM memory for use. */
 
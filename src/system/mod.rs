@@ -1,7 +1,10 @@
 pub mod apu;
+mod array;
+pub mod bus;
 pub mod cpu;
 pub mod joypad;
 pub mod mbc;
 pub mod mmu;
 pub mod ppu;
+pub mod sgb;
 pub mod timer;
@@ -1,3 +1,7 @@
+use serde::{Deserialize, Serialize};
+
+use crate::system::array;
+
 const SCREEN_WIDTH: usize = 160;
 const SCREEN_HEIGHT: usize = 144;
 
@@ -6,7 +10,12 @@ const DRAW_CYCLES: u16 = 172;
 const HBLANK_CYCLES: u16 = 204;
 const VBLANK_CYCLES: u16 = 456;
 
-#[derive(Copy, Clone, Debug)]
+// layers a pixel can come from, so the frontend can color them with their own palettes.
+pub const LAYER_BG: u8 = 0;
+pub const LAYER_OBJ0: u8 = 1;
+pub const LAYER_OBJ1: u8 = 2;
+
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
 struct LCDC {
     enable_lcd: bool,
     tile_map_window: bool,
@@ -41,7 +50,7 @@ impl LCDC {
     }
 }
 
-#[derive(Copy, Clone, Debug)]
+#[derive(Serialize, Deserialize, Copy, Clone, Debug)]
 enum Mode {
     OAMScan = 2,
     Drawing = 3,
@@ -49,6 +58,7 @@ enum Mode {
     VBlank = 1,
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct PPU {
     lcdc: LCDC,
     ly: u8,
@@ -57,11 +67,17 @@ pub struct PPU {
     scx: u8,
     winy: u8,
     winx: u8,
+    #[serde(with = "array")]
     bank_0: [u8; 0x800],
+    #[serde(with = "array")]
     bank_1: [u8; 0x800],
+    #[serde(with = "array")]
     bank_2: [u8; 0x800],
+    #[serde(with = "array")]
     map_0: [u8; 0x400],
+    #[serde(with = "array")]
     map_1: [u8; 0x400],
+    #[serde(with = "array")]
     oam: [[u8; 4]; 40],
     bgp: u8,
     obp_0: u8,
@@ -73,7 +89,11 @@ pub struct PPU {
     mode: Mode,
     clock: u16,
 
+    #[serde(with = "array")]
     pub buffer: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
+    // which layer every pixel of buffer comes from, one of the LAYER_ constants.
+    #[serde(with = "array")]
+    pub layers: [u8; SCREEN_HEIGHT * SCREEN_WIDTH],
 
     pub interrupt_stat: bool,
     pub interrupt_vblank: bool,
@@ -119,6 +139,7 @@ impl PPU {
             int_2: false,
 
             buffer: [1; SCREEN_HEIGHT * SCREEN_WIDTH],
+            layers: [LAYER_BG; SCREEN_HEIGHT * SCREEN_WIDTH],
 
             interrupt_stat: false,
             interrupt_vblank: false,
@@ -239,6 +260,101 @@ impl PPU {
         }
     }
 
+    /* the 4KB of tile data shown on screen, the first 256 tiles of the background map in
+    rows of 20. the SGB reads its VRAM transfers from the screen this way. */
+    pub fn transfer(&self) -> Vec<u8> {
+        let map = if self.lcdc.tile_map_bg {
+            0x9c00
+        } else {
+            0x9800
+        };
+
+        let mut data = Vec::with_capacity(0x1000);
+        for i in 0..0x100u16 {
+            let tile_index = self.read(map + (i / 20) * 32 + i % 20);
+            let tile_addr = if self.lcdc.tile_area {
+                0x8000 + (tile_index as u16) * 16
+            } else {
+                0x8800 + (((tile_index as i8 as i16) + 128) as u16) * 16
+            };
+            data.extend((0..16).map(|byte| self.read(tile_addr + byte)));
+        }
+        data
+    }
+
+    // color numbers 0 - 3 of one of the 384 tiles of VRAM, numbered from 0x8000.
+    pub fn tile(&self, index: usize) -> [[u8; 8]; 8] {
+        let bank = match index / 128 {
+            0 => &self.bank_0,
+            1 => &self.bank_1,
+            _ => &self.bank_2,
+        };
+        let offset = (index % 128) * 16;
+
+        let mut tile = [[0; 8]; 8];
+        for (y, row) in tile.iter_mut().enumerate() {
+            let low = bank[offset + y * 2];
+            let high = bank[offset + y * 2 + 1];
+            for (x, pixel) in row.iter_mut().enumerate() {
+                *pixel = (((high >> (7 - x)) & 0x1) << 1) | ((low >> (7 - x)) & 0x1);
+            }
+        }
+        tile
+    }
+
+    // the tile at x, y of map 0 (0x9800) or 1 (0x9c00), with the addressing LCDC selects.
+    pub fn map_tile(&self, map: usize, x: usize, y: usize) -> usize {
+        let index = match map {
+            0 => self.map_0[y * 32 + x],
+            _ => self.map_1[y * 32 + x],
+        };
+        if self.lcdc.tile_area {
+            index as usize
+        } else {
+            (256 + index as i8 as isize) as usize
+        }
+    }
+
+    // the maps the background and window are drawn from.
+    pub fn bg_map(&self) -> usize {
+        self.lcdc.tile_map_bg as usize
+    }
+
+    pub fn window_map(&self) -> usize {
+        self.lcdc.tile_map_window as usize
+    }
+
+    // SCX and SCY.
+    pub fn scroll(&self) -> (u8, u8) {
+        (self.scx, self.scy)
+    }
+
+    // WX and WY, when the window is shown.
+    pub fn window(&self) -> Option<(u8, u8)> {
+        match self.lcdc.enable_window {
+            true => Some((self.winx, self.winy)),
+            false => None,
+        }
+    }
+
+    // BGP, OBP0 and OBP1.
+    pub fn palettes(&self) -> [u8; 3] {
+        [self.bgp, self.obp_0, self.obp_1]
+    }
+
+    pub fn oam(&self) -> &[[u8; 4]; 40] {
+        &self.oam
+    }
+
+    // 8 or 16 pixels, set by LCDC.
+    pub fn sprite_height(&self) -> usize {
+        if self.lcdc.obj_size {
+            16
+        } else {
+            8
+        }
+    }
+
     fn draw_bg_line(&mut self) {
         if !self.lcdc.enable_bg_window {
             return;
@@ -276,6 +392,7 @@ impl PPU {
             let pixel_id = (self.bgp >> (pixel_color * 2)) & 0x03;
 
             self.buffer[(self.ly as usize) * SCREEN_WIDTH + pixel_index] = pixel_id;
+            self.layers[(self.ly as usize) * SCREEN_WIDTH + pixel_index] = LAYER_BG;
         }
     }
 
@@ -324,6 +441,7 @@ impl PPU {
                 //}
 
                 self.buffer[(self.ly as usize) * SCREEN_WIDTH + pixel_index] = pixel_id;
+                self.layers[(self.ly as usize) * SCREEN_WIDTH + pixel_index] = LAYER_BG;
             }
         }
     }
@@ -413,9 +531,11 @@ impl PPU {
                         };
 
                         if pixel_color != 0 {
-                            self.buffer[((self.ly as u16) * (SCREEN_WIDTH as u16)
+                            let index = ((self.ly as u16) * (SCREEN_WIDTH as u16)
                                 + ((x + x_in_sprite) as u16))
-                                as usize] = pixel_id;
+                                as usize;
+                            self.buffer[index] = pixel_id;
+                            self.layers[index] = LAYER_OBJ0 + palette;
                         }
                     }
                 }
@@ -0,0 +1,394 @@
+use serde::{Deserialize, Serialize};
+
+use crate::system::{array, joypad::Joypad, ppu::PPU};
+
+const SCREEN_WIDTH: usize = 160;
+const SCREEN_HEIGHT: usize = 144;
+
+// the SGB picture, the game screen inside its border.
+pub const BORDER_WIDTH: usize = 256;
+pub const BORDER_HEIGHT: usize = 224;
+const SCREEN_X: usize = 48;
+const SCREEN_Y: usize = 40;
+
+// the screen is colored in cells of 8x8 pixels.
+const CELLS_X: usize = 20;
+const CELLS_Y: usize = 18;
+const ATTRIBUTE_FILE: usize = 90;
+const ATTRIBUTE_FILES: usize = 45;
+
+// the size of an RGBA frame, with or without the border.
+pub fn frame_size(length: usize) -> (usize, usize) {
+    if length == BORDER_WIDTH * BORDER_HEIGHT * 4 {
+        (BORDER_WIDTH, BORDER_HEIGHT)
+    } else {
+        (SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+}
+
+// set with MASK_EN, hides the screen while the game draws the next one.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq)]
+enum Mask {
+    Cancel,
+    Freeze,
+    Black,
+    Color0,
+}
+
+/* the Super Game Boy side of the link. commands come in as 16 byte packets through the joypad,
+see Joypad::write, and larger data is sent through VRAM by showing it on screen. */
+#[derive(Serialize, Deserialize)]
+pub struct Sgb {
+    // packets of the command being received, a command is 1 to 7 packets long.
+    command: Vec<[u8; 16]>,
+    // the four palettes of the screen, RGB555. color 0 is shared by all of them.
+    palettes: [[u16; 4]; 4],
+    // palettes sent with PAL_TRN, picked with PAL_SET.
+    #[serde(with = "array")]
+    system_palettes: [[u16; 4]; 512],
+    // the palette of every cell of the screen.
+    #[serde(with = "array")]
+    attributes: [u8; CELLS_X * CELLS_Y],
+    // sent with ATTR_TRN, 4 cells per byte.
+    #[serde(with = "array")]
+    attribute_files: [u8; ATTRIBUTE_FILE * ATTRIBUTE_FILES],
+    // 256 4bpp tiles of the border, 32 bytes each.
+    #[serde(with = "array")]
+    border_tiles: [u8; 0x2000],
+    // 32x32 tile map of the border, only the first 28 rows are shown.
+    #[serde(with = "array")]
+    border_map: [u16; 0x400],
+    // palettes 4 - 7 of the border. color 0 is transparent.
+    border_palettes: [[u16; 16]; 4],
+    // true once a border was sent, until then the frame has no border.
+    border: bool,
+    mask: Mask,
+    // the screen as it was when MASK_EN froze it.
+    #[serde(with = "array")]
+    frozen: [u8; SCREEN_WIDTH * SCREEN_HEIGHT],
+}
+
+impl Sgb {
+    pub fn new() -> Self {
+        Self {
+            command: Vec::new(),
+            palettes: [[0x67bf, 0x265b, 0x10b5, 0x2866]; 4],
+            system_palettes: [[0; 4]; 512],
+            attributes: [0; CELLS_X * CELLS_Y],
+            attribute_files: [0; ATTRIBUTE_FILE * ATTRIBUTE_FILES],
+            border_tiles: [0; 0x2000],
+            border_map: [0; 0x400],
+            border_palettes: [[0; 16]; 4],
+            border: false,
+            mask: Mask::Cancel,
+            frozen: [0; SCREEN_WIDTH * SCREEN_HEIGHT],
+        }
+    }
+
+    // collects the packets of a command and runs it once all of them arrived.
+    pub fn packet(&mut self, packet: [u8; 16], ppu: &PPU, joypad: &mut Joypad) {
+        // the first packet holds the number of packets in its low 3 bits, 0 is not a command.
+        if self.command.is_empty() && packet[0] & 0x7 == 0 {
+            return;
+        }
+
+        self.command.push(packet);
+        if self.command.len() < (self.command[0][0] & 0x7) as usize {
+            return;
+        }
+
+        let data: Vec<u8> = self.command.drain(..).flatten().collect();
+        self.execute(&data, ppu, joypad);
+    }
+
+    fn execute(&mut self, data: &[u8], ppu: &PPU, joypad: &mut Joypad) {
+        match data[0] >> 3 {
+            // PAL01, PAL23, PAL03 and PAL12.
+            0x00 => self.set_palettes(data, 0, 1),
+            0x01 => self.set_palettes(data, 2, 3),
+            0x02 => self.set_palettes(data, 0, 3),
+            0x03 => self.set_palettes(data, 1, 2),
+            // ATTR_BLK
+            0x04 => {
+                let count = (data[1] as usize).min(18);
+                for set in data[2..].chunks_exact(6).take(count) {
+                    self.attribute_block(set);
+                }
+            }
+            // ATTR_LIN, a row or column of cells.
+            0x05 => {
+                let count = (data[1] as usize).min(110);
+                for &line in data[2..].iter().take(count) {
+                    let (n, palette) = ((line & 0x1f) as usize, (line >> 5) & 0x3);
+                    for (i, cell) in self.attributes.iter_mut().enumerate() {
+                        let (x, y) = (i % CELLS_X, i / CELLS_X);
+                        if (line & 0x80 != 0 && y == n) || (line & 0x80 == 0 && x == n) {
+                            *cell = palette;
+                        }
+                    }
+                }
+            }
+            // ATTR_DIV, the screen split in two at a row or column.
+            0x06 => {
+                let (after, before, on) =
+                    (data[1] & 0x3, (data[1] >> 2) & 0x3, (data[1] >> 4) & 0x3);
+                let n = (data[2] & 0x1f) as usize;
+                for (i, cell) in self.attributes.iter_mut().enumerate() {
+                    let position = if data[1] & 0x40 != 0 {
+                        i / CELLS_X
+                    } else {
+                        i % CELLS_X
+                    };
+                    *cell = match position.cmp(&n) {
+                        std::cmp::Ordering::Less => before,
+                        std::cmp::Ordering::Equal => on,
+                        std::cmp::Ordering::Greater => after,
+                    };
+                }
+            }
+            // ATTR_CHR, cells one by one from a starting cell.
+            0x07 => {
+                let (mut x, mut y) = ((data[1] & 0x1f) as usize, (data[2] & 0x1f) as usize);
+                let count = (u16::from_le_bytes([data[3], data[4]]) as usize).min(360);
+                let vertical = data[5] & 0x1 != 0;
+                for i in 0..count {
+                    let byte = match data.get(6 + i / 4) {
+                        Some(&byte) => byte,
+                        None => break,
+                    };
+                    if x < CELLS_X && y < CELLS_Y {
+                        self.attributes[y * CELLS_X + x] = (byte >> (6 - (i % 4) * 2)) & 0x3;
+                    }
+                    if vertical {
+                        y += 1;
+                        if y == CELLS_Y {
+                            y = 0;
+                            x += 1;
+                        }
+                    } else {
+                        x += 1;
+                        if x == CELLS_X {
+                            x = 0;
+                            y += 1;
+                        }
+                    }
+                }
+            }
+            // PAL_SET, palettes picked from the ones sent with PAL_TRN.
+            0x0a => {
+                for i in 0..4 {
+                    let n = u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]) & 0x1ff;
+                    self.palettes[i] = self.system_palettes[n as usize];
+                }
+                self.share_color0(self.palettes[0][0]);
+                if data[9] & 0x80 != 0 {
+                    self.attribute_file((data[9] & 0x3f) as usize);
+                }
+                if data[9] & 0x40 != 0 {
+                    self.mask = Mask::Cancel;
+                }
+            }
+            // PAL_TRN
+            0x0b => {
+                let transfer = ppu.transfer();
+                for (palette, colors) in self.system_palettes.iter_mut().zip(transfer.chunks(8)) {
+                    for (color, bytes) in palette.iter_mut().zip(colors.chunks(2)) {
+                        *color = u16::from_le_bytes([bytes[0], bytes[1]]);
+                    }
+                }
+            }
+            // MLT_REQ, 1, 2 or 4 joypads.
+            0x11 => joypad.set_players(match data[1] & 0x3 {
+                1 => 2,
+                3 => 4,
+                _ => 1,
+            }),
+            // CHR_TRN, half of the border tiles.
+            0x13 => {
+                let half = (data[1] & 0x1) as usize * 0x1000;
+                self.border_tiles[half..half + 0x1000].copy_from_slice(&ppu.transfer());
+            }
+            // PCT_TRN, the border tile map and its palettes.
+            0x14 => {
+                let transfer = ppu.transfer();
+                for (entry, bytes) in self.border_map.iter_mut().zip(transfer.chunks(2)) {
+                    *entry = u16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+                let colors = transfer[0x800..0x880].chunks(2);
+                for (i, bytes) in colors.enumerate() {
+                    self.border_palettes[i / 16][i % 16] = u16::from_le_bytes([bytes[0], bytes[1]]);
+                }
+                self.border = true;
+            }
+            // ATTR_TRN
+            0x15 => {
+                let transfer = ppu.transfer();
+                let length = self.attribute_files.len();
+                self.attribute_files.copy_from_slice(&transfer[..length]);
+            }
+            // ATTR_SET
+            0x16 => {
+                self.attribute_file((data[1] & 0x3f) as usize);
+                if data[1] & 0x40 != 0 {
+                    self.mask = Mask::Cancel;
+                }
+            }
+            // MASK_EN
+            0x17 => {
+                self.mask = match data[1] & 0x3 {
+                    1 => Mask::Freeze,
+                    2 => Mask::Black,
+                    3 => Mask::Color0,
+                    _ => Mask::Cancel,
+                };
+                if self.mask == Mask::Freeze {
+                    self.frozen.copy_from_slice(&ppu.buffer);
+                }
+            }
+            // sound, SNES code and the other commands are not emulated.
+            _ => (),
+        }
+    }
+
+    // PAL01 - PAL12 set color 0 of every palette, and colors 1 - 3 of two of them.
+    fn set_palettes(&mut self, data: &[u8], first: usize, second: usize) {
+        let color = |i: usize| u16::from_le_bytes([data[1 + i * 2], data[2 + i * 2]]);
+        self.share_color0(color(0));
+        for i in 1..4 {
+            self.palettes[first][i] = color(i);
+            self.palettes[second][i] = color(i + 3);
+        }
+    }
+
+    fn share_color0(&mut self, color: u16) {
+        for palette in self.palettes.iter_mut() {
+            palette[0] = color;
+        }
+    }
+
+    /* one rectangle of ATTR_BLK. the cells inside, on the edge and outside of it can each
+    get a palette. when only inside or only outside is set, the edge gets the same one. */
+    fn attribute_block(&mut self, set: &[u8]) {
+        let (control, palettes) = (set[0] & 0x7, set[1]);
+        let [x1, y1, x2, y2] = [set[2], set[3], set[4], set[5]].map(|n| (n & 0x1f) as usize);
+        let inside = palettes & 0x3;
+        let outside = (palettes >> 4) & 0x3;
+        let (edge_on, edge) = match control {
+            0x1 => (true, inside),
+            0x4 => (true, outside),
+            _ => (control & 0x2 != 0, (palettes >> 2) & 0x3),
+        };
+
+        for (i, cell) in self.attributes.iter_mut().enumerate() {
+            let (x, y) = (i % CELLS_X, i / CELLS_X);
+            let within = (x1..=x2).contains(&x) && (y1..=y2).contains(&y);
+            let on_edge = within && (x == x1 || x == x2 || y == y1 || y == y2);
+
+            if on_edge {
+                if edge_on {
+                    *cell = edge;
+                }
+            } else if within {
+                if control & 0x1 != 0 {
+                    *cell = inside;
+                }
+            } else if control & 0x4 != 0 {
+                *cell = outside;
+            }
+        }
+    }
+
+    fn attribute_file(&mut self, file: usize) {
+        if file >= ATTRIBUTE_FILES {
+            return;
+        }
+        let data = &self.attribute_files[file * ATTRIBUTE_FILE..(file + 1) * ATTRIBUTE_FILE];
+        for (i, cell) in self.attributes.iter_mut().enumerate() {
+            *cell = (data[i / 4] >> (6 - (i % 4) * 2)) & 0x3;
+        }
+    }
+
+    /* colors the shades of the screen with the palettes of their cells, as an RGBA frame.
+    once a border was sent, the frame is 256x224 with the screen in the middle. */
+    pub fn render(&self, screen: &[u8]) -> Vec<u8> {
+        let screen = match self.mask {
+            Mask::Freeze => &self.frozen[..],
+            _ => screen,
+        };
+        let game = |x: usize, y: usize| match self.mask {
+            Mask::Black => 0x0000,
+            Mask::Color0 => self.palettes[0][0],
+            _ => {
+                let palette = self.attributes[(y / 8) * CELLS_X + x / 8] as usize;
+                self.palettes[palette][(screen[y * SCREEN_WIDTH + x] & 0x3) as usize]
+            }
+        };
+
+        if !self.border {
+            let mut frame = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT * 4);
+            for y in 0..SCREEN_HEIGHT {
+                for x in 0..SCREEN_WIDTH {
+                    frame.extend_from_slice(&rgba(game(x, y)));
+                }
+            }
+            return frame;
+        }
+
+        let mut frame = Vec::with_capacity(BORDER_WIDTH * BORDER_HEIGHT * 4);
+        for y in 0..BORDER_HEIGHT {
+            for x in 0..BORDER_WIDTH {
+                let color = if (SCREEN_X..SCREEN_X + SCREEN_WIDTH).contains(&x)
+                    && (SCREEN_Y..SCREEN_Y + SCREEN_HEIGHT).contains(&y)
+                {
+                    game(x - SCREEN_X, y - SCREEN_Y)
+                } else {
+                    self.border_pixel(x, y)
+                };
+                frame.extend_from_slice(&rgba(color));
+            }
+        }
+        frame
+    }
+
+    // a pixel of the border, in SNES tile format. transparent pixels show color 0.
+    fn border_pixel(&self, x: usize, y: usize) -> u16 {
+        let entry = self.border_map[(y / 8) * 32 + x / 8];
+        let tile = (entry & 0xff) as usize * 32;
+        let column = if entry & 0x4000 != 0 {
+            7 - x % 8
+        } else {
+            x % 8
+        };
+        let row = if entry & 0x8000 != 0 {
+            7 - y % 8
+        } else {
+            y % 8
+        };
+
+        // bitplanes 0 and 1 are in the first 16 bytes of the tile, 2 and 3 in the next 16.
+        let planes = [
+            self.border_tiles[tile + row * 2],
+            self.border_tiles[tile + row * 2 + 1],
+            self.border_tiles[tile + 16 + row * 2],
+            self.border_tiles[tile + 16 + row * 2 + 1],
+        ];
+        let index = planes.iter().enumerate().fold(0, |index, (i, plane)| {
+            index | ((plane >> (7 - column)) & 0x1) << i
+        });
+
+        match index {
+            0 => self.palettes[0][0],
+            _ => {
+                // only palettes 4 - 7 can be sent.
+                let palette = ((entry >> 10) & 0x7).saturating_sub(4) as usize;
+                self.border_palettes[palette][index as usize]
+            }
+        }
+    }
+}
+
+// RGB555 to RGBA.
+fn rgba(color: u16) -> [u8; 4] {
+    let expand = |c: u16| ((c & 0x1f) << 3 | (c & 0x1f) >> 2) as u8;
+    [expand(color), expand(color >> 5), expand(color >> 10), 0xff]
+}
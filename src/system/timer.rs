@@ -1,5 +1,8 @@
 use core::panic;
 
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
 pub struct Timer {
     div: u16,
     tima: u8,
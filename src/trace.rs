@@ -0,0 +1,144 @@
+use crate::config::{Trace, TraceFormat};
+use crate::system::cpu::mnemonic;
+use crate::system::cpu::registers::Register;
+use crate::system::cpu::CPU;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/* writes a line for every instruction, with the registers before it runs. the line is made
+before each step and only written if the step ran an instruction, so halted steps and
+interrupt dispatches are left out, like in the logs of other emulators. */
+pub struct Tracer {
+    pub path: PathBuf,
+    writer: BufWriter<File>,
+    trace: Trace,
+    lines: u64,
+}
+
+impl Tracer {
+    pub fn new(path: &Path, trace: Trace) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: BufWriter::new(File::create(path)?),
+            trace,
+            lines: 0,
+        })
+    }
+
+    // the line for the instruction at PC, none if it is filtered out.
+    pub fn line(&self, cpu: &CPU) -> Option<String> {
+        let pc = cpu.pc();
+        if pc < self.trace.from || pc > self.trace.to {
+            return None;
+        }
+
+        // code outside of the ROM has no bank, so it is left out when one is asked for.
+        let bank = match pc {
+            0x0000..=0x3fff => Some(0),
+            0x4000..=0x7fff => Some(cpu.mmu.cartridge.banks().0),
+            _ => None,
+        };
+        if self.trace.bank.is_some() && self.trace.bank != bank {
+            return None;
+        }
+
+        Some(match self.trace.format {
+            TraceFormat::Doctor => doctor(cpu),
+            TraceFormat::Mnemonic => {
+                let r = |register| cpu.registers.get(register);
+                let f = r(Register::F);
+                let flags: String = [(0x80, 'Z'), (0x40, 'N'), (0x20, 'H'), (0x10, 'C')]
+                    .iter()
+                    .map(|&(bit, c)| if f & bit != 0 { c } else { '-' })
+                    .collect();
+                let bank = match bank {
+                    Some(bank) => format!("{:02X}", bank),
+                    None => "--".to_string(),
+                };
+                format!(
+                    "{}:{:04X}  {:<20} A:{:02X} F:{} BC:{:02X}{:02X} DE:{:02X}{:02X} HL:{:02X}{:02X} SP:{:04X} IME:{}",
+                    bank,
+                    pc,
+                    disassemble(memory(cpu)),
+                    r(Register::A),
+                    flags,
+                    r(Register::B),
+                    r(Register::C),
+                    r(Register::D),
+                    r(Register::E),
+                    r(Register::H),
+                    r(Register::L),
+                    cpu.sp(),
+                    cpu.ime as u8,
+                )
+            }
+        })
+    }
+
+    pub fn write(&mut self, line: &str) -> io::Result<()> {
+        writeln!(self.writer, "{}", line)?;
+        self.lines += 1;
+        Ok(())
+    }
+
+    // flushes the log, returning the number of lines in it.
+    pub fn finish(mut self) -> io::Result<u64> {
+        self.writer.flush()?;
+        Ok(self.lines)
+    }
+}
+
+// the 4 bytes from PC on.
+fn memory(cpu: &CPU) -> [u8; 4] {
+    [0, 1, 2, 3].map(|i| cpu.mmu.read_byte(cpu.pc().wrapping_add(i)))
+}
+
+// the format of gameboy-doctor.
+pub fn doctor(cpu: &CPU) -> String {
+    let r = |register| cpu.registers.get(register);
+    let memory = memory(cpu);
+    format!(
+        "A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{:02X},{:02X},{:02X},{:02X}",
+        r(Register::A),
+        r(Register::F),
+        r(Register::B),
+        r(Register::C),
+        r(Register::D),
+        r(Register::E),
+        r(Register::H),
+        r(Register::L),
+        cpu.sp(),
+        cpu.pc(),
+        memory[0],
+        memory[1],
+        memory[2],
+        memory[3],
+    )
+}
+
+// the instruction in the bytes, with the n, nn and d of the mnemonic replaced by its operands.
+pub fn disassemble(bytes: [u8; 4]) -> String {
+    let name = mnemonic::name(bytes[0], bytes[1]);
+    let mut text = String::with_capacity(name.len() + 4);
+    let mut word = String::new();
+    for c in name.chars().chain(std::iter::once(' ')) {
+        if c.is_ascii_alphanumeric() {
+            word.push(c);
+            continue;
+        }
+        match word.as_str() {
+            "n" => text.push_str(&format!("${:02X}", bytes[1])),
+            "nn" => text.push_str(&format!(
+                "${:04X}",
+                u16::from_le_bytes([bytes[1], bytes[2]])
+            )),
+            "d" => text.push_str(&format!("{:+}", bytes[1] as i8)),
+            _ => text.push_str(&word),
+        }
+        word.clear();
+        text.push(c);
+    }
+    text.pop();
+    text
+}
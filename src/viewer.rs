@@ -0,0 +1,355 @@
+use crate::config::{Color, Palettes};
+use crate::emulator::Emulator;
+use crate::font::{self, ADVANCE, GLYPH_HEIGHT};
+use crate::memory::{self, MemoryView};
+use crate::system::ppu::PPU;
+
+const LABEL_HEIGHT: usize = GLYPH_HEIGHT + 3;
+const LINE_HEIGHT: usize = GLYPH_HEIGHT + 2;
+const CHEAT_ROWS: usize = 24;
+// 2 lines of text next to every sprite, tall enough for 8x16 sprites.
+const OAM_ROW: usize = 18;
+const OAM_COLUMN: usize = 130;
+
+const BACKGROUND: [u8; 3] = [0x20, 0x20, 0x20];
+const WHITE: [u8; 3] = [0xff, 0xff, 0xff];
+const YELLOW: [u8; 3] = [0xff, 0xd8, 0x40];
+const GRAY: [u8; 3] = [0x88, 0x88, 0x88];
+const RED: [u8; 3] = [0xff, 0x30, 0x30];
+const BLUE: [u8; 3] = [0x30, 0x80, 0xff];
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum View {
+    // the 384 tiles of VRAM, 16 in a row.
+    Tiles,
+    // the 32x32 tile map of the background or the window.
+    Map,
+    // the 40 sprites of OAM and their attributes.
+    Oam,
+    // a hex view of the memory, see memory::MemoryView.
+    Memory,
+    // the cheats of the game, to turn them on and off.
+    Cheats,
+}
+
+pub const VIEWS: [View; 5] = [
+    View::Tiles,
+    View::Map,
+    View::Oam,
+    View::Memory,
+    View::Cheats,
+];
+
+impl View {
+    pub fn name(self) -> &'static str {
+        match self {
+            View::Tiles => "Tile Viewer",
+            View::Map => "Tile Map Viewer",
+            View::Oam => "OAM Viewer",
+            View::Memory => "Memory Viewer",
+            View::Cheats => "Cheats",
+        }
+    }
+
+    // size of the frames of the view, with the label at the bottom.
+    pub fn size(self) -> (usize, usize) {
+        match self {
+            View::Tiles => (128, 192 + LABEL_HEIGHT),
+            View::Map => (256, 256 + LABEL_HEIGHT),
+            View::Oam => (OAM_COLUMN * 2, OAM_ROW * 20),
+            View::Memory => memory::size(),
+            View::Cheats => (320, 4 + (CHEAT_ROWS + 3) * LINE_HEIGHT),
+        }
+    }
+}
+
+/* one of the windows inspecting the emulator. option is the palette the tiles are shown with,
+whether the map of the background or of the window is shown, or the selected cheat. */
+pub struct Viewer {
+    pub view: View,
+    option: usize,
+    memory: MemoryView,
+}
+
+impl Viewer {
+    pub fn new(view: View) -> Self {
+        Self {
+            view,
+            option: 0,
+            memory: MemoryView::new(),
+        }
+    }
+
+    // a key pressed in the window of the viewer. P picks the next palette or map.
+    pub fn key(&mut self, name: &str, emulator: &mut Emulator) {
+        let options = match self.view {
+            View::Tiles => 4,
+            View::Map => 2,
+            View::Oam => 1,
+            View::Memory => {
+                let (mmu, freezes) = emulator.memory();
+                self.memory.key(name, mmu, freezes);
+                return;
+            }
+            // the arrows pick a cheat, enter or space turns it on or off.
+            View::Cheats => {
+                let count = emulator.cheats().len();
+                match name {
+                    "up" => self.option = self.option.saturating_sub(1),
+                    "down" => self.option = (self.option + 1).min(count.saturating_sub(1)),
+                    "enter" | "space" => {
+                        if let Err(e) = emulator.toggle_cheat(self.option) {
+                            println!("Cheats could not be saved: {}", e);
+                        }
+                    }
+                    _ => (),
+                }
+                return;
+            }
+        };
+        if name == "p" {
+            self.option = (self.option + 1) % options;
+        }
+    }
+
+    // draws the view as an RGBA frame, coloring the shades with the palettes of the config.
+    pub fn draw(&self, emulator: &Emulator, palettes: &Palettes) -> Vec<u8> {
+        let ppu = emulator.ppu();
+        let (width, height) = self.view.size();
+        let mut frame = Vec::with_capacity(width * height * 4);
+        for _ in 0..width * height {
+            frame.extend_from_slice(&[BACKGROUND[0], BACKGROUND[1], BACKGROUND[2], 0xff]);
+        }
+
+        match self.view {
+            View::Tiles => self.draw_tiles(&mut frame, ppu, palettes),
+            View::Map => self.draw_map(&mut frame, ppu, palettes),
+            View::Oam => draw_oam(&mut frame, ppu, palettes),
+            View::Memory => self
+                .memory
+                .draw(&mut frame, width, emulator.mmu(), emulator.freezes()),
+            View::Cheats => self.draw_cheats(&mut frame, emulator),
+        }
+        frame
+    }
+
+    fn draw_cheats(&self, frame: &mut [u8], emulator: &Emulator) {
+        let (width, _) = View::Cheats.size();
+        let cheats = emulator.cheats();
+        if cheats.is_empty() {
+            font::draw_text(frame, width, 2, 2, "No cheats for this game.", WHITE);
+            font::draw_text(
+                frame,
+                width,
+                2,
+                2 + LINE_HEIGHT,
+                "Use File > Import Cheats.",
+                GRAY,
+            );
+            return;
+        }
+
+        let columns = width / ADVANCE - 1;
+        let first = (self.option + 1).saturating_sub(CHEAT_ROWS);
+        for (i, cheat) in cheats.iter().enumerate().skip(first).take(CHEAT_ROWS) {
+            let selected = i == self.option;
+            let line = format!(
+                "{}[{}] {}  {}",
+                if selected { ">" } else { " " },
+                if cheat.enabled { "x" } else { " " },
+                cheat.name,
+                cheat.code
+            );
+            let line: String = line.chars().take(columns).collect();
+            let color = if selected { YELLOW } else { WHITE };
+            font::draw_text(frame, width, 2, 2 + (i - first) * LINE_HEIGHT, &line, color);
+        }
+
+        let help = "Enter turns a cheat on or off";
+        font::draw_text(
+            frame,
+            width,
+            2,
+            2 + (CHEAT_ROWS + 1) * LINE_HEIGHT,
+            help,
+            GRAY,
+        );
+    }
+
+    fn draw_tiles(&self, frame: &mut [u8], ppu: &PPU, palettes: &Palettes) {
+        let [bgp, obp_0, obp_1] = ppu.palettes();
+        // the last option shows the color numbers themselves, without a palette register.
+        let (label, register, color) = match self.option {
+            0 => ("BGP", bgp, &palettes.bg),
+            1 => ("OBP0", obp_0, &palettes.obj0),
+            2 => ("OBP1", obp_1, &palettes.obj1),
+            _ => ("None", 0b11100100, &palettes.bg),
+        };
+
+        let (width, _) = View::Tiles.size();
+        for index in 0..384 {
+            let (x, y) = ((index % 16) * 8, (index / 16) * 8);
+            draw_tile(
+                frame,
+                width,
+                (x, y),
+                &ppu.tile(index),
+                register,
+                color,
+                false,
+            );
+        }
+
+        let text = format!("Palette: {}", label);
+        font::draw_text(frame, width, 2, 192 + 2, &text, WHITE);
+    }
+
+    /* draws the map with the area on screen outlined, the scroll viewport for the background
+    and the part that is shown for the window. both wrap around the edges of the map. */
+    fn draw_map(&self, frame: &mut [u8], ppu: &PPU, palettes: &Palettes) {
+        let (width, _) = View::Map.size();
+        let register = ppu.palettes()[0];
+        let map = match self.option {
+            0 => ppu.bg_map(),
+            _ => ppu.window_map(),
+        };
+
+        for y in 0..32 {
+            for x in 0..32 {
+                let tile = ppu.tile(ppu.map_tile(map, x, y));
+                draw_tile(
+                    frame,
+                    width,
+                    (x * 8, y * 8),
+                    &tile,
+                    register,
+                    &palettes.bg,
+                    false,
+                );
+            }
+        }
+
+        let label = match self.option {
+            0 => {
+                let (scx, scy) = ppu.scroll();
+                outline(frame, width, scx as usize, scy as usize, 160, 144, RED);
+                "BG"
+            }
+            _ => {
+                if let Some((wx, wy)) = ppu.window() {
+                    let (w, h) = (
+                        167usize.saturating_sub(wx as usize),
+                        144usize.saturating_sub(wy as usize),
+                    );
+                    outline(frame, width, 0, 0, w.min(256), h.min(256), BLUE);
+                }
+                "Window"
+            }
+        };
+
+        let text = format!("{} map {:04X}", label, 0x9800 + map * 0x400);
+        font::draw_text(frame, width, 2, 256 + 2, &text, WHITE);
+    }
+}
+
+// the sprites in 2 columns, each with its tile, position, tile number and attributes.
+fn draw_oam(frame: &mut [u8], ppu: &PPU, palettes: &Palettes) {
+    let (width, _) = View::Oam.size();
+    let [_, obp_0, obp_1] = ppu.palettes();
+    let height = ppu.sprite_height();
+
+    for (i, &[y, x, tile, attributes]) in ppu.oam().iter().enumerate() {
+        let (left, top) = ((i / 20) * OAM_COLUMN, (i % 20) * OAM_ROW);
+        let palette = (attributes >> 4) & 0x1;
+        let (register, color) = match palette {
+            0 => (obp_0, &palettes.obj0),
+            _ => (obp_1, &palettes.obj1),
+        };
+
+        // 8x16 sprites ignore bit 0 of the tile number.
+        let first = if height == 16 { tile & 0xfe } else { tile } as usize;
+        for part in 0..height / 8 {
+            let pixels = ppu.tile(first + part);
+            draw_tile(
+                frame,
+                width,
+                (left + 2, top + 1 + part * 8),
+                &pixels,
+                register,
+                color,
+                true,
+            );
+        }
+
+        // sprites outside of the screen are grayed out.
+        let visible = y > 0 && y < 160 && x > 0 && x < 168;
+        let text_color = if visible { WHITE } else { GRAY };
+        let flags = format!(
+            "OBP{} {} {} {}",
+            palette,
+            if attributes & 0x20 != 0 { "XF" } else { "--" },
+            if attributes & 0x40 != 0 { "YF" } else { "--" },
+            if attributes & 0x80 != 0 { "BG" } else { "--" },
+        );
+        let position = format!("{:02} X{:3} Y{:3} T{:02X}", i, x, y, tile);
+        let text_x = left + 4 + 8 + ADVANCE;
+        font::draw_text(frame, width, text_x, top + 1, &position, text_color);
+        font::draw_text(
+            frame,
+            width,
+            text_x,
+            top + 2 + GLYPH_HEIGHT + 1,
+            &flags,
+            text_color,
+        );
+    }
+}
+
+/* draws a tile at x, y, mapping its color numbers through a palette register to the shades
+of the layer. color 0 of sprites is transparent and left as it is. */
+fn draw_tile(
+    frame: &mut [u8],
+    width: usize,
+    (x, y): (usize, usize),
+    tile: &[[u8; 8]; 8],
+    register: u8,
+    color: &Color,
+    sprite: bool,
+) {
+    for (row, pixels) in tile.iter().enumerate() {
+        for (column, &number) in pixels.iter().enumerate() {
+            if sprite && number == 0 {
+                continue;
+            }
+            let rgb = match (register >> (number * 2)) & 0x3 {
+                0 => color.id0,
+                1 => color.id1,
+                2 => color.id2,
+                _ => color.id3,
+            };
+            set(frame, width, x + column, y + row, rgb);
+        }
+    }
+}
+
+// outlines a rectangle of the 256x256 map, wrapping around its edges like the scroll does.
+fn outline(frame: &mut [u8], width: usize, x: usize, y: usize, w: usize, h: usize, color: [u8; 3]) {
+    if w == 0 || h == 0 {
+        return;
+    }
+    for i in 0..w {
+        set(frame, width, (x + i) % 256, y % 256, color);
+        set(frame, width, (x + i) % 256, (y + h - 1) % 256, color);
+    }
+    for i in 0..h {
+        set(frame, width, x % 256, (y + i) % 256, color);
+        set(frame, width, (x + w - 1) % 256, (y + i) % 256, color);
+    }
+}
+
+fn set(frame: &mut [u8], width: usize, x: usize, y: usize, rgb: [u8; 3]) {
+    let offset = (y * width + x) * 4;
+    if offset + 4 <= frame.len() && x < width {
+        frame[offset..offset + 3].copy_from_slice(&rgb);
+    }
+}
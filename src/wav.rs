@@ -0,0 +1,130 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+// size of the RIFF header up to the start of the sample data.
+const HEADER_SIZE: u32 = 44;
+
+#[derive(Copy, Clone, Debug)]
+pub enum WavFormat {
+    // 16 bit signed integer samples.
+    Pcm16,
+    // 32 bit IEEE float samples.
+    Float32,
+}
+
+impl WavFormat {
+    fn tag(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 1,
+            WavFormat::Float32 => 3,
+        }
+    }
+
+    fn bits(&self) -> u16 {
+        match self {
+            WavFormat::Pcm16 => 16,
+            WavFormat::Float32 => 32,
+        }
+    }
+}
+
+// minimal streaming WAV writer. the sizes in the header are patched in when finished.
+pub struct WavWriter {
+    file: BufWriter<File>,
+    format: WavFormat,
+    channels: u16,
+    data_size: u32,
+    finished: bool,
+}
+
+impl WavWriter {
+    pub fn create(
+        path: &Path,
+        format: WavFormat,
+        channels: u16,
+        sample_rate: u32,
+    ) -> io::Result<Self> {
+        let mut writer = Self {
+            file: BufWriter::new(File::create(path)?),
+            format,
+            channels,
+            data_size: 0,
+            finished: false,
+        };
+        writer.header(sample_rate)?;
+        Ok(writer)
+    }
+
+    fn header(&mut self, sample_rate: u32) -> io::Result<()> {
+        let block_align = self.channels * self.format.bits() / 8;
+
+        self.file.write_all(b"RIFF")?;
+        self.file.write_all(&(HEADER_SIZE - 8).to_le_bytes())?;
+        self.file.write_all(b"WAVE")?;
+
+        self.file.write_all(b"fmt ")?;
+        self.file.write_all(&16u32.to_le_bytes())?;
+        self.file.write_all(&self.format.tag().to_le_bytes())?;
+        self.file.write_all(&self.channels.to_le_bytes())?;
+        self.file.write_all(&sample_rate.to_le_bytes())?;
+        self.file
+            .write_all(&(sample_rate * block_align as u32).to_le_bytes())?;
+        self.file.write_all(&block_align.to_le_bytes())?;
+        self.file.write_all(&self.format.bits().to_le_bytes())?;
+
+        self.file.write_all(b"data")?;
+        self.file.write_all(&0u32.to_le_bytes())
+    }
+
+    // writes interleaved samples. floats are converted when the writer is 16 bit.
+    pub fn write_f32(&mut self, samples: &[f32]) -> io::Result<()> {
+        for sample in samples {
+            match self.format {
+                WavFormat::Pcm16 => {
+                    let v = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                    self.file.write_all(&v.to_le_bytes())?;
+                }
+                WavFormat::Float32 => self.file.write_all(&sample.to_le_bytes())?,
+            }
+        }
+        self.data_size += samples.len() as u32 * self.format.bits() as u32 / 8;
+        Ok(())
+    }
+
+    pub fn write_i16(&mut self, samples: &[i16]) -> io::Result<()> {
+        for sample in samples {
+            match self.format {
+                WavFormat::Pcm16 => self.file.write_all(&sample.to_le_bytes())?,
+                WavFormat::Float32 => self
+                    .file
+                    .write_all(&(*sample as f32 / i16::MAX as f32).to_le_bytes())?,
+            }
+        }
+        self.data_size += samples.len() as u32 * self.format.bits() as u32 / 8;
+        Ok(())
+    }
+
+    // patches the RIFF and data chunk sizes, leaving a valid file behind.
+    pub fn finish(&mut self) -> io::Result<()> {
+        if self.finished {
+            return Ok(());
+        }
+        self.finished = true;
+
+        self.file.seek(SeekFrom::Start(4))?;
+        self.file
+            .write_all(&(HEADER_SIZE - 8 + self.data_size).to_le_bytes())?;
+        self.file.seek(SeekFrom::Start(40))?;
+        self.file.write_all(&self.data_size.to_le_bytes())?;
+        self.file.flush()
+    }
+}
+
+impl Drop for WavWriter {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}